@@ -0,0 +1,52 @@
+//! Compare fetching devices, scenes and rooms one after another with fetching them concurrently
+//! through `Hub::snapshot`. The hub is simulated by a transport that waits before responding to
+//! mimic the latency of a hub on Wi-Fi. Run with:
+//!
+//! ```sh
+//! cargo bench --bench snapshot
+//! ```
+use dirigera::hub::Hub;
+use dirigera::transport::{ResponseFuture, Transport};
+
+use std::time::{Duration, Instant};
+
+const LATENCY: Duration = Duration::from_millis(50);
+const ITERATIONS: u32 = 20;
+
+#[derive(Debug)]
+struct SlowTransport;
+
+impl Transport for SlowTransport {
+    fn send(&self, _request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            tokio::time::sleep(LATENCY).await;
+
+            Ok(http::Response::new(hyper::Body::from("[]")))
+        })
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut hub = Hub::with_transport(SlowTransport, "127.0.0.1".parse()?, "token".to_string());
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        hub.devices().await?;
+        hub.scenes().await?;
+        hub.rooms().await?;
+    }
+    let sequential = start.elapsed() / ITERATIONS;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        hub.snapshot().await?;
+    }
+    let concurrent = start.elapsed() / ITERATIONS;
+
+    println!("latency per request: {LATENCY:?}");
+    println!("sequential:          {sequential:?}");
+    println!("snapshot:            {concurrent:?}");
+
+    Ok(())
+}
@@ -51,7 +51,7 @@ async fn toggle_light(hub: &mut dirigera::hub::Hub) -> anyhow::Result<()> {
     loop {
         println!(
             "Light is on = {}, will toggle",
-            light.inner().attributes.is_on
+            light.inner().attributes.is_on.unwrap_or(false)
         );
 
         hub.toggle_on_off(&mut light).await?;
@@ -67,10 +67,11 @@ async fn light_level(hub: &mut dirigera::hub::Hub) -> anyhow::Result<()> {
         level = if level <= 90 { level + 10 } else { 0 };
         println!(
             "Light has level = {}",
-            light.inner().attributes.light_level.unwrap_or(0),
+            light.inner().attributes.light_level.unwrap_or_default(),
         );
 
-        hub.set_light_level(&mut light, level).await?;
+        hub.set_light_level(&mut light, dirigera::units::Percent(level))
+            .await?;
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     }
 }
@@ -83,17 +84,22 @@ async fn temperature(hub: &mut dirigera::hub::Hub) -> anyhow::Result<()> {
         temp = if temp <= 3900 { temp + 100 } else { 2300 };
         println!(
             "Light has temp = {}",
-            light.inner().attributes.color_temperature.unwrap_or(0),
+            light
+                .inner()
+                .attributes
+                .color_temperature
+                .unwrap_or_default(),
         );
 
-        hub.set_temperature(&mut light, temp).await?;
+        hub.set_temperature(&mut light, dirigera::units::Kelvin(temp))
+            .await?;
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     }
 }
 
 async fn randomize_hue(hub: &mut dirigera::hub::Hub) -> anyhow::Result<()> {
     let mut light = hub.device("3b1a04db-9abe-4811-b60a-797970f51e8a_1").await?;
-    if !light.inner().attributes.is_on {
+    if !light.inner().attributes.is_on.unwrap_or(false) {
         hub.toggle_on_off(&mut light).await?;
     }
 
@@ -146,3 +152,13 @@ async fn trigger_scene(hub: &mut dirigera::hub::Hub) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+async fn listen_for_events(hub: &mut dirigera::hub::Hub) -> anyhow::Result<()> {
+    let mut events = hub.events();
+
+    while let Some(event) = events.next().await {
+        println!("{:#?}", event);
+    }
+
+    Ok(())
+}
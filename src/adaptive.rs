@@ -0,0 +1,273 @@
+//! Adaptive lighting follows the daylight outside for lights where the circadian mode of the hub
+//! isn't available. Selected lights, referenced by their name or id or by the room they're placed
+//! in, get a warm and dim light when the sun is down and a cold and bright light when it's at its
+//! highest. The light is adjusted every interval based on the [`elevation`](crate::sun::elevation)
+//! of the sun at the configured location, or the coordinates of the hub:
+//!
+//! ```no_run
+//! # async fn example(hub: dirigera::hub::Hub) -> anyhow::Result<()> {
+//! let adaptive = dirigera::adaptive::AdaptiveLighting::new()
+//!     .with_room("Living room")
+//!     .with_device("Desk lamp");
+//!
+//! adaptive
+//!     .spawn(hub, |device, result| println!("{}: {result:?}", device.inner().id))
+//!     .await??;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only lights that are turned on are adjusted so the lights can still be turned off as usual,
+//! but a changed color temperature or light level is overridden at the next interval. It's only
+//! available behind the `adaptive` feature flag.
+
+/// How often the lights are adjusted if not set with [`AdaptiveLighting::with_interval`].
+pub const DEFAULT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// The elevation in degrees where the light starts to change, the end of civil twilight.
+const TWILIGHT_ELEVATION: f64 = -6.0;
+
+/// The color temperature and light level to set at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Target {
+    pub color_temperature: crate::units::Kelvin,
+    pub light_level: crate::units::Percent,
+}
+
+/// An [`AdaptiveLighting`] adjusts the selected lights over the day.
+#[derive(Debug, Clone)]
+pub struct AdaptiveLighting {
+    devices: Vec<String>,
+    rooms: Vec<String>,
+    location: Option<(f64, f64)>,
+    warmest: crate::units::Kelvin,
+    coldest: crate::units::Kelvin,
+    min_level: crate::units::Percent,
+    max_level: crate::units::Percent,
+    interval: std::time::Duration,
+}
+
+impl Default for AdaptiveLighting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdaptiveLighting {
+    /// Create a new [`AdaptiveLighting`] without any lights, going from 2200K at 30% at night to
+    /// 4000K at 100% at noon.
+    pub fn new() -> Self {
+        Self {
+            devices: Vec::new(),
+            rooms: Vec::new(),
+            location: None,
+            warmest: crate::units::Kelvin(2200),
+            coldest: crate::units::Kelvin(4000),
+            min_level: crate::units::Percent(30),
+            max_level: crate::units::Percent::MAX,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Adjust the light with the given custom name or id.
+    pub fn with_device(mut self, device: impl Into<String>) -> Self {
+        self.devices.push(device.into());
+        self
+    }
+
+    /// Adjust all lights in the room with the given name or id.
+    pub fn with_room(mut self, room: impl Into<String>) -> Self {
+        self.rooms.push(room.into());
+        self
+    }
+
+    /// Follow the sun at the given latitude and longitude in degrees instead of the coordinates
+    /// of the hub.
+    pub fn with_location(mut self, latitude: f64, longitude: f64) -> Self {
+        self.location = Some((latitude, longitude));
+        self
+    }
+
+    /// Set the color temperature to use at night and at noon. The temperature is clamped to what
+    /// each light supports.
+    pub fn with_color_temperature(
+        mut self,
+        warmest: crate::units::Kelvin,
+        coldest: crate::units::Kelvin,
+    ) -> Self {
+        self.warmest = warmest;
+        self.coldest = coldest;
+        self
+    }
+
+    /// Set the light level to use at night and at noon.
+    pub fn with_light_level(
+        mut self,
+        min_level: crate::units::Percent,
+        max_level: crate::units::Percent,
+    ) -> Self {
+        self.min_level = min_level;
+        self.max_level = max_level;
+        self
+    }
+
+    /// Set how often the lights are adjusted. A zero interval is treated as one millisecond since
+    /// the interval can't be zero.
+    pub fn with_interval(mut self, interval: std::time::Duration) -> Self {
+        self.interval = interval.max(std::time::Duration::from_millis(1));
+        self
+    }
+
+    /// Get the [`Target`] at the given location and time. The light is at its warmest and
+    /// dimmest from the end of the evening twilight until the morning twilight starts and follows
+    /// the sun up to its highest elevation of the day.
+    pub fn target(
+        &self,
+        at: chrono::DateTime<chrono::Utc>,
+        latitude: f64,
+        longitude: f64,
+    ) -> Target {
+        let max_elevation = crate::sun::max_elevation(at.date_naive(), latitude, longitude);
+        let elevation = crate::sun::elevation(at, latitude, longitude);
+
+        let daylight = if max_elevation > TWILIGHT_ELEVATION {
+            ((elevation - TWILIGHT_ELEVATION) / (max_elevation - TWILIGHT_ELEVATION))
+                .clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let between = |from: f64, to: f64| from + (to - from) * daylight;
+
+        Target {
+            color_temperature: crate::units::Kelvin(
+                between(self.warmest.0.into(), self.coldest.0.into()).round() as u16,
+            ),
+            light_level: crate::units::Percent(
+                between(self.min_level.0.into(), self.max_level.0.into()).round() as u8,
+            ),
+        }
+    }
+
+    /// Adjust the selected lights forever. Every time a light has been adjusted `on_adjusted` is
+    /// called with the result. Lights that already have the target color temperature and light
+    /// level aren't touched. If the devices can't be fetched the lights are adjusted at the next
+    /// interval instead. Returns an error if no location is configured and the hub has no
+    /// coordinates.
+    pub async fn run<F>(&self, hub: &mut crate::hub::Hub, mut on_adjusted: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&crate::Device, anyhow::Result<()>),
+    {
+        let (latitude, longitude) = match self.location {
+            Some(location) => location,
+            None => crate::sun::hub_coordinates(hub).await.ok_or_else(|| {
+                anyhow::anyhow!("no location configured and the hub has no coordinates")
+            })?,
+        };
+
+        let mut ticker = tokio::time::interval(self.interval);
+
+        loop {
+            ticker.tick().await;
+
+            let Ok(devices) = hub.devices().await else {
+                continue;
+            };
+
+            let target = self.target(chrono::Utc::now(), latitude, longitude);
+
+            for mut device in devices
+                .into_iter()
+                .filter(|device| self.is_selected(device))
+            {
+                if let Some(result) = adjust(hub, &mut device, target).await {
+                    on_adjusted(&device, result);
+                }
+            }
+        }
+    }
+
+    /// Spawn a task running the adaptive lighting. See [`AdaptiveLighting::run`].
+    pub fn spawn<F>(
+        self,
+        mut hub: crate::hub::Hub,
+        on_adjusted: F,
+    ) -> tokio::task::JoinHandle<anyhow::Result<()>>
+    where
+        F: FnMut(&crate::Device, anyhow::Result<()>) + Send + 'static,
+    {
+        tokio::spawn(async move { self.run(&mut hub, on_adjusted).await })
+    }
+
+    fn is_selected(&self, device: &crate::Device) -> bool {
+        let inner = device.inner();
+
+        inner.device_type == crate::DeviceType::Light
+            && (self
+                .devices
+                .iter()
+                .any(|name| inner.id == *name || *name == inner.attributes.custom_name)
+                || inner.room.as_ref().is_some_and(|room| {
+                    self.rooms
+                        .iter()
+                        .any(|name| room.id == *name || *name == room.name)
+                }))
+    }
+}
+
+/// Set the [`Target`] on a light that is turned on. Returns [`None`] if nothing was changed.
+async fn adjust(
+    hub: &mut crate::hub::Hub,
+    device: &mut crate::Device,
+    target: Target,
+) -> Option<anyhow::Result<()>> {
+    use crate::device::Capability;
+
+    if device.is_on().ok() != Some(true) {
+        return None;
+    }
+
+    let attributes = &device.inner().attributes;
+
+    // The min temperature of a light is the coldest one, i.e. the highest kelvin.
+    let color_temperature = match (
+        attributes.color_temperature_min,
+        attributes.color_temperature_max,
+    ) {
+        (Some(min), Some(max)) if device.has_capability(Capability::ColorTemperature) => {
+            Some(crate::units::Kelvin(
+                target
+                    .color_temperature
+                    .0
+                    .clamp(min.0.min(max.0), min.0.max(max.0)),
+            ))
+            .filter(|temperature| attributes.color_temperature != Some(*temperature))
+        }
+        _ => None,
+    };
+
+    let light_level = Some(target.light_level).filter(|level| {
+        device.has_capability(Capability::LightLevel)
+            && level.is_valid_light_level()
+            && attributes.light_level != Some(*level)
+    });
+
+    if color_temperature.is_none() && light_level.is_none() {
+        return None;
+    }
+
+    let result = async {
+        if let Some(temperature) = color_temperature {
+            hub.set_temperature(device, temperature).await?;
+        }
+
+        if let Some(level) = light_level {
+            hub.set_light_level(device, level).await?;
+        }
+
+        Ok(())
+    }
+    .await;
+
+    Some(result)
+}
@@ -0,0 +1,122 @@
+//! An opt-in audit log of every request that changes something in the hub, f.ex. turning on a
+//! light or triggering a scene. Register an [`AuditSink`] with
+//! [`Hub::with_audit_sink`](crate::hub::Hub::with_audit_sink) and it will get an [`AuditEntry`]
+//! for each request, successful or not, so a long running daemon can answer questions such as
+//! "what turned the bedroom light on at 03:00?". [`JsonLinesAuditSink`] writes the entries as
+//! JSON, one per line, to a file or any other writer.
+use serde::Serialize;
+
+use std::sync::Mutex;
+
+/// A single mutating request sent to the hub.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub method: String,
+    /// The path of the request, f.ex. `/devices/abc123_1`.
+    pub endpoint: String,
+    /// The [`Device`](crate::Device) the request was for, if any.
+    pub device_id: Option<crate::DeviceId>,
+    /// The [`Scene`](crate::Scene) the request was for, if any.
+    pub scene_id: Option<crate::SceneId>,
+    /// The JSON body sent to the hub, f.ex. the attributes that was changed.
+    pub body: Option<serde_json::Value>,
+    /// The status the hub responded with, if it responded at all.
+    pub status: Option<u16>,
+    /// The error if the request failed.
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub(crate) fn new(
+        method: &http::Method,
+        endpoint: &str,
+        body: Option<serde_json::Value>,
+        result: &anyhow::Result<http::Response<hyper::Body>>,
+    ) -> Self {
+        let mut segments = endpoint.trim_start_matches('/').split('/');
+        let (device_id, scene_id) = match (segments.next(), segments.next()) {
+            (Some("devices"), Some(id)) => (Some(id.into()), None),
+            (Some("scenes"), Some(id)) => (None, Some(id.into())),
+            _ => (None, None),
+        };
+
+        let (status, error) = match result {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None)
+            }
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                Some(response.status().to_string()),
+            ),
+            Err(err) => (None, Some(err.to_string())),
+        };
+
+        Self {
+            timestamp: chrono::Utc::now(),
+            method: method.to_string(),
+            endpoint: endpoint.to_string(),
+            device_id,
+            scene_id,
+            body,
+            status,
+            error,
+        }
+    }
+
+    /// Check if the request was successful.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// An [`AuditSink`] receives an [`AuditEntry`] for every mutating request sent by the
+/// [`Hub`](crate::hub::Hub). Recording must not block for long since it's done before the
+/// response is returned to the caller.
+pub trait AuditSink: std::fmt::Debug + Send + Sync {
+    fn record(&self, entry: &AuditEntry);
+}
+
+/// An [`AuditSink`] writing each [`AuditEntry`] as a line of JSON. Entries that can't be written
+/// are dropped.
+#[derive(Debug)]
+pub struct JsonLinesAuditSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: std::io::Write> JsonLinesAuditSink<W> {
+    /// Create a new [`JsonLinesAuditSink`] writing to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl JsonLinesAuditSink<std::fs::File> {
+    /// Create a new [`JsonLinesAuditSink`] appending to the file at `path`, creating it if it
+    /// doesn't exist.
+    pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        Ok(Self::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        ))
+    }
+}
+
+impl<W: std::io::Write + std::fmt::Debug + Send> AuditSink for JsonLinesAuditSink<W> {
+    fn record(&self, entry: &AuditEntry) {
+        let mut writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if serde_json::to_writer(&mut *writer, entry).is_ok() {
+            let _ = writeln!(writer);
+            let _ = writer.flush();
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Obtain a token for the hub with the same OAuth 2 flow as the `generate-token` binary, so apps
+//! can include onboarding with their own UI. The flow asks the hub for a code, waits for the
+//! action button on the hub to be pressed and then exchanges the code for a token:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! let ip_address = "192.168.1.101".parse()?;
+//! let token = dirigera::auth::obtain_token(ip_address, "my-app", || async {
+//!     println!("Press the action button on the hub within 60 seconds");
+//!     tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+//!     Ok(())
+//! })
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The hub doesn't have a token yet so its certificate can't be pinned and isn't verified. This
+//! doesn't enable the `danger-accept-invalid-certs` feature flag, so a [`Hub`](crate::hub::Hub)
+//! still verifies the certificate. It's only available behind the `auth` feature flag.
+use serde::Deserialize;
+
+const AUTH_PORT: u16 = 8443;
+
+#[derive(Debug, Deserialize)]
+struct AuthorizeResponse {
+    code: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Get the name of this computer to use as client name, or `localhost` if it can't be read.
+pub fn default_client_name() -> String {
+    let hostname = gethostname::gethostname();
+    let hostname = hostname.to_string_lossy();
+
+    if hostname.trim().is_empty() {
+        "localhost".to_string()
+    } else {
+        hostname.to_string()
+    }
+}
+
+/// Obtain a new token from the hub at `ip_address`. `wait_for_button` is called once the hub is
+/// waiting for its action button to be pressed and should return when the user says it's pressed,
+/// f.ex. after pressing enter or clicking a button. The token is listed as `client_name` among the
+/// paired clients in the hub, so use a name that tells what app or computer it's for to be able
+/// to revoke it later, f.ex. [`default_client_name`].
+pub async fn obtain_token<F, Fut>(
+    ip_address: std::net::Ipv4Addr,
+    client_name: &str,
+    wait_for_button: F,
+) -> anyhow::Result<String>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<()>>,
+{
+    let https = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(crate::danger::no_verify())
+        .https_only()
+        .enable_http1()
+        .build();
+    let client: hyper::Client<_, hyper::Body> = hyper::Client::builder().build(https);
+
+    let code_verifier = String::from_utf8(pkce::code_verifier(128))?;
+    let code_challenge = pkce::code_challenge(code_verifier.as_bytes());
+
+    let request = http::Request::builder()
+        .method(http::Method::GET)
+        .uri(format!(
+            "https://{ip_address}:{AUTH_PORT}/v1/oauth/authorize?audience=homesmart.local&response_type=code&code_challenge={code_challenge}&code_challenge_method=S256"
+        ))
+        .body(hyper::Body::empty())?;
+
+    let AuthorizeResponse { code } = send(&client, request).await?;
+
+    wait_for_button().await?;
+
+    let body = serde_json::json!({
+        "code": code,
+        "name": client_name,
+        "grant_type": "authorization_code",
+        "code_verifier": code_verifier,
+    });
+
+    let request = http::Request::builder()
+        .method(http::Method::POST)
+        .uri(format!("https://{ip_address}:{AUTH_PORT}/v1/oauth/token"))
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(serde_json::to_vec(&body)?))?;
+
+    let TokenResponse { access_token } = send(&client, request).await?;
+
+    Ok(access_token)
+}
+
+async fn send<T: serde::de::DeserializeOwned>(
+    client: &hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>, hyper::Body>,
+    request: http::Request<hyper::Body>,
+) -> anyhow::Result<T> {
+    let path = request.uri().path().to_string();
+    let response = client.request(request).await?;
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+
+    if !status.is_success() {
+        anyhow::bail!(
+            "{path} responded with {status}: {}",
+            String::from_utf8_lossy(&body)
+        );
+    }
+
+    serde_json::from_slice(&body).map_err(|err| {
+        anyhow::anyhow!(
+            "unexpected response from {path}: {err}: {}",
+            String::from_utf8_lossy(&body)
+        )
+    })
+}
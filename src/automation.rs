@@ -0,0 +1,288 @@
+//! Automations are rules that are executed by this crate rather than by the hub. Each [`Rule`]
+//! has a [`Trigger`] at a specific time of day or relative to sunrise or sunset and a list of
+//! [`Action`]s to apply to devices referenced by their name or id. This makes it possible to run a
+//! headless alternative to the automations in the IKEA app, configured from a `toml` file:
+//!
+//! ```toml
+//! latitude = 59.33
+//! longitude = 18.07
+//!
+//! [[rule]]
+//! name = "Evening lights"
+//! trigger = { type = "sunset", offset = -15 }
+//! actions = [{ device = "Living room lamp", is-on = true, light-level = 60 }]
+//!
+//! [[rule]]
+//! name = "Bedtime"
+//! trigger = { type = "time", at = "22:30", days = ["Sun", "Mon", "Tue", "Wed", "Thu"] }
+//! actions = [{ device = "Living room lamp", is-on = false }]
+//! ```
+//!
+//! If no location is configured the coordinates of the hub will be used for sunrise and sunset.
+//! Times and days are in the timezone of the hub unless another IANA `timezone` is configured,
+//! f.ex. `timezone = "Europe/Stockholm"`.
+//! It's only available behind the `automation` feature flag.
+use crate::deserialize_time;
+use crate::scene::Weekday;
+use chrono::Datelike;
+use serde::Deserialize;
+
+use std::collections::BTreeSet;
+
+/// A set of [`Rule`]s together with the location used to calculate sunrise and sunset and the
+/// timezone the rules are evaluated in.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Automation {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub timezone: Option<chrono_tz::Tz>,
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+}
+
+/// A [`Rule`] will apply all its [`Action`]s each time the [`Trigger`] fires.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Rule {
+    pub name: String,
+    #[serde(default)]
+    pub disabled: bool,
+    pub trigger: Trigger,
+    pub actions: Vec<Action>,
+}
+
+/// A [`Trigger`] fires at a specific time or relative to sunrise or sunset. The offset for sunrise
+/// and sunset is in minutes and can be negative. If no days are set the trigger fires every day.
+/// Times are in the timezone of the [`Automation`], which is the timezone of the hub by default.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "type")]
+pub enum Trigger {
+    Time {
+        #[serde(deserialize_with = "deserialize_time")]
+        at: chrono::NaiveTime,
+        days: Option<BTreeSet<Weekday>>,
+    },
+    Sunrise {
+        #[serde(default)]
+        offset: i64,
+        days: Option<BTreeSet<Weekday>>,
+    },
+    Sunset {
+        #[serde(default)]
+        offset: i64,
+        days: Option<BTreeSet<Weekday>>,
+    },
+}
+
+/// An [`Action`] changes the state of a single [`Device`](crate::Device), referenced by either its
+/// custom name or its id. Only the attributes that are set will be changed.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Action {
+    pub device: String,
+    pub is_on: Option<bool>,
+    pub light_level: Option<crate::units::Percent>,
+    pub color_temperature: Option<crate::units::Kelvin>,
+    pub blinds_target_level: Option<crate::units::Percent>,
+}
+
+/// A location given as latitude and longitude in degrees.
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    latitude: f64,
+    longitude: f64,
+}
+
+impl std::str::FromStr for Automation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).map_err(|err| anyhow::anyhow!(err))
+    }
+}
+
+impl Automation {
+    /// Read an [`Automation`] from a `toml` file.
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Run the automation forever. Every time a [`Rule`] has been executed `on_executed` is
+    /// called with the result. A failing [`Action`] doesn't stop the other actions in the same
+    /// rule from being applied. Returns an error if the timezone of the hub can't be fetched or if
+    /// no rule can ever trigger, f.ex. if there are only sunrise and sunset rules and no location
+    /// could be found.
+    pub async fn run<F>(&self, hub: &mut crate::hub::Hub, mut on_executed: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&Rule, anyhow::Result<()>),
+    {
+        let location = if self.rules.iter().any(|rule| rule.trigger.follows_sun()) {
+            self.location(hub).await
+        } else {
+            None
+        };
+
+        let timezone = self.timezone(hub).await?;
+        let mut after = chrono::Utc::now().with_timezone(&timezone);
+
+        loop {
+            let Some(next) = self
+                .rules
+                .iter()
+                .filter(|rule| !rule.disabled)
+                .filter_map(|rule| rule.trigger.next_occurrence(&after, location))
+                .min()
+            else {
+                anyhow::bail!("no rule will ever trigger");
+            };
+
+            let wait = (next.to_utc() - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default();
+            tokio::time::sleep(wait).await;
+
+            for rule in self.rules.iter().filter(|rule| !rule.disabled) {
+                if rule.trigger.next_occurrence(&after, location) == Some(next) {
+                    on_executed(rule, rule.execute(hub).await);
+                }
+            }
+
+            after = next;
+        }
+    }
+
+    /// Spawn a task running the automation. See [`Automation::run`].
+    pub fn spawn<F>(
+        self,
+        mut hub: crate::hub::Hub,
+        on_executed: F,
+    ) -> tokio::task::JoinHandle<anyhow::Result<()>>
+    where
+        F: FnMut(&Rule, anyhow::Result<()>) + Send + 'static,
+    {
+        tokio::spawn(async move { self.run(&mut hub, on_executed).await })
+    }
+
+    /// Use the configured timezone or fall back to the timezone of the hub, so the date used for
+    /// sunrise and sunset is the date where the hub is.
+    async fn timezone(&self, hub: &mut crate::hub::Hub) -> anyhow::Result<chrono_tz::Tz> {
+        if let Some(timezone) = self.timezone {
+            return Ok(timezone);
+        }
+
+        let timezone = hub.time().await?.timezone;
+        timezone
+            .parse()
+            .map_err(|_| anyhow::anyhow!("unknown timezone {timezone} configured in the hub"))
+    }
+
+    /// Use the configured location or fall back to the coordinates of the hub.
+    async fn location(&self, hub: &mut crate::hub::Hub) -> Option<Location> {
+        if let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) {
+            return Some(Location {
+                latitude,
+                longitude,
+            });
+        }
+
+        let (latitude, longitude) = crate::sun::hub_coordinates(hub).await?;
+
+        Some(Location {
+            latitude,
+            longitude,
+        })
+    }
+}
+
+impl Rule {
+    /// Apply all [`Action`]s of the rule now, regardless of the [`Trigger`]. Returns the first
+    /// error if any of the actions failed.
+    pub async fn execute(&self, hub: &mut crate::hub::Hub) -> anyhow::Result<()> {
+        let mut devices = hub.devices().await?;
+        let mut result = Ok(());
+
+        for action in &self.actions {
+            let outcome = match devices.iter_mut().find(|device| {
+                let inner = device.inner();
+                inner.id == action.device || inner.attributes.custom_name == action.device
+            }) {
+                Some(device) => action.apply(hub, device).await,
+                None => Err(anyhow::anyhow!("no device named {}", action.device)),
+            };
+
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+
+        result
+    }
+}
+
+impl Trigger {
+    fn follows_sun(&self) -> bool {
+        matches!(self, Self::Sunrise { .. } | Self::Sunset { .. })
+    }
+
+    fn next_occurrence<Tz: chrono::TimeZone>(
+        &self,
+        after: &chrono::DateTime<Tz>,
+        location: Option<Location>,
+    ) -> Option<chrono::DateTime<Tz>> {
+        let (days, offset, sun_event): (_, _, fn(_, _, _) -> _) = match self {
+            Self::Time { at, days } => {
+                return crate::scene::Time {
+                    days: days.clone(),
+                    time: *at,
+                }
+                .next_occurrence(after)
+            }
+            Self::Sunrise { offset, days } => (days, offset, crate::sun::sunrise),
+            Self::Sunset { offset, days } => (days, offset, crate::sun::sunset),
+        };
+
+        let location = location?;
+        let today = after.date_naive();
+
+        (0..=7)
+            .map(|offset| today + chrono::Duration::days(offset))
+            .filter(|date| {
+                days.as_ref()
+                    .is_none_or(|days| days.is_empty() || days.contains(&date.weekday().into()))
+            })
+            .filter_map(|date| sun_event(date, location.latitude, location.longitude))
+            // A trigger with an offset too large to add never fires.
+            .filter_map(|time| {
+                time.with_timezone(&after.timezone())
+                    .checked_add_signed(chrono::Duration::try_minutes(*offset)?)
+            })
+            .find(|candidate| candidate > after)
+    }
+}
+
+impl Action {
+    async fn apply(
+        &self,
+        hub: &mut crate::hub::Hub,
+        device: &mut crate::Device,
+    ) -> anyhow::Result<()> {
+        if let Some(is_on) = self.is_on {
+            hub.set_on(device, is_on).await?;
+        }
+
+        if let Some(level) = self.light_level {
+            hub.set_light_level(device, level).await?;
+        }
+
+        if let Some(temperature) = self.color_temperature {
+            hub.set_temperature(device, temperature).await?;
+        }
+
+        if let Some(level) = self.blinds_target_level {
+            hub.set_target_level(device, level).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+//! A command line interface to the hub. It reads the `config.toml` created by `generate-token`
+//! from the current directory.
+mod pair;
+mod sensors;
+#[cfg(feature = "serve")]
+mod serve;
+#[cfg(feature = "tui")]
+mod tui;
+
+const USAGE: &str = "Usage: dirigera <command>
+
+Commands:
+  pair     Pair a new device and give it a name and a room
+  sensors  Show values from all sensors [--interval 30s] [--json]
+  serve    Serve a local HTTP API [--listen 127.0.0.1:8080] [--token <token>] [--openapi] (requires the `serve` feature)
+  tui      Show rooms, devices and scenes with live state (requires the `tui` feature)";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let hub = || {
+        let config = dirigera::hub::Config::from_path(dirigera::hub::Config::DEFAULT_PATH)?;
+        dirigera::hub::Hub::try_from_config(config)
+    };
+
+    match args.first().map(String::as_str) {
+        Some("pair") => pair::run(hub()?).await,
+        Some("sensors") => sensors::run(hub()?, &args[1..]).await,
+        #[cfg(feature = "serve")]
+        Some("serve") if args.iter().any(|arg| arg == "--openapi") => serve::print_openapi(),
+        #[cfg(feature = "serve")]
+        Some("serve") => serve::run(hub()?, &args[1..]).await,
+        #[cfg(feature = "tui")]
+        Some("tui") => tui::run(hub()?).await,
+        Some("-h" | "--help") => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(command) => anyhow::bail!("unknown command '{command}'\n\n{USAGE}"),
+        None => anyhow::bail!("{USAGE}"),
+    }
+}
@@ -0,0 +1,117 @@
+//! Pair a new device: let the gateway accept new devices, wait for one to be added and give it a
+//! name and a room.
+use std::io::Write;
+
+/// How long to wait for a new device before giving up.
+const PAIR_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(180);
+
+/// How long to wait for the event listener to connect before giving up.
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+pub async fn run(mut hub: dirigera::hub::Hub) -> anyhow::Result<()> {
+    let mut gateway = hub
+        .devices()
+        .await?
+        .into_iter()
+        .find(|device| matches!(device, dirigera::Device::Gateway(_)))
+        .ok_or_else(|| anyhow::anyhow!("no gateway found"))?;
+
+    // Be connected before permitting join so the new device isn't missed.
+    let mut events = hub.events();
+    match tokio::time::timeout(CONNECT_TIMEOUT, events.connected()).await {
+        Ok(true) => {}
+        Ok(false) => anyhow::bail!("lost connection to the hub"),
+        Err(_) => anyhow::bail!(
+            "failed to listen for events within {} seconds",
+            CONNECT_TIMEOUT.as_secs()
+        ),
+    }
+
+    hub.set_permitting_join(&mut gateway, true).await?;
+    println!(
+        "Put the device in pairing mode, waiting {} seconds...",
+        PAIR_TIMEOUT.as_secs()
+    );
+
+    let added = tokio::select! {
+        added = tokio::time::timeout(PAIR_TIMEOUT, added_device(&mut events)) => match added {
+            Ok(Some(id)) => Ok(id),
+            Ok(None) => Err(anyhow::anyhow!("lost connection to the hub")),
+            Err(_) => Err(anyhow::anyhow!(
+                "no device was added within {} seconds",
+                PAIR_TIMEOUT.as_secs()
+            )),
+        },
+        _ = tokio::signal::ctrl_c() => Err(anyhow::anyhow!("pairing was cancelled")),
+    };
+
+    // Always stop accepting new devices, even if nothing was paired or pairing was cancelled.
+    hub.set_permitting_join(&mut gateway, false).await?;
+
+    let id = added?;
+
+    let mut device = hub.device(&id).await?;
+    println!(
+        "🎉 Found {} {}",
+        device.inner().device_type,
+        device.inner().attributes.model
+    );
+
+    let name = prompt("Name: ")?;
+    if !name.is_empty() {
+        hub.rename(&mut device, &name).await?;
+    }
+
+    let mut rooms = hub.rooms().await?;
+    rooms.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for (i, room) in rooms.iter().enumerate() {
+        println!("{:>3}. {}", i + 1, room.name);
+    }
+
+    let choice = prompt("Room (leave empty to skip): ")?;
+    if !choice.is_empty() {
+        let room = choice
+            .parse::<usize>()
+            .ok()
+            .and_then(|choice| rooms.get(choice.checked_sub(1)?))
+            .ok_or_else(|| anyhow::anyhow!("invalid room '{choice}'"))?;
+
+        hub.set_room(&mut device, room).await?;
+    }
+
+    println!(
+        "Paired '{}' with id {}",
+        device.inner().attributes.custom_name,
+        device.inner().id
+    );
+
+    Ok(())
+}
+
+/// Wait for a device to be added and get its id. Returns [`None`] if the listener stopped.
+async fn added_device(events: &mut dirigera::events::EventListener) -> Option<String> {
+    while let Some(event) = events.next().await {
+        let dirigera::events::Event::Hub(event) = event else {
+            continue;
+        };
+
+        if event.event_type == "deviceAdded" {
+            if let Some(id) = event.device_id() {
+                return Some(id.to_string());
+            }
+        }
+    }
+
+    None
+}
+
+fn prompt(question: &str) -> anyhow::Result<String> {
+    print!("{question}");
+    std::io::stdout().flush()?;
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+
+    Ok(input.trim().to_string())
+}
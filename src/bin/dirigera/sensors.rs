@@ -0,0 +1,146 @@
+//! Show the latest values from all sensors, refreshed on an interval.
+use serde::Serialize;
+
+/// How often sensors are refetched if no `--interval` is given.
+const DEFAULT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// The values of a single sensor. Values the sensor doesn't measure are `None`.
+#[derive(Debug, Serialize)]
+struct Reading {
+    id: String,
+    name: String,
+    room: Option<String>,
+    temperature: Option<dirigera::units::Celsius>,
+    humidity: Option<dirigera::units::Percent>,
+    pm25: Option<u16>,
+    voc_index: Option<u16>,
+    is_open: Option<bool>,
+    battery: Option<i8>,
+}
+
+impl Reading {
+    /// Get the [`Reading`] for the device, if it's a sensor.
+    fn from_device(device: &dirigera::Device) -> Option<Self> {
+        let inner = device.inner();
+        let attributes = &inner.attributes;
+
+        let reading = Self {
+            id: inner.id.to_string(),
+            name: attributes.custom_name.clone(),
+            room: inner.room.as_ref().map(|room| room.name.clone()),
+            temperature: attributes.current_temperature,
+            humidity: attributes.current_r_h,
+            pm25: attributes.current_p_m25,
+            voc_index: attributes.voc_index,
+            is_open: attributes.is_open,
+            battery: attributes.battery_percentage,
+        };
+
+        let is_sensor = reading.temperature.is_some()
+            || reading.humidity.is_some()
+            || reading.pm25.is_some()
+            || reading.voc_index.is_some()
+            || reading.is_open.is_some();
+
+        is_sensor.then_some(reading)
+    }
+}
+
+pub async fn run(mut hub: dirigera::hub::Hub, args: &[String]) -> anyhow::Result<()> {
+    let mut interval = DEFAULT_INTERVAL;
+    let mut json = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--interval" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--interval needs a value, f.ex. 30s"))?;
+                interval = parse_interval(value)?;
+            }
+            _ => anyhow::bail!("unknown argument '{arg}'"),
+        }
+    }
+
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        // A failed poll, f.ex. while the hub restarts, is tried again at the next refresh.
+        let devices = match hub.devices().await {
+            Ok(devices) => devices,
+            Err(err) => {
+                eprintln!("failed to get devices: {err}");
+                continue;
+            }
+        };
+
+        let mut readings: Vec<_> = devices.iter().filter_map(Reading::from_device).collect();
+        readings.sort_by(|a, b| (&a.room, &a.name).cmp(&(&b.room, &b.name)));
+
+        if json {
+            // One line per refresh to make it easy to pipe into other tools.
+            println!("{}", serde_json::to_string(&readings)?);
+        } else {
+            // Clear the screen and move the cursor to the top before printing the table again.
+            print!("\x1b[2J\x1b[H");
+            print_table(&readings);
+        }
+    }
+}
+
+fn print_table(readings: &[Reading]) {
+    fn show<T: std::fmt::Display>(value: Option<T>) -> String {
+        value.map_or("-".to_string(), |value| value.to_string())
+    }
+
+    println!(
+        "{:<16} {:<24} {:>10} {:>8} {:>6} {:>5} {:>7} {:>7}",
+        "ROOM", "NAME", "TEMP", "HUMIDITY", "PM2.5", "VOC", "OPEN", "BATTERY"
+    );
+
+    for reading in readings {
+        println!(
+            "{:<16} {:<24} {:>10} {:>8} {:>6} {:>5} {:>7} {:>7}",
+            reading.room.as_deref().unwrap_or("-"),
+            reading.name,
+            show(reading.temperature),
+            show(reading.humidity),
+            show(reading.pm25),
+            show(reading.voc_index),
+            show(
+                reading
+                    .is_open
+                    .map(|is_open| if is_open { "open" } else { "closed" })
+            ),
+            show(reading.battery.map(|battery| format!("{battery}%"))),
+        );
+    }
+}
+
+/// Parse an interval such as `30s`, `5m` or `1h`. A number without unit is seconds.
+fn parse_interval(value: &str) -> anyhow::Result<std::time::Duration> {
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 's')) => (&value[..i], 1),
+        Some((i, 'm')) => (&value[..i], 60),
+        Some((i, 'h')) => (&value[..i], 60 * 60),
+        _ => (value, 1),
+    };
+
+    let seconds: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid interval '{value}', use f.ex. 30s or 5m"))?;
+
+    if seconds == 0 {
+        anyhow::bail!("interval must be more than zero");
+    }
+
+    let seconds = seconds
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow::anyhow!("interval '{value}' is too long"))?;
+
+    Ok(std::time::Duration::from_secs(seconds))
+}
@@ -0,0 +1,383 @@
+//! A local HTTP API in front of the hub so services that can't speak its TLS and auth can list
+//! devices, change their state and trigger scenes. Every request must have the token passed with
+//! `--token`, or set in `DIRIGERA_SERVE_TOKEN`, as bearer token. An OpenAPI document describing
+//! the API is served without token at `/openapi.json`, or printed with `--openapi`.
+use axum::extract::{Path, Request, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+/// The address to listen on if no `--listen` is given.
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+#[derive(Clone)]
+struct AppState {
+    hub: dirigera::hub::Hub,
+    token: std::sync::Arc<str>,
+}
+
+/// The state of a device with only the attributes most clients care about.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct DeviceState {
+    id: String,
+    name: String,
+    device_type: String,
+    room: Option<String>,
+    is_reachable: bool,
+    is_on: Option<bool>,
+    light_level: Option<dirigera::units::Percent>,
+    color_temperature: Option<dirigera::units::Kelvin>,
+    blinds_level: Option<dirigera::units::Percent>,
+}
+
+impl From<&dirigera::Device> for DeviceState {
+    fn from(device: &dirigera::Device) -> Self {
+        let inner = device.inner();
+        let attributes = &inner.attributes;
+
+        Self {
+            id: inner.id.to_string(),
+            name: attributes.custom_name.clone(),
+            device_type: inner.device_type.to_string(),
+            room: inner.room.as_ref().map(|room| room.name.clone()),
+            is_reachable: inner.is_reachable,
+            is_on: attributes.is_on,
+            light_level: attributes.light_level,
+            color_temperature: attributes.color_temperature,
+            blinds_level: attributes.blinds_current_level,
+        }
+    }
+}
+
+/// The attributes to change on a device. Attributes that are left out are kept as they are.
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct StateChange {
+    is_on: Option<bool>,
+    light_level: Option<dirigera::units::Percent>,
+    color_temperature: Option<dirigera::units::Kelvin>,
+    blinds_level: Option<dirigera::units::Percent>,
+}
+
+impl StateChange {
+    fn commands(&self) -> Vec<dirigera::command::Command> {
+        use dirigera::command::Command;
+
+        let mut commands = Vec::new();
+
+        // Turn the light on before and off after changing the level so the change is seen.
+        if self.is_on == Some(true) {
+            commands.push(Command::TurnOn);
+        }
+
+        if let Some(level) = self.light_level {
+            commands.push(Command::SetLightLevel(level));
+        }
+
+        if let Some(temperature) = self.color_temperature {
+            commands.push(Command::SetColorTemperature(temperature));
+        }
+
+        if let Some(level) = self.blinds_level {
+            commands.push(Command::SetBlindsLevel(level));
+        }
+
+        if self.is_on == Some(false) {
+            commands.push(Command::TurnOff);
+        }
+
+        commands
+    }
+}
+
+/// A scene that can be triggered.
+#[derive(Debug, Serialize, schemars::JsonSchema)]
+struct SceneSummary {
+    id: String,
+    name: String,
+}
+
+/// An error returned as `{"error": "..."}`. Everything but a resource the hub couldn't find is a
+/// bad gateway since the request couldn't be completed by the hub, the status the hub responded
+/// with is included as `upstreamStatus`. Passing the status on as is would f.ex. make a rejected
+/// hub token look like a wrong token for this API.
+struct ApiError(anyhow::Error);
+
+/// The body of an [`ApiError`], only used for the OpenAPI document.
+#[derive(schemars::JsonSchema)]
+#[schemars(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct ErrorResponse {
+    error: String,
+    upstream_status: Option<u16>,
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let upstream_status = self
+            .0
+            .downcast_ref::<dirigera::error::ApiError>()
+            .map(|err| err.status.as_u16());
+
+        let status = match upstream_status {
+            Some(404) => StatusCode::NOT_FOUND,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+
+        let mut body = serde_json::json!({ "error": format!("{:#}", self.0) });
+        if let Some(upstream_status) = upstream_status {
+            body["upstreamStatus"] = upstream_status.into();
+        }
+
+        (status, Json(body)).into_response()
+    }
+}
+
+pub async fn run(hub: dirigera::hub::Hub, args: &[String]) -> anyhow::Result<()> {
+    let mut listen = DEFAULT_LISTEN.to_string();
+    let mut token = std::env::var("DIRIGERA_SERVE_TOKEN").ok();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => {
+                listen = args
+                    .next()
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("--listen needs an address, f.ex. {DEFAULT_LISTEN}")
+                    })?
+                    .clone();
+            }
+            "--token" => {
+                token = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow::anyhow!("--token needs a value"))?
+                        .clone(),
+                );
+            }
+            _ => anyhow::bail!("unknown argument '{arg}'"),
+        }
+    }
+
+    let token = token.filter(|token| !token.is_empty()).ok_or_else(|| {
+        anyhow::anyhow!("a token is required, use --token or DIRIGERA_SERVE_TOKEN")
+    })?;
+
+    let state = AppState {
+        hub,
+        token: token.into(),
+    };
+
+    let app = Router::new()
+        .route("/devices", get(list_devices))
+        .route("/devices/:id", get(get_device).patch(set_device_state))
+        .route("/scenes", get(list_scenes))
+        .route("/scenes/:id/trigger", post(trigger_scene))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            require_token,
+        ))
+        .route("/openapi.json", get(|| async { Json(openapi()) }))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&listen).await?;
+    println!("Listening on http://{}", listener.local_addr()?);
+
+    axum::serve(listener, app).await?;
+
+    Ok(())
+}
+
+async fn require_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), state.token.as_bytes()));
+
+    if !authorized {
+        let body = serde_json::json!({ "error": "missing or invalid token" });
+        return (StatusCode::UNAUTHORIZED, Json(body)).into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Compare two tokens in a time that only depends on their length so the token can't be guessed
+/// by timing the responses.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn list_devices(
+    State(mut state): State<AppState>,
+) -> Result<Json<Vec<DeviceState>>, ApiError> {
+    let devices = state.hub.devices().await?;
+
+    Ok(Json(devices.iter().map(DeviceState::from).collect()))
+}
+
+async fn get_device(
+    State(mut state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<DeviceState>, ApiError> {
+    let device = state.hub.device(&id).await?;
+
+    Ok(Json(DeviceState::from(&device)))
+}
+
+async fn set_device_state(
+    State(mut state): State<AppState>,
+    Path(id): Path<String>,
+    Json(change): Json<StateChange>,
+) -> Result<Json<DeviceState>, ApiError> {
+    let mut device = state.hub.device(&id).await?;
+
+    for command in change.commands() {
+        state.hub.execute(&mut device, &command).await?;
+    }
+
+    Ok(Json(DeviceState::from(&device)))
+}
+
+async fn list_scenes(
+    State(mut state): State<AppState>,
+) -> Result<Json<Vec<SceneSummary>>, ApiError> {
+    let scenes = state.hub.scenes().await?;
+
+    Ok(Json(
+        scenes
+            .iter()
+            .map(|scene| SceneSummary {
+                id: scene.inner().id.to_string(),
+                name: scene.inner().info.name.clone(),
+            })
+            .collect(),
+    ))
+}
+
+async fn trigger_scene(
+    State(mut state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let scene = state.hub.scene(&id).await?;
+    state.hub.trigger_scene(&scene).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Print the OpenAPI document, used for `dirigera serve --openapi`.
+pub fn print_openapi() -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(&openapi())?);
+
+    Ok(())
+}
+
+/// Create the OpenAPI document for all routes. The schemas are generated from the same types that
+/// are used to serialize the requests and responses.
+fn openapi() -> serde_json::Value {
+    let mut generator = schemars::gen::SchemaSettings::openapi3().into_generator();
+
+    let device = generator.subschema_for::<DeviceState>();
+    let devices = generator.subschema_for::<Vec<DeviceState>>();
+    let change = generator.subschema_for::<StateChange>();
+    let scenes = generator.subschema_for::<Vec<SceneSummary>>();
+    let error = generator.subschema_for::<ErrorResponse>();
+    let schemas = generator.take_definitions();
+
+    let json = |schema: &schemars::schema::Schema| serde_json::json!({ "content": { "application/json": { "schema": schema } } });
+    let ok = |description: &str, schema: &schemars::schema::Schema| {
+        let mut response = json(schema);
+        response["description"] = description.into();
+        response
+    };
+    let errors = serde_json::json!({
+        "401": { "description": "Missing or invalid token", "content": { "application/json": { "schema": error } } },
+        "404": { "description": "The hub couldn't find the resource", "content": { "application/json": { "schema": error } } },
+        "502": { "description": "The hub failed to handle the request", "content": { "application/json": { "schema": error } } },
+    });
+    let with_errors = |mut responses: serde_json::Value| {
+        if let (Some(responses), Some(errors)) = (responses.as_object_mut(), errors.as_object()) {
+            responses.extend(errors.clone());
+        }
+
+        responses
+    };
+    let id = |description: &str| {
+        serde_json::json!([{
+            "name": "id",
+            "in": "path",
+            "required": true,
+            "description": description,
+            "schema": { "type": "string" },
+        }])
+    };
+
+    serde_json::json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "dirigera",
+            "description": "A local HTTP API in front of an IKEA Dirigera hub.",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "security": [{ "token": [] }],
+        "paths": {
+            "/devices": {
+                "get": {
+                    "summary": "List all devices",
+                    "operationId": "listDevices",
+                    "responses": with_errors(serde_json::json!({ "200": ok("All devices", &devices) })),
+                },
+            },
+            "/devices/{id}": {
+                "parameters": id("The id of the device"),
+                "get": {
+                    "summary": "Get a single device",
+                    "operationId": "getDevice",
+                    "responses": with_errors(serde_json::json!({ "200": ok("The device", &device) })),
+                },
+                "patch": {
+                    "summary": "Change the state of a device",
+                    "operationId": "setDeviceState",
+                    "requestBody": json(&change),
+                    "responses": with_errors(serde_json::json!({ "200": ok("The device with the new state", &device) })),
+                },
+            },
+            "/scenes": {
+                "get": {
+                    "summary": "List all scenes",
+                    "operationId": "listScenes",
+                    "responses": with_errors(serde_json::json!({ "200": ok("All scenes", &scenes) })),
+                },
+            },
+            "/scenes/{id}/trigger": {
+                "parameters": id("The id of the scene"),
+                "post": {
+                    "summary": "Trigger a scene",
+                    "operationId": "triggerScene",
+                    "responses": with_errors(serde_json::json!({ "204": { "description": "The scene was triggered" } })),
+                },
+            },
+        },
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "token": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
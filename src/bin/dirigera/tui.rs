@@ -0,0 +1,342 @@
+//! An interactive dashboard showing all devices, grouped by room, and all scenes. Device state is
+//! kept up to date with the events from the hub.
+use ratatui::crossterm::event::{self, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+
+const KEYS: &str =
+    "↑/↓ select  tab switch list  space toggle/trigger  +/- brightness  r refresh  q quit";
+
+/// How much the brightness changes for each press on `+` or `-`.
+const BRIGHTNESS_STEP: u8 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Devices,
+    Scenes,
+}
+
+struct App {
+    hub: dirigera::hub::Hub,
+    devices: Vec<dirigera::Device>,
+    scenes: Vec<dirigera::Scene>,
+    focus: Focus,
+    device_list: ListState,
+    scene_list: ListState,
+    status: String,
+}
+
+/// Run the dashboard until `q` or `esc` is pressed.
+pub async fn run(hub: dirigera::hub::Hub) -> anyhow::Result<()> {
+    let mut app = App {
+        hub,
+        devices: Vec::new(),
+        scenes: Vec::new(),
+        focus: Focus::Devices,
+        device_list: ListState::default().with_selected(Some(0)),
+        scene_list: ListState::default().with_selected(Some(0)),
+        status: String::new(),
+    };
+
+    app.refresh().await?;
+
+    let mut events = app.hub.events();
+    let mut keys = read_keys();
+    let mut terminal = ratatui::init();
+
+    let result = loop {
+        if let Err(err) = terminal.draw(|frame| app.draw(frame)) {
+            break Err(err.into());
+        }
+
+        tokio::select! {
+            key = keys.recv() => match key {
+                Some(KeyCode::Char('q') | KeyCode::Esc) | None => break Ok(()),
+                Some(key) => app.handle_key(key).await,
+            },
+            Some(event) = events.next() => app.handle_event(event).await,
+        }
+    };
+
+    ratatui::restore();
+
+    result
+}
+
+/// Read key presses on a separate thread since reading from the terminal is blocking.
+fn read_keys() -> tokio::sync::mpsc::Receiver<KeyCode> {
+    let (sender, receiver) = tokio::sync::mpsc::channel(16);
+
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            let event::Event::Key(key) = event else {
+                continue;
+            };
+
+            if key.kind == KeyEventKind::Press && sender.blocking_send(key.code).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+impl App {
+    async fn refresh(&mut self) -> anyhow::Result<()> {
+        let mut devices = self.hub.devices().await?;
+        devices.sort_by_cached_key(|device| {
+            let inner = device.inner();
+
+            (
+                room_name(device).to_string(),
+                inner.attributes.custom_name.clone(),
+            )
+        });
+
+        let mut scenes = self.hub.scenes().await?;
+        scenes.sort_by_cached_key(|scene| scene.inner().info.name.clone());
+
+        self.devices = devices;
+        self.scenes = scenes;
+        self.status = format!(
+            "{} devices and {} scenes",
+            self.devices.len(),
+            self.scenes.len()
+        );
+
+        Ok(())
+    }
+
+    async fn handle_key(&mut self, key: KeyCode) {
+        let result = match key {
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Focus::Devices => Focus::Scenes,
+                    Focus::Scenes => Focus::Devices,
+                };
+
+                Ok(())
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected_list().select_previous();
+                Ok(())
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected_list().select_next();
+                Ok(())
+            }
+            KeyCode::Char(' ') | KeyCode::Enter => self.activate().await,
+            KeyCode::Char('+') => self.adjust_brightness(i16::from(BRIGHTNESS_STEP)).await,
+            KeyCode::Char('-') => self.adjust_brightness(-i16::from(BRIGHTNESS_STEP)).await,
+            KeyCode::Char('r') => self.refresh().await,
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            self.status = format!("error: {err:#}");
+        }
+    }
+
+    /// Keep the listed devices up to date by refetching every device an event is about.
+    async fn handle_event(&mut self, event: dirigera::events::Event) {
+        let result = match event {
+            dirigera::events::Event::Hub(event) => match event.device_id() {
+                Some(id) => self.reload_device(id).await,
+                None => Ok(()),
+            },
+            dirigera::events::Event::Resync => self.refresh().await,
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            self.status = format!("error: {err:#}");
+        }
+    }
+
+    async fn reload_device(&mut self, id: &str) -> anyhow::Result<()> {
+        let Some(index) = self
+            .devices
+            .iter()
+            .position(|device| device.inner().id == id)
+        else {
+            return Ok(());
+        };
+
+        self.devices[index] = self.hub.device(id).await?;
+
+        Ok(())
+    }
+
+    /// Toggle the selected device or trigger the selected scene.
+    async fn activate(&mut self) -> anyhow::Result<()> {
+        match self.focus {
+            Focus::Devices => {
+                let Some(device) = self
+                    .device_list
+                    .selected()
+                    .and_then(|i| self.devices.get_mut(i))
+                else {
+                    return Ok(());
+                };
+
+                self.hub.toggle_on_off(device).await?;
+                let name = device.inner().attributes.custom_name.clone();
+                self.status = format!("toggled {name}");
+            }
+            Focus::Scenes => {
+                let Some(scene) = self.scene_list.selected().and_then(|i| self.scenes.get(i))
+                else {
+                    return Ok(());
+                };
+
+                self.hub.trigger_scene(scene).await?;
+                self.status = format!("triggered {}", scene.inner().info.name);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn adjust_brightness(&mut self, change: i16) -> anyhow::Result<()> {
+        if self.focus != Focus::Devices {
+            return Ok(());
+        }
+
+        let Some(device) = self
+            .device_list
+            .selected()
+            .and_then(|i| self.devices.get_mut(i))
+        else {
+            return Ok(());
+        };
+
+        let Some(current) = device.inner().attributes.light_level else {
+            anyhow::bail!("device has no brightness");
+        };
+
+        let level = (i16::from(current.0) + change).clamp(1, 100);
+        let level = dirigera::units::Percent(u8::try_from(level)?);
+
+        self.hub.set_light_level(device, level).await?;
+        let name = device.inner().attributes.custom_name.clone();
+        self.status = format!("set brightness of {name} to {level}");
+
+        Ok(())
+    }
+
+    fn selected_list(&mut self) -> &mut ListState {
+        match self.focus {
+            Focus::Devices => &mut self.device_list,
+            Focus::Scenes => &mut self.scene_list,
+        }
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame) {
+        let [main, status, help] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [devices, scenes] =
+            Layout::horizontal([Constraint::Percentage(70), Constraint::Percentage(30)])
+                .areas(main);
+
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+        let border = |focus| {
+            if self.focus == focus {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            }
+        };
+
+        let device_items = self.devices.iter().map(|device| {
+            ListItem::new(format!(
+                "{:<16} {:<28} {}",
+                room_name(device),
+                device.inner().attributes.custom_name,
+                device_state(device),
+            ))
+        });
+        let device_items = List::new(device_items)
+            .block(
+                Block::bordered()
+                    .title(" Devices ")
+                    .border_style(border(Focus::Devices)),
+            )
+            .highlight_style(highlight);
+
+        let scene_items = self
+            .scenes
+            .iter()
+            .map(|scene| ListItem::new(scene.inner().info.name.clone()));
+        let scene_items = List::new(scene_items)
+            .block(
+                Block::bordered()
+                    .title(" Scenes ")
+                    .border_style(border(Focus::Scenes)),
+            )
+            .highlight_style(highlight);
+
+        frame.render_stateful_widget(device_items, devices, &mut self.device_list);
+        frame.render_stateful_widget(scene_items, scenes, &mut self.scene_list);
+        frame.render_widget(Paragraph::new(self.status.as_str()), status);
+        frame.render_widget(
+            Paragraph::new(KEYS).style(Style::default().add_modifier(Modifier::DIM)),
+            help,
+        );
+    }
+}
+
+fn room_name(device: &dirigera::Device) -> &str {
+    device
+        .inner()
+        .room
+        .as_ref()
+        .map_or("-", |room| room.name.as_str())
+}
+
+/// A short summary of the state of the device, f.ex. `on 80%` for a light or the temperature for
+/// a sensor.
+fn device_state(device: &dirigera::Device) -> String {
+    let inner = device.inner();
+    let attributes = &inner.attributes;
+
+    if !inner.is_reachable {
+        return "unreachable".to_string();
+    }
+
+    let mut state = Vec::new();
+
+    match (attributes.is_on, attributes.light_level) {
+        (Some(true), Some(level)) => state.push(format!("on {level}")),
+        (Some(true), None) => state.push("on".to_string()),
+        (Some(false), _) => state.push("off".to_string()),
+        _ => {}
+    }
+
+    if let Some(level) = attributes.blinds_current_level {
+        state.push(format!("blinds {level}"));
+    }
+
+    if let Some(temperature) = attributes.current_temperature {
+        state.push(temperature.to_string());
+    }
+
+    if let Some(humidity) = attributes.current_r_h {
+        state.push(format!("{humidity} RH"));
+    }
+
+    if let Some(is_open) = attributes.is_open {
+        state.push(if is_open { "open" } else { "closed" }.to_string());
+    }
+
+    if attributes.water_leak_detected == Some(true) {
+        state.push("LEAK".to_string());
+    }
+
+    state.join(", ")
+}
@@ -1,92 +1,155 @@
-#[cfg(feature = "binary")]
-use std::collections::HashMap;
 use std::io::Write;
 
+const USAGE: &str = "Usage: generate-token [--format toml|json|env] [--name <client-name>] [--discover | ip-address]
+
+With the default toml format the credentials are saved to 'config.toml', with json or env they're
+printed to stdout. With --discover the hub is found on the local network instead of asking for its
+ip address. The token is listed with the client name in the hub, defaults to the hostname.";
+
+/// The format to output the credentials in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Toml,
+    Json,
+    Env,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toml" => Ok(Self::Toml),
+            "json" => Ok(Self::Json),
+            "env" => Ok(Self::Env),
+            _ => anyhow::bail!("unknown format '{s}', use toml, json or env"),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    let ip_address = if args.len() < 2 {
-        print!("Enter ip address: ");
-        std::io::stdout().flush()?;
+    let mut format = Format::Toml;
+    let mut ip_address = None;
+    let mut discover = false;
+    let mut client_name = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--format needs a value\n\n{USAGE}"))?
+                    .parse()?;
+            }
+            "--name" => {
+                client_name = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow::anyhow!("--name needs a value\n\n{USAGE}"))?,
+                );
+            }
+            "--discover" => discover = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if ip_address.is_none() => ip_address = Some(arg),
+            _ => anyhow::bail!("unknown argument '{arg}'\n\n{USAGE}"),
+        }
+    }
 
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
+    // Prompts are written to stderr so only the credentials end up on stdout.
+    let ip_address = match ip_address {
+        Some(_) if discover => anyhow::bail!("can't use both --discover and an ip address"),
+        Some(ip_address) => ip_address,
+        None if discover => discover_hub().await?,
+        None => {
+            eprint!("Enter ip address: ");
+            std::io::stderr().flush()?;
 
-        input.trim().to_string()
-    } else {
-        args[1].to_string()
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+
+            input.trim().to_string()
+        }
     };
 
     let file_path = "config.toml";
-    if std::path::Path::new(file_path).exists() {
+    if format == Format::Toml && std::path::Path::new(file_path).exists() {
         anyhow::bail!("'config.toml' already exist!");
     }
 
-    let client = reqwest::Client::builder()
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap();
-
-    let code_verify = pkce::code_verifier(128);
-    let code_challenge = pkce::code_challenge(&code_verify);
-    let code_veirifier_string = String::from_utf8(code_verify)?;
-
-    let auth_url = reqwest::Url::parse_with_params(
-        format!("https://{}:8443/v1/oauth/authorize", ip_address).as_str(),
-        &[
-            ("audience", "homesmart.local"),
-            ("response_type", "code"),
-            ("code_challenge", code_challenge.as_str()),
-            ("code_challenge_method", "S256"),
-        ],
-    )?;
-
-    let response: HashMap<String, String> = client.get(auth_url).send().await?.json().await?;
-    let code = response
-        .get("code")
-        .ok_or_else(|| anyhow::anyhow!("code not found in body: {:?}", response))?;
-
-    println!("Press ENTER after pressing the button on your Dirigera device");
-
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-
-    let mut token_params = HashMap::new();
-    token_params.insert("code", code.as_str());
-    token_params.insert("name", "localhost");
-    token_params.insert("grant_type", "authorization_code");
-    token_params.insert("code_verifier", code_veirifier_string.as_str());
-
-    let token_url =
-        reqwest::Url::parse(format!("https://{}:8443/v1/oauth/token", ip_address).as_str())?;
-
-    let response: HashMap<String, String> = client
-        .post(token_url)
-        .json(&token_params)
-        .send()
-        .await?
-        .json()
-        .await?;
-
-    let access_token = response
-        .get("access_token")
-        .ok_or_else(|| anyhow::anyhow!("code not found in body: {:?}", response))?;
-
-    let mut config = toml::value::Table::new();
-    config.insert(
-        "ip-address".to_string(),
-        toml::Value::String(ip_address.to_string()),
-    );
-    config.insert(
-        "token".to_string(),
-        toml::Value::String(access_token.to_string()),
-    );
-
-    let mut file = std::fs::File::create(file_path)?;
-    let toml_string = toml::to_string(&config)?;
-    file.write_all(toml_string.as_bytes())?;
-
-    println!("🎉 Configuration has been saved to 'config.toml'");
+    let ip_address: std::net::Ipv4Addr = ip_address
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid ip address '{ip_address}'"))?;
+
+    let client_name = client_name.unwrap_or_else(dirigera::auth::default_client_name);
+
+    let access_token = dirigera::auth::obtain_token(ip_address, &client_name, || async {
+        eprintln!("Press ENTER after pressing the button on your Dirigera device");
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+
+        Ok(())
+    })
+    .await?;
+
+    match format {
+        Format::Toml => {
+            let mut config = toml::value::Table::new();
+            config.insert(
+                "ip-address".to_string(),
+                toml::Value::String(ip_address.to_string()),
+            );
+            config.insert("token".to_string(), toml::Value::String(access_token));
+
+            let mut file = std::fs::File::create(file_path)?;
+            let toml_string = toml::to_string(&config)?;
+            file.write_all(toml_string.as_bytes())?;
+
+            eprintln!("🎉 Configuration has been saved to 'config.toml'");
+        }
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({
+                "ip-address": ip_address.to_string(),
+                "token": access_token,
+            })
+        ),
+        Format::Env => {
+            println!("DIRIGERA_IP_ADDRESS={ip_address}");
+            println!("DIRIGERA_TOKEN={access_token}");
+        }
+    }
 
     Ok(())
 }
+
+/// Find the only hub on the local network. Fails if there's none or more than one.
+async fn discover_hub() -> anyhow::Result<String> {
+    eprintln!("Looking for hubs on the local network...");
+
+    let hubs = dirigera::discovery::discover(dirigera::discovery::DEFAULT_TIMEOUT).await?;
+
+    match hubs.as_slice() {
+        [] => anyhow::bail!("no hub found, pass the ip address instead"),
+        [hub] => {
+            eprintln!("Found {hub}");
+            Ok(hub.ip_address.to_string())
+        }
+        hubs => {
+            let candidates = hubs
+                .iter()
+                .map(|hub| format!("  {hub}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            anyhow::bail!(
+                "found {} hubs, pass the ip address of one of them:\n{candidates}",
+                hubs.len()
+            )
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! A [`DeviceCache`] keeps the latest known state of every [`Device`](crate::Device) while it's
+//! updated both from events and from local, optimistic, writes. Each attribute of a cached device
+//! has a version which is the hub timestamp of the state it was last set from, so state can be
+//! applied in any order and newer state reported by the hub always wins:
+//!
+//! - Events are applied for each attribute that hasn't been set from anything newer.
+//! - Refetched devices are versioned by their `last_seen` and only replace older attributes.
+//! - Optimistic writes are based on the [`DeviceCache::version`] read before the command was sent
+//!   and are rejected for attributes the hub has reported something newer for since then.
+//!
+//! ```no_run
+//! # async fn example(mut hub: dirigera::hub::Hub) -> anyhow::Result<()> {
+//! use dirigera::command::Command;
+//!
+//! let cache = dirigera::cache::DeviceCache::new(hub.devices().await?);
+//!
+//! let based_on = cache.version("abc123_1").unwrap_or_default();
+//! let mut device = hub.device("abc123_1").await?;
+//! hub.execute(&mut device, &Command::TurnOn).await?;
+//!
+//! cache.apply_optimistic("abc123_1", &Command::TurnOn, based_on);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The cache is cheap to clone and all clones share the same state, so one clone can be updated
+//! from an event task while commands are sent and applied from other tasks. Each update is applied
+//! atomically. It's only available behind the `events` feature flag.
+use crate::command::Command;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// The attributes of an optimistic write that were applied and the ones that were rejected since
+/// the hub had reported newer state, see [`DeviceCache::apply_optimistic`]. Attribute names are
+/// in `camelCase` as sent by the hub.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciled {
+    pub applied: Vec<String>,
+    pub rejected: Vec<String>,
+}
+
+/// A [`DeviceCache`] holds one entry per device.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceCache {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    device: Arc<crate::Device>,
+    versions: HashMap<String, crate::Timestamp>,
+}
+
+impl DeviceCache {
+    /// Create a new [`DeviceCache`] from fetched devices, f.ex. from
+    /// [`Hub::devices`](crate::hub::Hub::devices).
+    pub fn new(devices: impl IntoIterator<Item = crate::Device>) -> Self {
+        let cache = Self::default();
+
+        for device in devices {
+            cache.apply_device(device);
+        }
+
+        cache
+    }
+
+    /// Get the cached [`Device`](crate::Device) with the given id as it is right now. Later
+    /// updates replace the device in the cache and don't change the returned one.
+    pub fn get(&self, device_id: &str) -> Option<Arc<crate::Device>> {
+        self.entries()
+            .get(device_id)
+            .map(|entry| entry.device.clone())
+    }
+
+    /// Get all cached [`Device`](crate::Device)s in no particular order.
+    pub fn devices(&self) -> Vec<Arc<crate::Device>> {
+        self.entries()
+            .values()
+            .map(|entry| entry.device.clone())
+            .collect()
+    }
+
+    /// Get the version of the [`Device`](crate::Device) with the given id, which is the newest
+    /// hub timestamp of any of its attributes. Read it before sending a command and pass it to
+    /// [`DeviceCache::apply_optimistic`].
+    pub fn version(&self, device_id: &str) -> Option<crate::Timestamp> {
+        self.entries()
+            .get(device_id)?
+            .versions
+            .values()
+            .max()
+            .copied()
+    }
+
+    /// Apply a refetched [`Device`](crate::Device). Attributes that have been set from newer
+    /// state than the `last_seen` of the device are kept. Returns `true` if the device is new or
+    /// any attribute was replaced.
+    pub fn apply_device(&self, device: crate::Device) -> bool {
+        let version = device.inner().last_seen;
+        let device_id = device.inner().id.to_string();
+        let mut entries = self.entries();
+
+        let Some(entry) = entries.get_mut(&device_id) else {
+            let versions = attributes(device.raw())
+                .map(|attributes| {
+                    attributes
+                        .keys()
+                        .map(|name| (name.clone(), version))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            let device = Arc::new(device);
+            entries.insert(device_id, Entry { device, versions });
+            return true;
+        };
+
+        let Some(reported) = attributes(device.raw()).cloned() else {
+            return false;
+        };
+
+        // Everything but the attributes, f.ex. the room and reachability, comes from the newest
+        // fetch.
+        let mut raw = device.raw().clone();
+        if let Some(cached) = attributes(entry.device.raw()) {
+            raw["attributes"] = serde_json::Value::Object(cached.clone());
+        }
+
+        !entry
+            .update(raw, &reported, version, |current| current <= version)
+            .is_empty()
+    }
+
+    /// Apply an event from the hub. Device state changes are applied for each attribute that
+    /// hasn't been set from newer state, added devices are inserted and removed devices are
+    /// removed. Returns `true` if the cache changed.
+    pub fn apply_event(&self, event: &crate::events::HubEvent) -> bool {
+        let Some(device_id) = event.device_id() else {
+            return false;
+        };
+
+        match event.event_type.as_str() {
+            "deviceRemoved" => self.entries().remove(device_id).is_some(),
+            "deviceAdded" if !self.entries().contains_key(device_id) => {
+                match crate::Device::try_from(event.data.clone()) {
+                    Ok(device) => self.apply_device(device),
+                    Err(_) => false,
+                }
+            }
+            "deviceStateChanged" => {
+                let mut entries = self.entries();
+                let (Some(entry), Some(reported)) =
+                    (entries.get_mut(device_id), attributes(&event.data))
+                else {
+                    return false;
+                };
+
+                let raw = entry.device.raw().clone();
+
+                !entry
+                    .update(raw, reported, event.time, |current| current < event.time)
+                    .is_empty()
+            }
+            _ => false,
+        }
+    }
+
+    /// Apply the result of a [`Command`] sent to the hub before the hub reports it. `based_on` is
+    /// the [`DeviceCache::version`] of the device when the command was sent, each attribute is
+    /// only applied if the hub hasn't reported anything newer for it since. Applied attributes get
+    /// `based_on` as version so older events that arrive late don't revert them.
+    pub fn apply_optimistic(
+        &self,
+        device_id: &str,
+        command: &Command,
+        based_on: crate::Timestamp,
+    ) -> Reconciled {
+        let mut entries = self.entries();
+        let Some(entry) = entries.get_mut(device_id) else {
+            return Reconciled::default();
+        };
+
+        let written = command_attributes(command, &entry.device);
+        let raw = entry.device.raw().clone();
+        let applied = entry.update(raw, &written, based_on, |current| current <= based_on);
+
+        Reconciled {
+            rejected: written
+                .keys()
+                .filter(|name| !applied.contains(name))
+                .cloned()
+                .collect(),
+            applied,
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Entry {
+    /// Set each attribute in `changed` whose current version is accepted and parse the device
+    /// again. Returns the names of the attributes that were set, nothing is set if the device
+    /// can't be parsed with the new attributes.
+    fn update<F>(
+        &mut self,
+        mut raw: serde_json::Value,
+        changed: &serde_json::Map<String, serde_json::Value>,
+        version: crate::Timestamp,
+        accept: F,
+    ) -> Vec<String>
+    where
+        F: Fn(crate::Timestamp) -> bool,
+    {
+        let mut applied = Vec::new();
+
+        if let Some(attributes) = raw
+            .get_mut("attributes")
+            .and_then(serde_json::Value::as_object_mut)
+        {
+            for (name, value) in changed {
+                if self
+                    .versions
+                    .get(name)
+                    .is_some_and(|current| !accept(*current))
+                {
+                    continue;
+                }
+
+                attributes.insert(name.clone(), value.clone());
+                applied.push(name.clone());
+            }
+        }
+
+        let Ok(device) = crate::Device::try_from(raw) else {
+            return Vec::new();
+        };
+
+        self.device = Arc::new(device);
+        for name in &applied {
+            self.versions.insert(name.clone(), version);
+        }
+
+        applied
+    }
+}
+
+fn attributes(raw: &serde_json::Value) -> Option<&serde_json::Map<String, serde_json::Value>> {
+    raw.get("attributes").and_then(serde_json::Value::as_object)
+}
+
+/// The attributes the hub will report once the [`Command`] has been applied to the device.
+fn command_attributes(
+    command: &Command,
+    device: &crate::Device,
+) -> serde_json::Map<String, serde_json::Value> {
+    let (name, value) = match command {
+        Command::TurnOn => ("isOn", serde_json::json!(true)),
+        Command::TurnOff => ("isOn", serde_json::json!(false)),
+        Command::Toggle => (
+            "isOn",
+            serde_json::json!(device.inner().attributes.is_on != Some(true)),
+        ),
+        Command::SetLightLevel(level) => ("lightLevel", serde_json::json!(level)),
+        Command::SetColorTemperature(temperature) => {
+            ("colorTemperature", serde_json::json!(temperature))
+        }
+        Command::SetBlindsLevel(level) => ("blindsTargetLevel", serde_json::json!(level)),
+    };
+
+    serde_json::Map::from_iter([(name.to_string(), value)])
+}
@@ -0,0 +1,280 @@
+//! Conversions between the color spaces used by different integrations. The hub uses hue and
+//! saturation, see [`Hub::set_hue_saturation`](crate::hub::Hub::set_hue_saturation), while f.ex.
+//! Home Assistant and Philips Hue often use CIE 1931 xy coordinates or RGB. Since a lamp can't
+//! show every color, xy coordinates can be clamped to the [`Gamut`] of the lamp before converting.
+//! All conversions ignore brightness so converting to RGB always gives the brightest color with
+//! the given hue and saturation.
+
+/// A color as hue in degrees, `0.0..360.0`, and saturation, `0.0..=1.0`, which is what the hub
+/// uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hs {
+    pub hue: f64,
+    pub saturation: f64,
+}
+
+impl Hs {
+    /// Get the color with the hue wrapped to `0.0..360.0` and the saturation clamped to
+    /// `0.0..=1.0` so it's accepted by the hub.
+    pub fn normalized(self) -> Self {
+        Self {
+            hue: self.hue.rem_euclid(360.0),
+            saturation: self.saturation.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// A color as coordinates in the CIE 1931 color space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Xy {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A color in the sRGB color space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+/// The white point of the sRGB color space, used for black when converting to [`Xy`].
+const D65: Xy = Xy {
+    x: 0.3127,
+    y: 0.3290,
+};
+
+/// A [`Gamut`] is the triangle in the CIE 1931 color space of colors a lamp can show.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gamut {
+    pub red: Xy,
+    pub green: Xy,
+    pub blue: Xy,
+}
+
+impl Gamut {
+    /// The sRGB color space.
+    pub const SRGB: Gamut = Gamut {
+        red: Xy { x: 0.64, y: 0.33 },
+        green: Xy { x: 0.30, y: 0.60 },
+        blue: Xy { x: 0.15, y: 0.06 },
+    };
+
+    /// Gamut A used by older Philips Hue lamps, also common for other Zigbee lamps.
+    pub const A: Gamut = Gamut {
+        red: Xy { x: 0.704, y: 0.296 },
+        green: Xy {
+            x: 0.2151,
+            y: 0.7106,
+        },
+        blue: Xy { x: 0.138, y: 0.08 },
+    };
+
+    /// Gamut B used by the first generation of Philips Hue bulbs.
+    pub const B: Gamut = Gamut {
+        red: Xy { x: 0.675, y: 0.322 },
+        green: Xy { x: 0.409, y: 0.518 },
+        blue: Xy { x: 0.167, y: 0.04 },
+    };
+
+    /// Gamut C used by newer Philips Hue bulbs.
+    pub const C: Gamut = Gamut {
+        red: Xy {
+            x: 0.6915,
+            y: 0.3083,
+        },
+        green: Xy { x: 0.17, y: 0.7 },
+        blue: Xy {
+            x: 0.1532,
+            y: 0.0475,
+        },
+    };
+
+    /// Check if the color is within the gamut.
+    pub fn contains(&self, xy: Xy) -> bool {
+        let side = |a: Xy, b: Xy| (b.x - a.x) * (xy.y - a.y) - (b.y - a.y) * (xy.x - a.x);
+
+        let d1 = side(self.red, self.green);
+        let d2 = side(self.green, self.blue);
+        let d3 = side(self.blue, self.red);
+
+        let has_negative = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+        let has_positive = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+
+        !(has_negative && has_positive)
+    }
+
+    /// Get the closest color within the gamut. Colors already within the gamut are returned as
+    /// is.
+    pub fn clamp(&self, xy: Xy) -> Xy {
+        if self.contains(xy) {
+            return xy;
+        }
+
+        [
+            closest_on_line(self.red, self.green, xy),
+            closest_on_line(self.green, self.blue, xy),
+            closest_on_line(self.blue, self.red, xy),
+        ]
+        .into_iter()
+        .min_by(|a, b| distance(*a, xy).total_cmp(&distance(*b, xy)))
+        .unwrap_or(xy)
+    }
+}
+
+fn closest_on_line(a: Xy, b: Xy, point: Xy) -> Xy {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((point.x - a.x) * dx + (point.y - a.y) * dy) / length).clamp(0.0, 1.0)
+    };
+
+    Xy {
+        x: a.x + t * dx,
+        y: a.y + t * dy,
+    }
+}
+
+fn distance(a: Xy, b: Xy) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+/// Convert a gamma encoded sRGB channel to linear light.
+fn to_linear(channel: u8) -> f64 {
+    let value = f64::from(channel) / 255.0;
+
+    if value > 0.04045 {
+        ((value + 0.055) / 1.055).powf(2.4)
+    } else {
+        value / 12.92
+    }
+}
+
+/// Convert a linear light channel to a gamma encoded sRGB channel.
+fn from_linear(value: f64) -> u8 {
+    let value = if value <= 0.0031308 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl From<Rgb> for Hs {
+    fn from(rgb: Rgb) -> Self {
+        let (r, g, b) = (
+            f64::from(rgb.red) / 255.0,
+            f64::from(rgb.green) / 255.0,
+            f64::from(rgb.blue) / 255.0,
+        );
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+
+        Hs {
+            hue,
+            saturation: if max == 0.0 { 0.0 } else { delta / max },
+        }
+    }
+}
+
+impl From<Hs> for Rgb {
+    fn from(hs: Hs) -> Self {
+        let hue = hs.hue.rem_euclid(360.0) / 60.0;
+        let chroma = hs.saturation.clamp(0.0, 1.0);
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let m = 1.0 - chroma;
+
+        let (r, g, b) = match hue as u8 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+
+        let channel = |value: f64| ((value + m) * 255.0).round() as u8;
+
+        Rgb {
+            red: channel(r),
+            green: channel(g),
+            blue: channel(b),
+        }
+    }
+}
+
+impl From<Rgb> for Xy {
+    fn from(rgb: Rgb) -> Self {
+        let (r, g, b) = (
+            to_linear(rgb.red),
+            to_linear(rgb.green),
+            to_linear(rgb.blue),
+        );
+
+        let x = 0.4124 * r + 0.3576 * g + 0.1805 * b;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = 0.0193 * r + 0.1192 * g + 0.9505 * b;
+        let sum = x + y + z;
+
+        if sum == 0.0 {
+            return D65;
+        }
+
+        Xy {
+            x: x / sum,
+            y: y / sum,
+        }
+    }
+}
+
+impl From<Xy> for Rgb {
+    fn from(xy: Xy) -> Self {
+        if xy.y <= 0.0 {
+            return Rgb {
+                red: 0,
+                green: 0,
+                blue: 0,
+            };
+        }
+
+        let x = xy.x / xy.y;
+        let z = (1.0 - xy.x - xy.y) / xy.y;
+
+        let r = (3.2406 * x - 1.5372 - 0.4986 * z).max(0.0);
+        let g = (-0.9689 * x + 1.8758 + 0.0415 * z).max(0.0);
+        let b = (0.0557 * x - 0.2040 + 1.0570 * z).max(0.0);
+        let max = r.max(g).max(b).max(f64::EPSILON);
+
+        Rgb {
+            red: from_linear(r / max),
+            green: from_linear(g / max),
+            blue: from_linear(b / max),
+        }
+    }
+}
+
+impl From<Xy> for Hs {
+    fn from(xy: Xy) -> Self {
+        Rgb::from(xy).into()
+    }
+}
+
+impl From<Hs> for Xy {
+    fn from(hs: Hs) -> Self {
+        Rgb::from(hs).into()
+    }
+}
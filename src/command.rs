@@ -0,0 +1,19 @@
+//! A [`Command`] is a single change to a [`Device`](crate::Device) that can be stored and sent to
+//! the hub later with [`Hub::execute`](crate::hub::Hub::execute). This is used by everything that
+//! doesn't act on a device right away, such as scripts and the scheduler.
+use crate::units::{Kelvin, Percent};
+use serde::{Deserialize, Serialize};
+
+/// A change to apply to a [`Device`](crate::Device). Turning a device on or off will only toggle
+/// it if it's not already in the wanted state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", tag = "type", content = "value")]
+pub enum Command {
+    TurnOn,
+    TurnOff,
+    Toggle,
+    SetLightLevel(Percent),
+    SetColorTemperature(Kelvin),
+    SetBlindsLevel(Percent),
+}
@@ -0,0 +1,250 @@
+//! A [`Connector`] opens the TCP connection to the hub and can be used when the hub can't be
+//! reached directly, f.ex. through a jump host with an HTTP `CONNECT` or SOCKS5 [`Proxy`], or
+//! when host names should be resolved with something other than the system resolver, see
+//! [`Resolve`]. Use it through [`HubBuilder::proxy`](crate::hub::HubBuilder::proxy) and
+//! [`HubBuilder::resolver`](crate::hub::HubBuilder::resolver). Events are not sent through the
+//! [`Connector`]. It's only available behind the `proxy` feature flag.
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// The largest response accepted from an HTTP proxy when opening a tunnel.
+const MAX_PROXY_RESPONSE: usize = 8 * 1024;
+
+/// The future returned by a [`Resolve`] implementation.
+pub type ResolveFuture = Pin<Box<dyn Future<Output = std::io::Result<Vec<SocketAddr>>> + Send>>;
+
+/// A [`Resolve`] implementation resolves a host name to the addresses to connect to.
+pub trait Resolve: std::fmt::Debug + Send + Sync {
+    fn resolve(&self, host: &str, port: u16) -> ResolveFuture;
+}
+
+/// The default resolver using the system resolver through [`tokio::net::lookup_host`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> ResolveFuture {
+        let host = host.to_string();
+
+        Box::pin(async move { Ok(tokio::net::lookup_host((host, port)).await?.collect()) })
+    }
+}
+
+/// A proxy to connect to the hub through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Proxy {
+    /// An HTTP proxy supporting the `CONNECT` method.
+    Http { host: String, port: u16 },
+    /// A SOCKS5 proxy without authentication.
+    Socks5 { host: String, port: u16 },
+}
+
+/// A [`Connector`] is a [`hyper`] connector that can connect through a [`Proxy`] and resolve
+/// host names with a custom [`Resolve`] implementation.
+#[derive(Debug, Clone)]
+pub struct Connector {
+    proxy: Option<Proxy>,
+    resolver: Arc<dyn Resolve>,
+}
+
+impl Default for Connector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Connector {
+    /// Create a new [`Connector`] connecting directly using the [`SystemResolver`].
+    pub fn new() -> Self {
+        Self {
+            proxy: None,
+            resolver: Arc::new(SystemResolver),
+        }
+    }
+
+    /// Connect through the given [`Proxy`].
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Resolve host names with the given [`Resolve`] implementation.
+    pub fn resolver(mut self, resolver: impl Resolve + 'static) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    async fn connect(&self, uri: hyper::Uri) -> std::io::Result<TcpStream> {
+        let host = uri
+            .host()
+            .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+            .ok_or_else(|| invalid_input("missing host in uri"))?
+            .to_string();
+        let port = uri.port_u16().unwrap_or(443);
+
+        match &self.proxy {
+            None => self.tcp_connect(&host, port).await,
+            Some(Proxy::Http {
+                host: proxy_host,
+                port: proxy_port,
+            }) => {
+                let stream = self.tcp_connect(proxy_host, *proxy_port).await?;
+                http_connect(stream, &host, port).await
+            }
+            Some(Proxy::Socks5 {
+                host: proxy_host,
+                port: proxy_port,
+            }) => {
+                let stream = self.tcp_connect(proxy_host, *proxy_port).await?;
+                socks5_connect(stream, &host, port).await
+            }
+        }
+    }
+
+    async fn tcp_connect(&self, host: &str, port: u16) -> std::io::Result<TcpStream> {
+        let addresses = match host.parse::<std::net::IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => self.resolver.resolve(host, port).await?,
+        };
+
+        let mut last_error = None;
+        for address in addresses {
+            match TcpStream::connect(address).await {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_error = Some(err),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no addresses found for {host}"),
+            )
+        }))
+    }
+}
+
+impl tower_service::Service<hyper::Uri> for Connector {
+    type Response = TcpStream;
+    type Error = std::io::Error;
+    type Future = Pin<Box<dyn Future<Output = std::io::Result<TcpStream>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: hyper::Uri) -> Self::Future {
+        let connector = self.clone();
+
+        Box::pin(async move { connector.connect(uri).await })
+    }
+}
+
+fn invalid_input(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+fn proxy_error(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::ConnectionRefused, message)
+}
+
+/// Open a tunnel to `host:port` through an HTTP proxy.
+async fn http_connect(mut stream: TcpStream, host: &str, port: u16) -> std::io::Result<TcpStream> {
+    stream
+        .write_all(
+            format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n\r\n").as_bytes(),
+        )
+        .await?;
+
+    let mut response = Vec::new();
+    let mut byte = [0; 1];
+
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() > MAX_PROXY_RESPONSE {
+            return Err(proxy_error("proxy response too large".to_string()));
+        }
+
+        // Read one byte at a time so nothing after the headers is consumed from the stream.
+        if stream.read(&mut byte).await? == 0 {
+            return Err(proxy_error("proxy closed the connection".to_string()));
+        }
+
+        response.push(byte[0]);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(stream),
+        _ => Err(proxy_error(format!("proxy refused tunnel: {status_line}"))),
+    }
+}
+
+/// Open a connection to `host:port` through a SOCKS5 proxy, see RFC 1928.
+async fn socks5_connect(
+    mut stream: TcpStream,
+    host: &str,
+    port: u16,
+) -> std::io::Result<TcpStream> {
+    // Version 5 with one authentication method, no authentication.
+    stream.write_all(&[5, 1, 0]).await?;
+
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply != [5, 0] {
+        return Err(proxy_error(
+            "socks5 proxy requires authentication".to_string(),
+        ));
+    }
+
+    // Version 5, connect command, reserved byte and then the address.
+    let mut request = vec![5, 1, 0];
+    match host.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(std::net::IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            let length =
+                u8::try_from(host.len()).map_err(|_| invalid_input("host name too long"))?;
+
+            request.push(3);
+            request.push(length);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(proxy_error(format!(
+            "socks5 proxy failed to connect with reply {}",
+            reply[1]
+        )));
+    }
+
+    // Skip the bound address and port.
+    let skip = match reply[3] {
+        1 => 4 + 2,
+        4 => 16 + 2,
+        3 => usize::from(stream.read_u8().await?) + 2,
+        _ => return Err(proxy_error("invalid socks5 address type".to_string())),
+    };
+    let mut bound = vec![0; skip];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(stream)
+}
@@ -1,122 +1,335 @@
 //! IKEA support multiple devices to be controlled via the Dirigera hub and they're divided into
 //! several types, in this code represented as the [Device] enum.
-use crate::deserialize_datetime;
+use crate::units::{Celsius, Kelvin, Percent};
+use crate::{
+    deserialize_datetime, deserialize_time, deserialize_time_optional, serialize_time,
+    serialize_time_optional,
+};
 use serde::{Deserialize, Serialize};
 
+use std::collections::HashMap;
+
 /// A [`Device`] is a resource that is able to connect to the IKEA Dirigera hub - or the actual hub
 /// itself. It's represented as an enum with one variant for each type rather than separate types
 /// for each content since the data for the devices are shared.
-#[derive(Debug, Deserialize, PartialEq, PartialOrd)]
-#[serde(rename_all = "camelCase", tag = "type")]
+///
+/// Two [`Device`]s are equal if they have the same id, so a [`Device`] can be used as a key in a
+/// [`HashMap`] or in a [`HashSet`](std::collections::HashSet) and a cached [`Device`] is replaced
+/// by a newer snapshot of the same device. Compare [`Device::inner`] to also compare attributes.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(try_from = "serde_json::Value")]
+#[cfg_attr(feature = "schemars", schemars(rename_all = "camelCase", tag = "type"))]
 pub enum Device {
+    /// An air purifier such as STARKVIND. It reports the air quality with the same attributes as
+    /// an environment sensor.
+    AirPurifier(DeviceData),
+    Blinds(DeviceData),
+    Controller(DeviceData),
+    Gateway(DeviceData),
+    Light(DeviceData),
+    /// A door or window sensor. The hub reports these as sensors but they're kept as a separate
+    /// variant since they never detect motion, see [`Attributes::is_open`] for the state.
+    #[cfg_attr(feature = "schemars", schemars(skip))]
+    OpenCloseSensor(DeviceData),
+    Outlet(DeviceData),
+    /// A signal repeater which only extends the Zigbee network and has no other attributes than
+    /// the common ones.
+    Repeater(DeviceData),
+    Sensor(DeviceData),
+}
+
+/// The [`Device`] as it's represented by the hub, before splitting out variants that the hub
+/// doesn't have a separate `type` for.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+enum DeviceRepr {
+    AirPurifier(DeviceData),
     Blinds(DeviceData),
     Controller(DeviceData),
     Gateway(DeviceData),
     Light(DeviceData),
     Outlet(DeviceData),
+    Repeater(DeviceData),
     Sensor(DeviceData),
 }
 
+impl TryFrom<serde_json::Value> for Device {
+    type Error = serde_json::Error;
+
+    fn try_from(raw: serde_json::Value) -> Result<Self, Self::Error> {
+        let mut device = Self::from(DeviceRepr::deserialize(&raw)?);
+        device.inner_mut().raw = raw;
+
+        Ok(device)
+    }
+}
+
+impl From<DeviceRepr> for Device {
+    fn from(device: DeviceRepr) -> Self {
+        match device {
+            DeviceRepr::AirPurifier(inner) => Self::AirPurifier(inner),
+            DeviceRepr::Blinds(inner) => Self::Blinds(inner),
+            DeviceRepr::Controller(inner) => Self::Controller(inner),
+            DeviceRepr::Gateway(inner) => Self::Gateway(inner),
+            DeviceRepr::Light(inner) => Self::Light(inner),
+            DeviceRepr::Outlet(inner) => Self::Outlet(inner),
+            DeviceRepr::Repeater(inner) => Self::Repeater(inner),
+            DeviceRepr::Sensor(inner) if inner.device_type == DeviceType::OpenCloseSensor => {
+                Self::OpenCloseSensor(inner)
+            }
+            DeviceRepr::Sensor(inner) => Self::Sensor(inner),
+        }
+    }
+}
+
 /// Common data that is shared between all [`Device`]s.
-#[derive(Debug, Deserialize, PartialEq, PartialOrd)]
+#[derive(Debug, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct DeviceData {
-    pub id: String,
+    pub id: crate::DeviceId,
     pub device_type: DeviceType,
     #[serde(deserialize_with = "deserialize_datetime")]
-    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: crate::Timestamp,
     pub is_reachable: bool,
     pub is_hidden: Option<bool>,
     #[serde(deserialize_with = "deserialize_datetime")]
-    pub last_seen: chrono::DateTime<chrono::Utc>,
+    pub last_seen: crate::Timestamp,
     pub room: Option<Room>,
     pub attributes: Attributes,
     pub remote_links: Vec<String>,
+    #[serde(default)]
+    pub device_set: Vec<DeviceSet>,
     pub capabilities: Capabilities,
+    #[serde(skip)]
+    raw: serde_json::Value,
 }
 
 /// A device can have capabilities it can send or receive. Each type is represented as a list of
 /// [`Capability`].
-#[derive(Debug, Deserialize, PartialEq, PartialOrd)]
+#[derive(Debug, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Capabilities {
     pub can_send: Vec<Capability>,
     pub can_receive: Vec<Capability>,
 }
 
-/// Available capabilities across all devices that is listed either as something the device can
-/// send or receive.
-#[derive(Debug, Deserialize, PartialEq, PartialOrd)]
-#[serde(rename_all = "camelCase")]
-pub enum Capability {
-    BlindsCurrentLevel,
-    BlindsState,
-    BlindsTargetLevel,
-    ColorHue,
-    ColorSaturation,
-    ColorTemperature,
-    Coordinates,
-    CountryCode,
-    CustomName,
-    IsOn,
-    LightLevel,
-    LogLevel,
-    PermittingJoin,
-    Time,
-    Timezone,
-    UserConsents,
-}
-
-/// A [`Device`] has both a `type` which is interpreted as the [`Device`] enum but also a
-/// `device_type`. They don't always overlap.
-#[derive(Debug, Deserialize, PartialEq, PartialOrd)]
-#[serde(rename_all = "camelCase")]
-pub enum DeviceType {
-    LightController,
-    Light,
+crate::string_enum! {
+    /// Available capabilities across all devices that is listed either as something the device
+    /// can send or receive. Capabilities not known by this crate are kept as
+    /// [`Capability::Unknown`].
+    pub enum Capability {
+        BlindsCurrentLevel => "blindsCurrentLevel",
+        BlindsState => "blindsState",
+        BlindsTargetLevel => "blindsTargetLevel",
+        ColorHue => "colorHue",
+        ColorSaturation => "colorSaturation",
+        ColorTemperature => "colorTemperature",
+        Coordinates => "coordinates",
+        CountryCode => "countryCode",
+        CustomName => "customName",
+        Effect => "effect",
+        IsOn => "isOn",
+        LightLevel => "lightLevel",
+        LogLevel => "logLevel",
+        PermittingJoin => "permittingJoin",
+        Time => "time",
+        Timezone => "timezone",
+        UserConsents => "userConsents",
+    }
+}
+
+crate::string_enum! {
+    /// A [`Device`] has both a `type` which is interpreted as the [`Device`] enum but also a
+    /// `device_type`. They don't always overlap, f.ex. a [`DeviceType::MotionSensor`] is a
+    /// [`Device::Sensor`] and all kinds of remotes are a [`Device::Controller`]. Use
+    /// [`DeviceType::expected_variant`] to map between them and [`Device::validate_kind`] to find
+    /// devices where the hub reports something unexpected. Types not known by this crate are kept
+    /// as [`DeviceType::Unknown`].
+    pub enum DeviceType {
+        LightController => "lightController",
+        Light => "light",
+        Gateway => "gateway",
+        MotionSensor => "motionSensor",
+        Outlet => "outlet",
+        Blinds => "blinds",
+        BlindsController => "blindsController",
+        ShortcutController => "shortcutController",
+        SoundController => "soundController",
+        AirPurifier => "airPurifier",
+        EnvironmentSensor => "environmentSensor",
+        OpenCloseSensor => "openCloseSensor",
+        WaterSensor => "waterSensor",
+        Repeater => "repeater",
+    }
+}
+
+impl DeviceType {
+    /// Get the [`DeviceKind`] a [`Device`] with this [`DeviceType`] is expected to be parsed as.
+    /// Returns [`None`] for types that this crate has no [`Device`] variant for.
+    pub fn expected_variant(&self) -> Option<DeviceKind> {
+        match self {
+            Self::LightController
+            | Self::BlindsController
+            | Self::ShortcutController
+            | Self::SoundController => Some(DeviceKind::Controller),
+            Self::Light => Some(DeviceKind::Light),
+            Self::Gateway => Some(DeviceKind::Gateway),
+            Self::MotionSensor | Self::EnvironmentSensor | Self::WaterSensor => {
+                Some(DeviceKind::Sensor)
+            }
+            Self::Outlet => Some(DeviceKind::Outlet),
+            Self::Blinds => Some(DeviceKind::Blinds),
+            Self::OpenCloseSensor => Some(DeviceKind::OpenCloseSensor),
+            Self::Repeater => Some(DeviceKind::Repeater),
+            Self::AirPurifier => Some(DeviceKind::AirPurifier),
+            Self::Unknown(_) => None,
+        }
+    }
+}
+
+/// The variant of a [`Device`] without its data, as returned by [`Device::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceKind {
+    AirPurifier,
+    Blinds,
+    Controller,
     Gateway,
-    MotionSensor,
+    Light,
+    OpenCloseSensor,
     Outlet,
-    Blinds,
-    BlindsController,
+    Repeater,
+    Sensor,
 }
 
-impl std::fmt::Display for DeviceType {
+impl std::fmt::Display for DeviceKind {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Self::LightController => f.pad("LightController"),
-            Self::Light => f.pad("Light"),
+            Self::AirPurifier => f.pad("AirPurifier"),
+            Self::Blinds => f.pad("Blinds"),
+            Self::Controller => f.pad("Controller"),
             Self::Gateway => f.pad("Gateway"),
-            Self::MotionSensor => f.pad("MotionSensor"),
+            Self::Light => f.pad("Light"),
+            Self::OpenCloseSensor => f.pad("OpenCloseSensor"),
             Self::Outlet => f.pad("Outlet"),
-            Self::Blinds => f.pad("BlindsController"),
-            Self::BlindsController => f.pad("BlindsController"),
+            Self::Repeater => f.pad("Repeater"),
+            Self::Sensor => f.pad("Sensor"),
         }
     }
 }
 
 /// A device can start in different modes. It can start on, off, same as previous or toggled. This
-/// is used f.ex. after a power outage.
-#[derive(Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+/// is used f.ex. after a power outage. Modes not known by this crate, f.ex. ones only reported by
+/// some firmware versions, are kept as [`Startup::Unknown`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub enum Startup {
     StartOn,
     StartOff,
     StartPrevious,
     StartToggle,
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+/// Newer lights support effects that animates the light, such as candlelight. Effects not known
+/// by this crate are kept as [`Effect::Unknown`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum Effect {
+    Off,
+    Candlelight,
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+/// The release channel the gateway gets firmware updates from. Users enrolled in IKEA's beta
+/// program can switch to a non production environment. Environments not known by this crate are
+/// kept as [`OtaEnvironment::Unknown`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum OtaEnvironment {
+    Production,
+    Beta,
+    Test,
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+/// A [`DeviceSet`] groups devices, f.ex. multiple bulbs behind one switch, so they can be
+/// controlled as one. The icon is only included when listing the sets, not on each [`Device`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct DeviceSet {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
 }
 
 /// The room which the [`Device`] is bound to. Icon and color represents what icon and color is
 /// selected in the IKEA [iPhone](https://apps.apple.com/se/app/ikea-home-smart/id1633226273) or
 /// [Android](https://play.google.com/store/apps/details?id=com.ikea.inter.homesmart.system2&hl=sv&pli=1)
-/// app.
-#[derive(Debug, Deserialize, PartialEq, PartialOrd)]
+/// app. Two [`Room`]s are equal if they have the same id.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Room {
-    pub id: String,
+    pub id: crate::RoomId,
     pub name: String,
-    pub color: String,
-    pub icon: String,
+    pub color: RoomColor,
+    pub icon: RoomIcon,
+}
+
+crate::string_enum! {
+    /// The color of a [`Room`]. The hub only accepts colors from the IKEA palette used by the app,
+    /// any other value is kept as [`RoomColor::Unknown`].
+    pub enum RoomColor {
+        Beige => "ikea_beige_1",
+        Blue => "ikea_blue_no_31",
+        Green => "ikea_green_no_65",
+        Lilac => "ikea_lilac_no_52",
+        Orange => "ikea_orange_no_18",
+        Pink => "ikea_pink_no_8",
+        Red => "ikea_red_no_39",
+        Turquoise => "ikea_turquoise_no_7",
+        Yellow => "ikea_yellow_no_24",
+    }
+}
+
+crate::string_enum! {
+    /// The icon of a [`Room`] as shown in the app. Icons not known by this crate are kept as
+    /// [`RoomIcon::Unknown`].
+    pub enum RoomIcon {
+        ArmChair => "rooms_arm_chair",
+        Bathtub => "rooms_bathtub",
+        Bed => "rooms_bed",
+        Box => "rooms_box",
+        Car => "rooms_car",
+        ChangingTable => "rooms_changing_table",
+        Desk => "rooms_desk",
+        DiningTable => "rooms_dining_table",
+        Door => "rooms_door",
+        Kitchen => "rooms_kitchen",
+        PlayArea => "rooms_play_area",
+        Plant => "rooms_plant",
+        Shower => "rooms_shower",
+        Sofa => "rooms_sofa",
+        Stairs => "rooms_stairs",
+        Toilet => "rooms_toilet",
+        Tv => "rooms_tv",
+        WashingMachine => "rooms_washing_machine",
+    }
 }
 
 /// Each [`Device`] has attributes that's unique to the specific [`Device`]. Here however they're
@@ -126,8 +339,14 @@ pub struct Room {
 /// <div class="warning">
 /// This is not optimal and will most likely change in a future version.
 /// </div>
-#[derive(Debug, Deserialize, PartialEq, PartialOrd)]
+///
+/// Attributes that are not (yet) modeled are kept in [`Attributes::extra`] so they're still
+/// accessible and will be included when serializing the attributes again. With the `strict`
+/// feature flag unknown attributes fail the parsing instead.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Attributes {
     pub custom_name: String,
     pub firmware_version: String,
@@ -136,9 +355,21 @@ pub struct Attributes {
     pub model: String,
     pub ota_policy: String,
     pub ota_progress: u8,
-    pub ota_schedule_end: String,
-    pub ota_schedule_start: String,
+    // Not sent by all firmware versions, and values that can't be parsed are ignored.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_time_optional",
+        serialize_with = "serialize_time_optional"
+    )]
+    pub ota_schedule_end: Option<crate::TimeOfDay>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_time_optional",
+        serialize_with = "serialize_time_optional"
+    )]
+    pub ota_schedule_start: Option<crate::TimeOfDay>,
     pub ota_state: String,
+    pub ota_environment: Option<OtaEnvironment>,
     pub ota_status: String,
     pub product_code: Option<String>,
     pub serial_number: String,
@@ -146,62 +377,453 @@ pub struct Attributes {
     // Light, controller and outlet
     pub is_on: Option<bool>,
 
-    // Light and outlet
+    // Light and outlet. Some devices report `null` if no mode is set, which is parsed as `None`.
+    #[serde(default)]
     pub startup_on_off: Option<Startup>,
 
     // Light
-    pub light_level: Option<u8>,
+    pub light_level: Option<Percent>,
+    #[serde(default)]
     pub permitting_join: bool,
     pub color_mode: Option<String>,
-    pub color_temperature: Option<u16>,
-    pub color_temperature_min: Option<u16>,
-    pub color_temperature_max: Option<u16>,
+    pub color_temperature: Option<Kelvin>,
+    pub color_temperature_min: Option<Kelvin>,
+    pub color_temperature_max: Option<Kelvin>,
+    /// The color temperature in kelvin to use after a power outage, `-1` means the previous value.
     pub startup_temperature: Option<i16>,
+    /// The light level to use after a power outage, `-1` means the previous value.
+    pub startup_light_level: Option<i16>,
+    /// The hue to use after a power outage, `-1` means the previous value.
+    pub startup_color_hue: Option<f64>,
+    /// The saturation to use after a power outage, `-1` means the previous value.
+    pub startup_color_saturation: Option<f64>,
     pub color_hue: Option<f64>,
     pub color_saturation: Option<f64>,
     pub circadian_rhythm_mode: Option<String>,
+    pub effect: Option<Effect>,
 
     // Controller
     pub battery_percentage: Option<i8>,
 
     // Blinds and controller
-    pub blinds_current_level: Option<u8>,
-    pub blinds_target_level: Option<u8>,
+    pub blinds_current_level: Option<Percent>,
+    pub blinds_target_level: Option<Percent>,
     pub blinds_state: Option<String>,
 
-    // Environment sensor
-    pub current_temperature: Option<u8>,
-    pub current_r_h: Option<u8>,
-    pub current_p_m25: Option<u8>,
-    pub max_measured_p_m25: Option<u8>,
-    pub min_measured_p_m25: Option<u8>,
-    pub voc_index: Option<u8>,
+    // Environment sensor and air purifier
+    pub current_temperature: Option<Celsius>,
+    pub current_r_h: Option<Percent>,
+    pub current_p_m25: Option<u16>,
+    pub max_measured_p_m25: Option<u16>,
+    pub min_measured_p_m25: Option<u16>,
+    pub voc_index: Option<u16>,
 
     // Open and close sensor
     pub is_open: Option<bool>,
+
+    // Water sensor
+    pub water_leak_detected: Option<bool>,
+
+    // Motion sensor
+    pub is_detected: Option<bool>,
+    pub sensor_config: Option<SensorConfig>,
+
+    // Everything not listed above
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// Configuration of a motion sensor which controls for how long lights stay on and if the sensor
+/// is only active during parts of the day.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct SensorConfig {
+    /// If the sensor only triggers within the [`SensorConfig::schedule`].
+    pub schedule_on: bool,
+    /// Seconds the lights are kept on after the last detected motion.
+    pub on_duration: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<SensorSchedule>,
+}
+
+/// The part of the day when a motion sensor is active, f.ex. between sunset and sunrise.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct SensorSchedule {
+    pub on_condition: SensorScheduleCondition,
+    pub off_condition: SensorScheduleCondition,
+}
+
+/// When a [`SensorSchedule`] starts or ends. The offset is in minutes from sunrise or sunset and
+/// ignored for a fixed time.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct SensorScheduleCondition {
+    pub time: SensorScheduleTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i32>,
+}
+
+/// A point in time for a [`SensorScheduleCondition`]. The hub represents this as either
+/// `sunrise`, `sunset` or a time formatted as `HH:MM` in the hub's timezone. It's only `Copy`
+/// with the `chrono` feature flag since the time is kept as a string without it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "chrono", derive(Copy))]
+pub enum SensorScheduleTime {
+    Sunrise,
+    Sunset,
+    At(crate::TimeOfDay),
+}
+
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for SensorScheduleTime {
+    fn schema_name() -> String {
+        "SensorScheduleTime".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        String::json_schema(gen)
+    }
+}
+
+impl Serialize for SensorScheduleTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Sunrise => serializer.serialize_str("sunrise"),
+            Self::Sunset => serializer.serialize_str("sunset"),
+            Self::At(time) => serialize_time(time, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SensorScheduleTime {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::IntoDeserializer;
+
+        let value = String::deserialize(deserializer)?;
+
+        match value.as_str() {
+            "sunrise" => Ok(Self::Sunrise),
+            "sunset" => Ok(Self::Sunset),
+            _ => deserialize_time(value.into_deserializer()).map(Self::At),
+        }
+    }
+}
+
+impl SensorSchedule {
+    /// A schedule where the sensor is only active between sunset and sunrise.
+    pub fn sunset_to_sunrise() -> Self {
+        Self {
+            on_condition: SensorScheduleCondition {
+                time: SensorScheduleTime::Sunset,
+                offset: None,
+            },
+            off_condition: SensorScheduleCondition {
+                time: SensorScheduleTime::Sunrise,
+                offset: None,
+            },
+        }
+    }
+}
+
+/// A single attribute that differs between two snapshots of the same [`Device`], see
+/// [`DeviceData::diff`]. The attribute name is the snake case field name in [`Attributes`] and a
+/// missing value is represented as [`serde_json::Value::Null`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AttributeChange {
+    pub attribute: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+impl std::fmt::Display for AttributeChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} → {}", self.attribute, self.old, self.new)
+    }
+}
+
+/// A guideline for the longest name of a [`Device`] or [`DeviceSet`], based on what fits in the
+/// IKEA app. The hub doesn't document a limit so this isn't enforced by the
+/// [`Hub`](crate::hub::Hub), use [`validate_name_length`] to check it before sending a name.
+pub const MAX_NAME_LENGTH: usize = 40;
+
+/// Check that a name can be used for a [`Device`] or [`DeviceSet`] before sending it to the hub.
+/// The name can't be empty or contain control characters such as newlines. The length isn't
+/// checked, see [`validate_name_length`].
+pub fn validate_name(name: &str) -> Result<(), crate::error::NameError> {
+    if name.trim().is_empty() {
+        return Err(crate::error::NameError::Empty);
+    }
+
+    if let Some(c) = name.chars().find(|c| c.is_control()) {
+        return Err(crate::error::NameError::InvalidCharacter(c));
+    }
+
+    Ok(())
+}
+
+/// Check that a name isn't longer than the [`MAX_NAME_LENGTH`] guideline. This is a client side
+/// check only, the hub may accept longer names.
+pub fn validate_name_length(name: &str) -> Result<(), crate::error::NameError> {
+    let length = name.chars().count();
+    if length > MAX_NAME_LENGTH {
+        return Err(crate::error::NameError::TooLong {
+            length,
+            max: MAX_NAME_LENGTH,
+        });
+    }
+
+    Ok(())
+}
+
+impl PartialEq for Device {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner().id == other.inner().id
+    }
+}
+
+impl Eq for Device {}
+
+impl std::hash::Hash for Device {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.inner().id.hash(state);
+    }
+}
+
+impl PartialEq for Room {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Room {}
+
+impl std::hash::Hash for Room {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl Device {
+    /// Get the [`Device`] as a [`Light`](crate::typed::Light) if it can be turned on and off.
+    pub fn as_light(&mut self) -> Option<crate::typed::Light<'_>> {
+        crate::typed::Light::new(self)
+    }
+
+    /// Get the [`Device`] as a [`Dimmable`](crate::typed::Dimmable) if it has a light level.
+    pub fn as_dimmable(&mut self) -> Option<crate::typed::Dimmable<'_>> {
+        crate::typed::Dimmable::new(self)
+    }
+
+    /// Get the [`Device`] as a [`Colorable`](crate::typed::Colorable) if it has hue and
+    /// saturation.
+    pub fn as_colorable(&mut self) -> Option<crate::typed::Colorable<'_>> {
+        crate::typed::Colorable::new(self)
+    }
+
+    /// Get the [`Device`] as a [`Blind`](crate::typed::Blind) if it has a target level.
+    pub fn as_blind(&mut self) -> Option<crate::typed::Blind<'_>> {
+        crate::typed::Blind::new(self)
+    }
+
+    /// Check if the [`Device`] can send or receive the [`Capability`].
+    pub fn has_capability(&self, capability: Capability) -> bool {
+        let capabilities = &self.inner().capabilities;
+
+        capabilities.can_send.contains(&capability)
+            || capabilities.can_receive.contains(&capability)
+    }
+
+    /// Get an attribute for a [`Capability`]. Fails with
+    /// [`CapabilityError::Unsupported`](crate::error::CapabilityError::Unsupported) if the
+    /// [`Device`] doesn't have the capability and with
+    /// [`CapabilityError::Missing`](crate::error::CapabilityError::Missing) if it has the
+    /// capability but the hub didn't report a value.
+    fn capability_attribute<T>(
+        &self,
+        capability: Capability,
+        value: Option<T>,
+    ) -> Result<T, crate::error::CapabilityError> {
+        if !self.has_capability(capability.clone()) {
+            return Err(crate::error::CapabilityError::Unsupported(capability));
+        }
+
+        value.ok_or(crate::error::CapabilityError::Missing(capability))
+    }
+
+    /// Check if the [`Device`] is on. See [`CapabilityError`](crate::error::CapabilityError) for
+    /// why it can fail.
+    pub fn is_on(&self) -> Result<bool, crate::error::CapabilityError> {
+        self.capability_attribute(Capability::IsOn, self.inner().attributes.is_on)
+    }
+
+    /// Get the light level of the [`Device`]. See
+    /// [`CapabilityError`](crate::error::CapabilityError) for why it can fail.
+    pub fn light_level(&self) -> Result<Percent, crate::error::CapabilityError> {
+        self.capability_attribute(Capability::LightLevel, self.inner().attributes.light_level)
+    }
+
+    /// Get the color temperature of the [`Device`]. See
+    /// [`CapabilityError`](crate::error::CapabilityError) for why it can fail.
+    pub fn color_temperature(&self) -> Result<Kelvin, crate::error::CapabilityError> {
+        self.capability_attribute(
+            Capability::ColorTemperature,
+            self.inner().attributes.color_temperature,
+        )
+    }
+
+    /// Get the hue of the [`Device`]. See [`CapabilityError`](crate::error::CapabilityError) for
+    /// why it can fail.
+    pub fn color_hue(&self) -> Result<f64, crate::error::CapabilityError> {
+        self.capability_attribute(Capability::ColorHue, self.inner().attributes.color_hue)
+    }
+
+    /// Get the saturation of the [`Device`]. See [`CapabilityError`](crate::error::CapabilityError)
+    /// for why it can fail.
+    pub fn color_saturation(&self) -> Result<f64, crate::error::CapabilityError> {
+        self.capability_attribute(
+            Capability::ColorSaturation,
+            self.inner().attributes.color_saturation,
+        )
+    }
+
+    /// Get the current level of the blinds. See [`CapabilityError`](crate::error::CapabilityError)
+    /// for why it can fail.
+    pub fn blinds_current_level(&self) -> Result<Percent, crate::error::CapabilityError> {
+        self.capability_attribute(
+            Capability::BlindsCurrentLevel,
+            self.inner().attributes.blinds_current_level,
+        )
+    }
+
+    /// Get the level the blinds are moving to. See
+    /// [`CapabilityError`](crate::error::CapabilityError) for why it can fail.
+    pub fn blinds_target_level(&self) -> Result<Percent, crate::error::CapabilityError> {
+        self.capability_attribute(
+            Capability::BlindsTargetLevel,
+            self.inner().attributes.blinds_target_level,
+        )
+    }
+}
+
+impl DeviceData {
+    /// Get all attributes that changed from `self` to `other`, sorted by attribute name. This is
+    /// meant to compare two snapshots of the same device, f.ex. before and after polling.
+    pub fn diff(&self, other: &DeviceData) -> Vec<AttributeChange> {
+        let as_map = |attributes: &Attributes| match serde_json::to_value(attributes) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+
+        let old = as_map(&self.attributes);
+        let new = as_map(&other.attributes);
+
+        let names: std::collections::BTreeSet<_> = old.keys().chain(new.keys()).collect();
+
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let old = old.get(name).unwrap_or(&serde_json::Value::Null);
+                let new = new.get(name).unwrap_or(&serde_json::Value::Null);
+
+                (old != new).then(|| AttributeChange {
+                    attribute: to_snake_case(name),
+                    old: old.clone(),
+                    new: new.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+
+    snake
 }
 
 impl Device {
     /// Get a reference to the [`DeviceData`] for the [`Device`].
     pub fn inner(&self) -> &DeviceData {
         match self {
+            Device::AirPurifier(inner) => inner,
             Device::Blinds(inner) => inner,
             Device::Controller(inner) => inner,
             Device::Gateway(inner) => inner,
             Device::Light(inner) => inner,
+            Device::OpenCloseSensor(inner) => inner,
             Device::Outlet(inner) => inner,
+            Device::Repeater(inner) => inner,
             Device::Sensor(inner) => inner,
         }
     }
 
+    /// Get the [`DeviceKind`] of the [`Device`], i.e. which variant it was parsed as.
+    pub fn kind(&self) -> DeviceKind {
+        match self {
+            Device::AirPurifier(_) => DeviceKind::AirPurifier,
+            Device::Blinds(_) => DeviceKind::Blinds,
+            Device::Controller(_) => DeviceKind::Controller,
+            Device::Gateway(_) => DeviceKind::Gateway,
+            Device::Light(_) => DeviceKind::Light,
+            Device::OpenCloseSensor(_) => DeviceKind::OpenCloseSensor,
+            Device::Outlet(_) => DeviceKind::Outlet,
+            Device::Repeater(_) => DeviceKind::Repeater,
+            Device::Sensor(_) => DeviceKind::Sensor,
+        }
+    }
+
+    /// Check that the `type` and the `device_type` reported by the hub agree, i.e. that the
+    /// [`Device::kind`] is the [`DeviceType::expected_variant`]. A mismatch usually means the
+    /// hub has started reporting a new kind of device that this crate doesn't handle correctly
+    /// yet.
+    pub fn validate_kind(&self) -> Result<(), crate::error::KindMismatch> {
+        let device_type = &self.inner().device_type;
+        let kind = self.kind();
+
+        if device_type.expected_variant() == Some(kind) {
+            return Ok(());
+        }
+
+        Err(crate::error::KindMismatch {
+            device_type: device_type.clone(),
+            kind,
+        })
+    }
+
+    /// Get the JSON the [`Device`] was parsed from, including fields that aren't modeled by this
+    /// crate. Changes made through the [`Hub`](crate::hub::Hub) are not reflected here.
+    pub fn raw(&self) -> &serde_json::Value {
+        &self.inner().raw
+    }
+
     /// Get a mutable reference to the [`DeviceData`] for the [`Device`].
     pub fn inner_mut(&mut self) -> &mut DeviceData {
         match self {
+            Device::AirPurifier(ref mut inner) => inner,
             Device::Blinds(ref mut inner) => inner,
             Device::Controller(ref mut inner) => inner,
             Device::Gateway(ref mut inner) => inner,
             Device::Light(ref mut inner) => inner,
+            Device::OpenCloseSensor(ref mut inner) => inner,
             Device::Outlet(ref mut inner) => inner,
+            Device::Repeater(ref mut inner) => inner,
             Device::Sensor(ref mut inner) => inner,
         }
     }
@@ -0,0 +1,78 @@
+//! Find hubs on the local network with mDNS. Each hub announces itself as a
+//! [`SERVICE_TYPE`] service, so the IP address doesn't have to be looked up in the router:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! for hub in dirigera::discovery::discover(dirigera::discovery::DEFAULT_TIMEOUT).await? {
+//!     println!("{} at {}", hub.name, hub.ip_address);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! It's only available behind the `discovery` feature flag.
+
+/// The mDNS service type announced by the hub.
+pub const SERVICE_TYPE: &str = "_ihsp._tcp.local.";
+
+/// How long to wait for hubs to answer if nothing else is set.
+pub const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// A hub found on the local network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredHub {
+    /// The name of the mDNS service, f.ex. `gw2-abc123`.
+    pub name: String,
+    pub hostname: String,
+    pub ip_address: std::net::Ipv4Addr,
+    pub port: u16,
+}
+
+impl std::fmt::Display for DiscoveredHub {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.ip_address)
+    }
+}
+
+/// Find all hubs that answer within `timeout`, sorted by name. An empty list means that no hub
+/// was found, errors are only returned if the network can't be used for mDNS.
+pub async fn discover(timeout: std::time::Duration) -> anyhow::Result<Vec<DiscoveredHub>> {
+    let daemon = mdns_sd::ServiceDaemon::new()?;
+    let receiver = daemon.browse(SERVICE_TYPE)?;
+
+    let mut hubs: Vec<DiscoveredHub> = Vec::new();
+
+    let _ = tokio::time::timeout(timeout, async {
+        while let Ok(event) = receiver.recv_async().await {
+            let mdns_sd::ServiceEvent::ServiceResolved(info) = event else {
+                continue;
+            };
+
+            let name = info
+                .get_fullname()
+                .trim_end_matches(SERVICE_TYPE)
+                .trim_end_matches('.')
+                .to_string();
+
+            for ip_address in info.get_addresses_v4() {
+                let hub = DiscoveredHub {
+                    name: name.clone(),
+                    hostname: info.get_hostname().trim_end_matches('.').to_string(),
+                    ip_address: *ip_address,
+                    port: info.get_port(),
+                };
+
+                if !hubs.contains(&hub) {
+                    hubs.push(hub);
+                }
+            }
+        }
+    })
+    .await;
+
+    let _ = daemon.shutdown();
+
+    hubs.sort_by(|a, b| (&a.name, a.ip_address).cmp(&(&b.name, b.ip_address)));
+
+    Ok(hubs)
+}
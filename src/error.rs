@@ -0,0 +1,251 @@
+//! Errors returned by the hub. All methods on the [`Hub`](crate::hub::Hub) return an
+//! [`anyhow::Error`] but when the hub responds with an unsuccessful status the error is an
+//! [`ApiError`] which can be inspected with [`anyhow::Error::downcast_ref`]:
+//!
+//! ```no_run
+//! # async fn example(hub: &mut dirigera::hub::Hub) {
+//! if let Err(err) = hub.device("abc123_1").await {
+//!     if let Some(api_error) = err.downcast_ref::<dirigera::error::ApiError>() {
+//!         eprintln!("{} responded with {}", api_error.endpoint, api_error.status);
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! Requests that never got a response, f.ex. since the hub is offline, fail with a
+//! [`ConnectionError`] instead.
+use serde::Deserialize;
+
+/// An [`ApiError`] is an unsuccessful response from the hub. If the hub responded with its error
+/// envelope, `{"error": ..., "message": ...}`, those fields are parsed. The raw body is always
+/// kept in [`ApiError::body`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    pub method: http::Method,
+    /// The path of the request, f.ex. `/devices/abc123_1`.
+    pub endpoint: String,
+    pub status: http::StatusCode,
+    /// The id of the request if the hub included one in the response.
+    pub request_id: Option<String>,
+    pub error: Option<String>,
+    pub message: Option<String>,
+    pub body: String,
+    /// How long the hub asked us to wait before trying again, from the `Retry-After` header.
+    pub retry_after: Option<std::time::Duration>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    error: Option<String>,
+    message: Option<String>,
+    request_id: Option<String>,
+}
+
+impl ApiError {
+    pub(crate) async fn from_response(
+        method: http::Method,
+        endpoint: &str,
+        response: http::Response<hyper::Body>,
+    ) -> Self {
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body)
+            .await
+            .map(|body| String::from_utf8_lossy(&body).into_owned())
+            .unwrap_or_default();
+
+        let envelope: Envelope = serde_json::from_str(&body).unwrap_or_default();
+        let retry_after = parts
+            .headers
+            .get(http::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(std::time::Duration::from_secs);
+        let request_id = parts
+            .headers
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .or(envelope.request_id);
+
+        Self {
+            method,
+            endpoint: endpoint.to_string(),
+            status: parts.status,
+            request_id,
+            error: envelope.error,
+            message: envelope.message,
+            body,
+            retry_after,
+        }
+    }
+
+    /// Check if the hub responded that it's too busy to handle the request, either with `429 Too
+    /// Many Requests` or `503 Service Unavailable`.
+    pub fn is_busy(&self) -> bool {
+        self.status == http::StatusCode::TOO_MANY_REQUESTS
+            || self.status == http::StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} failed with {}",
+            self.method, self.endpoint, self.status
+        )?;
+
+        match (&self.error, &self.message) {
+            (Some(error), Some(message)) => write!(f, ": {error}: {message}")?,
+            (Some(error), None) => write!(f, ": {error}")?,
+            (None, Some(message)) => write!(f, ": {message}")?,
+            (None, None) if !self.body.is_empty() => write!(f, ": {}", self.body)?,
+            (None, None) => {}
+        }
+
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request id {request_id})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// A [`ConnectionError`] is a request that never got a response from the hub, f.ex. since the hub
+/// is offline or has a new IP address. Unlike an [`ApiError`] the hub may not have seen the request
+/// at all.
+#[derive(Debug)]
+pub enum ConnectionError {
+    /// The request couldn't be sent or the response couldn't be read.
+    Failed {
+        method: http::Method,
+        endpoint: String,
+        source: anyhow::Error,
+    },
+    /// No response was read within the timeout set with
+    /// [`Hub::with_timeout`](crate::hub::Hub::with_timeout).
+    TimedOut {
+        method: http::Method,
+        endpoint: String,
+        after: std::time::Duration,
+    },
+}
+
+impl std::fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Failed {
+                method,
+                endpoint,
+                source,
+            } => write!(f, "{method} {endpoint} failed: {source}"),
+            Self::TimedOut {
+                method,
+                endpoint,
+                after,
+            } => write!(f, "{method} {endpoint} timed out after {after:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ConnectionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Failed { source, .. } => Some(source.as_ref()),
+            Self::TimedOut { .. } => None,
+        }
+    }
+}
+
+/// The reason a name for a [`Device`](crate::Device) or
+/// [`DeviceSet`](crate::device::DeviceSet) was rejected before it was sent to the hub, see
+/// [`validate_name`](crate::device::validate_name) and
+/// [`validate_name_length`](crate::device::validate_name_length).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameError {
+    /// The name is empty or only whitespace.
+    Empty,
+    /// The name is longer than the [`MAX_NAME_LENGTH`](crate::device::MAX_NAME_LENGTH) guideline.
+    TooLong { length: usize, max: usize },
+    /// The name contains a character the hub doesn't accept, f.ex. a newline.
+    InvalidCharacter(char),
+}
+
+impl std::fmt::Display for NameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "name cannot be empty"),
+            Self::TooLong { length, max } => {
+                write!(f, "name is {length} characters, max is {max}")
+            }
+            Self::InvalidCharacter(c) => write!(f, "name contains invalid character {c:?}"),
+        }
+    }
+}
+
+impl std::error::Error for NameError {}
+
+/// A [`Device`](crate::Device) whose `type` doesn't match its
+/// [`DeviceType`](crate::device::DeviceType), see
+/// [`Device::validate_kind`](crate::Device::validate_kind).
+#[derive(Debug, Clone, PartialEq)]
+pub struct KindMismatch {
+    pub device_type: crate::device::DeviceType,
+    pub kind: crate::device::DeviceKind,
+}
+
+impl std::fmt::Display for KindMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.device_type.expected_variant() {
+            Some(expected) => write!(
+                f,
+                "device type {} should be {expected} but was {}",
+                self.device_type, self.kind
+            ),
+            None => write!(
+                f,
+                "device type {} isn't expected as any kind but was {}",
+                self.device_type, self.kind
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KindMismatch {}
+
+/// The reason an attribute couldn't be read from a [`Device`](crate::Device), f.ex. with
+/// [`Device::light_level`](crate::Device::light_level).
+#[derive(Debug, Clone, PartialEq)]
+pub enum CapabilityError {
+    /// The device doesn't have the capability, f.ex. a light level for a light that can only be
+    /// turned on and off.
+    Unsupported(crate::device::Capability),
+    /// The device has the capability but the hub didn't report a value, f.ex. because the device
+    /// is unreachable.
+    Missing(crate::device::Capability),
+}
+
+impl CapabilityError {
+    /// Get the [`Capability`](crate::device::Capability) that was asked for.
+    pub fn capability(&self) -> &crate::device::Capability {
+        match self {
+            Self::Unsupported(capability) | Self::Missing(capability) => capability,
+        }
+    }
+}
+
+impl std::fmt::Display for CapabilityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Unsupported(capability) => write!(f, "device doesn't support {capability:?}"),
+            Self::Missing(capability) => {
+                write!(f, "device didn't report a value for {capability:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CapabilityError {}
@@ -0,0 +1,1186 @@
+//! The hub pushes events over a WebSocket whenever something changes, f.ex. when a light is turned
+//! on or a scene is triggered. An [`EventListener`] keeps that connection alive in the background
+//! and will reconnect with exponential backoff whenever the connection is lost. Since events may
+//! have been missed while disconnected, an [`Event::Resync`] is emitted after each reconnect so
+//! consumers know to refetch their state. Events can be filtered with an [`EventFilter`] so only
+//! events for the devices you care about are passed on. The events can be consumed one by one, as
+//! a [`Stream`](futures_util::Stream) or by multiple independent subscribers through a
+//! [`tokio::sync::broadcast`] channel. It's only available behind the `events` feature flag.
+//!
+//! Use [`HubEvent::kind`] to get an [`EventKind`] for each event sent by the hub that can be
+//! matched exhaustively instead of looking at the raw `event_type` and `data`.
+//!
+//! Water leaks are reported as [`Event::WaterLeakDetected`] and [`Event::WaterLeakCleared`] to
+//! every listener, even if it has an [`EventFilter`] that doesn't match the sensor, since they're
+//! never something you want to miss.
+use crate::deserialize_datetime;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+
+use std::collections::HashMap;
+
+/// An [`Event`] is either something sent by the hub or something that happened to the
+/// connection itself.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// An event sent by the hub.
+    Hub(HubEvent),
+    /// An open/close sensor reported that it was opened. This is sent right after the
+    /// [`Event::Hub`] event it was created from.
+    DoorOpened {
+        device_id: crate::DeviceId,
+        time: chrono::DateTime<chrono::Utc>,
+    },
+    /// An open/close sensor reported that it was closed. This is sent right after the
+    /// [`Event::Hub`] event it was created from.
+    DoorClosed {
+        device_id: crate::DeviceId,
+        time: chrono::DateTime<chrono::Utc>,
+    },
+    /// A water sensor detected a leak. This is never filtered by an [`EventFilter`].
+    WaterLeakDetected {
+        device_id: crate::DeviceId,
+        time: chrono::DateTime<chrono::Utc>,
+    },
+    /// A water sensor no longer detects a leak. This is never filtered by an [`EventFilter`].
+    WaterLeakCleared {
+        device_id: crate::DeviceId,
+        time: chrono::DateTime<chrono::Utc>,
+    },
+    /// One or more attributes of a [`Device`](crate::Device) changed, with both the previous and
+    /// the new value. This is sent right after the [`Event::Hub`] event it was created from.
+    /// Attributes reported with the same value as before are left out, so turning on a light
+    /// that was already on doesn't result in this event. The previous values are the values from
+    /// the events since the listener connected, so an attribute that was never seen has
+    /// [`serde_json::Value::Null`] as the old value. Set [`EventOptions::fetch_state`] to start
+    /// from the state of all devices on every connect instead.
+    AttributesChanged {
+        device_id: crate::DeviceId,
+        changes: Vec<crate::device::AttributeChange>,
+        time: chrono::DateTime<chrono::Utc>,
+    },
+    /// The hub is reached at a new IP address, f.ex. after it was found with
+    /// [`Hub::with_rediscovery`](crate::hub::Hub::with_rediscovery) or the credentials were
+    /// replaced. This is sent when the listener has connected to the new address, right before the
+    /// [`Event::Resync`].
+    AddressChanged {
+        previous: std::net::Ipv4Addr,
+        ip_address: std::net::Ipv4Addr,
+        time: chrono::DateTime<chrono::Utc>,
+    },
+    /// The connection was lost and has been re-established. Events that happened while
+    /// disconnected are lost so any cached state should be refetched. It's also returned by
+    /// [`EventListener::next`] and [`EventListener::stream`] instead of the events that were missed
+    /// because they weren't consumed fast enough, the number of missed events isn't kept.
+    Resync,
+}
+
+/// A [`HubEvent`] is the raw message sent by the hub. The `data` depends on the `event_type`, for
+/// device events it's (a part of) the [`Device`](crate::Device).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HubEvent {
+    pub id: String,
+    #[serde(deserialize_with = "deserialize_datetime")]
+    pub time: chrono::DateTime<chrono::Utc>,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+/// The [`EventKind`] is a typed view of what a [`HubEvent`] is about, see [`HubEvent::kind`].
+/// Events that aren't known, or that are missing the expected ids, are
+/// [`EventKind::Unknown`] with the `data` of the event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    /// One or more attributes of a [`Device`](crate::Device) changed. Only the attributes that
+    /// changed are included.
+    DeviceStateChanged {
+        device_id: crate::DeviceId,
+        changed_attributes: serde_json::Map<String, serde_json::Value>,
+    },
+    /// A button on a remote or shortcut button was pressed.
+    ButtonPressed {
+        device_id: crate::DeviceId,
+        click_pattern: ClickPattern,
+    },
+    /// A [`Device`](crate::Device) was paired with the hub.
+    DeviceAdded {
+        device_id: crate::DeviceId,
+    },
+    /// A [`Device`](crate::Device) was removed from the hub.
+    DeviceRemoved {
+        device_id: crate::DeviceId,
+    },
+    SceneCreated {
+        scene_id: crate::SceneId,
+    },
+    SceneUpdated {
+        scene_id: crate::SceneId,
+    },
+    SceneDeleted {
+        scene_id: crate::SceneId,
+    },
+    SceneTriggered {
+        scene_id: crate::SceneId,
+    },
+    /// A [`Room`](crate::device::Room) was created, updated or deleted.
+    RoomChanged {
+        room_id: crate::RoomId,
+    },
+    /// The status of the hub itself changed, f.ex. its time zone or firmware.
+    HubStatusChanged {
+        changed_attributes: serde_json::Map<String, serde_json::Value>,
+    },
+    Unknown(serde_json::Value),
+}
+
+crate::string_enum! {
+    /// How a button was pressed in an [`EventKind::ButtonPressed`] event.
+    pub enum ClickPattern {
+        SinglePress => "singlePress",
+        DoublePress => "doublePress",
+        LongPress => "longPress",
+    }
+}
+
+impl HubEvent {
+    /// Get the [`EventKind`] of the event.
+    pub fn kind(&self) -> EventKind {
+        let id = self.data.get("id").and_then(serde_json::Value::as_str);
+        let changed_attributes = || {
+            self.data
+                .get("attributes")
+                .and_then(serde_json::Value::as_object)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        match (self.event_type.as_str(), id) {
+            ("deviceStateChanged", Some(id)) => EventKind::DeviceStateChanged {
+                device_id: id.into(),
+                changed_attributes: changed_attributes(),
+            },
+            ("remotePressEvent", Some(id)) => EventKind::ButtonPressed {
+                device_id: id.into(),
+                click_pattern: self
+                    .data
+                    .get("clickPattern")
+                    .and_then(serde_json::Value::as_str)
+                    .unwrap_or_default()
+                    .into(),
+            },
+            ("deviceAdded", Some(id)) => EventKind::DeviceAdded {
+                device_id: id.into(),
+            },
+            ("deviceRemoved", Some(id)) => EventKind::DeviceRemoved {
+                device_id: id.into(),
+            },
+            ("sceneCreated", Some(id)) => EventKind::SceneCreated {
+                scene_id: id.into(),
+            },
+            ("sceneUpdated", Some(id)) => EventKind::SceneUpdated {
+                scene_id: id.into(),
+            },
+            ("sceneDeleted", Some(id)) => EventKind::SceneDeleted {
+                scene_id: id.into(),
+            },
+            ("sceneTriggered", Some(id)) => EventKind::SceneTriggered {
+                scene_id: id.into(),
+            },
+            ("roomCreated" | "roomUpdated" | "roomDeleted", Some(id)) => {
+                EventKind::RoomChanged { room_id: id.into() }
+            }
+            ("hubStatusChanged", _) => EventKind::HubStatusChanged {
+                changed_attributes: changed_attributes(),
+            },
+            _ => EventKind::Unknown(self.data.clone()),
+        }
+    }
+
+    /// Get the id of the [`Device`](crate::Device) the event is about, if it's a device event.
+    pub fn device_id(&self) -> Option<&str> {
+        if !self.event_type.starts_with("device") {
+            return None;
+        }
+
+        self.data.get("id").and_then(serde_json::Value::as_str)
+    }
+
+    /// Get the typed [`Event`] for an open/close sensor changing state, if this is such an event.
+    pub fn door_event(&self) -> Option<Event> {
+        if self.event_type != "deviceStateChanged"
+            || self.device_type() != Some(crate::DeviceType::OpenCloseSensor)
+        {
+            return None;
+        }
+
+        let device_id = self.device_id()?.into();
+
+        Some(if self.attribute_bool("isOpen")? {
+            Event::DoorOpened {
+                device_id,
+                time: self.time,
+            }
+        } else {
+            Event::DoorClosed {
+                device_id,
+                time: self.time,
+            }
+        })
+    }
+
+    /// Get the typed [`Event`] for a water sensor detecting or no longer detecting a leak, if
+    /// this is such an event.
+    pub fn water_leak_event(&self) -> Option<Event> {
+        if self.event_type != "deviceStateChanged"
+            || self.device_type() != Some(crate::DeviceType::WaterSensor)
+        {
+            return None;
+        }
+
+        let device_id = self.device_id()?.into();
+
+        Some(if self.attribute_bool("waterLeakDetected")? {
+            Event::WaterLeakDetected {
+                device_id,
+                time: self.time,
+            }
+        } else {
+            Event::WaterLeakCleared {
+                device_id,
+                time: self.time,
+            }
+        })
+    }
+
+    pub(crate) fn attribute_bool(&self, name: &str) -> Option<bool> {
+        self.data
+            .get("attributes")
+            .and_then(|attributes| attributes.get(name))
+            .and_then(serde_json::Value::as_bool)
+    }
+
+    pub(crate) fn room_id(&self) -> Option<&str> {
+        self.data
+            .get("room")
+            .and_then(|room| room.get("id"))
+            .and_then(serde_json::Value::as_str)
+    }
+
+    fn device_type(&self) -> Option<crate::DeviceType> {
+        self.data
+            .get("deviceType")
+            .and_then(|device_type| crate::DeviceType::deserialize(device_type).ok())
+    }
+}
+
+/// An [`EventHandler`] gets a call for each kind of [`Event`] when passed to
+/// [`EventListener::dispatch`], so only the methods for the events you care about have to be
+/// implemented. All methods do nothing by default. Hub events are passed to the method for their
+/// [`EventKind`] or to [`EventHandler::on_other`] if there's no specific method. Note that a door
+/// or water leak event, as well as [`Event::AttributesChanged`], also results in a call to
+/// [`EventHandler::on_device_changed`] for the event it was created from.
+pub trait EventHandler: Send {
+    /// Attributes of a [`Device`](crate::Device) changed, see [`EventKind::DeviceStateChanged`].
+    fn on_device_changed(
+        &mut self,
+        device_id: crate::DeviceId,
+        changed_attributes: serde_json::Map<String, serde_json::Value>,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        let _ = (device_id, changed_attributes);
+        async {}
+    }
+
+    /// A button was pressed, see [`EventKind::ButtonPressed`].
+    fn on_button_pressed(
+        &mut self,
+        device_id: crate::DeviceId,
+        click_pattern: ClickPattern,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        let _ = (device_id, click_pattern);
+        async {}
+    }
+
+    /// An open/close sensor was opened or closed, see [`Event::DoorOpened`] and
+    /// [`Event::DoorClosed`].
+    fn on_door_changed(
+        &mut self,
+        device_id: crate::DeviceId,
+        is_open: bool,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        let _ = (device_id, is_open);
+        async {}
+    }
+
+    /// A water sensor detected a leak, see [`Event::WaterLeakDetected`].
+    fn on_leak_detected(
+        &mut self,
+        device_id: crate::DeviceId,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        let _ = device_id;
+        async {}
+    }
+
+    /// A water sensor no longer detects a leak, see [`Event::WaterLeakCleared`].
+    fn on_leak_cleared(
+        &mut self,
+        device_id: crate::DeviceId,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        let _ = device_id;
+        async {}
+    }
+
+    /// A [`Scene`](crate::Scene) was triggered, see [`EventKind::SceneTriggered`].
+    fn on_scene_triggered(
+        &mut self,
+        scene_id: crate::SceneId,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        let _ = scene_id;
+        async {}
+    }
+
+    /// Attributes of a [`Device`](crate::Device) changed from a previous value, see
+    /// [`Event::AttributesChanged`].
+    fn on_attributes_changed(
+        &mut self,
+        device_id: crate::DeviceId,
+        changes: Vec<crate::device::AttributeChange>,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        let _ = (device_id, changes);
+        async {}
+    }
+
+    /// The hub is reached at a new IP address, see [`Event::AddressChanged`].
+    fn on_address_changed(
+        &mut self,
+        previous: std::net::Ipv4Addr,
+        ip_address: std::net::Ipv4Addr,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        let _ = (previous, ip_address);
+        async {}
+    }
+
+    /// The connection was re-established and events may have been missed, see
+    /// [`Event::Resync`].
+    fn on_resync(&mut self) -> impl std::future::Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// Any other event from the hub.
+    fn on_other(&mut self, event: HubEvent) -> impl std::future::Future<Output = ()> + Send {
+        let _ = event;
+        async {}
+    }
+}
+
+/// An [`EventFilter`] limits what [`Event::Hub`] events are passed on from an [`EventListener`].
+/// Only device events can match a filter. Filters can be combined with [`EventFilter::or`].
+/// [`Event::Resync`] is never filtered.
+#[derive(Debug, Clone)]
+pub enum EventFilter {
+    Device(String),
+    Room(String),
+    DeviceType(crate::DeviceType),
+    Any(Vec<EventFilter>),
+}
+
+impl EventFilter {
+    /// Match events for the [`Device`](crate::Device) with the given id.
+    pub fn device(id: impl Into<String>) -> Self {
+        Self::Device(id.into())
+    }
+
+    /// Match events for all [`Device`](crate::Device)s in the room with the given id.
+    pub fn room(id: impl Into<String>) -> Self {
+        Self::Room(id.into())
+    }
+
+    /// Match events for all [`Device`](crate::Device)s of the given
+    /// [`DeviceType`](crate::DeviceType).
+    pub fn device_type(device_type: crate::DeviceType) -> Self {
+        Self::DeviceType(device_type)
+    }
+
+    /// Match events matching either this filter or `other`.
+    pub fn or(self, other: EventFilter) -> Self {
+        match self {
+            Self::Any(mut filters) => {
+                filters.push(other);
+                Self::Any(filters)
+            }
+            filter => Self::Any(vec![filter, other]),
+        }
+    }
+
+    fn needs_rooms(&self) -> bool {
+        match self {
+            Self::Room(_) => true,
+            Self::Any(filters) => filters.iter().any(Self::needs_rooms),
+            _ => false,
+        }
+    }
+
+    fn matches(&self, event: &HubEvent, rooms: &HashMap<String, String>) -> bool {
+        let Some(device_id) = event.device_id() else {
+            return false;
+        };
+
+        match self {
+            Self::Device(id) => id == device_id,
+            Self::Room(id) => event
+                .room_id()
+                .or_else(|| rooms.get(device_id).map(String::as_str))
+                .is_some_and(|room_id| room_id == id),
+            Self::DeviceType(device_type) => event
+                .device_type()
+                .is_some_and(|event_type| &event_type == device_type),
+            Self::Any(filters) => filters.iter().any(|filter| filter.matches(event, rooms)),
+        }
+    }
+}
+
+/// Options for an [`EventListener`]. The last `history` events are kept to be returned by
+/// [`EventListener::recent`]. If `fetch_state` is set all devices are fetched on every connect to
+/// know the previous values for [`Event::AttributesChanged`]. They're always fetched when the
+/// `filter` matches on rooms since the room of every device is needed.
+#[derive(Debug, Clone)]
+pub struct EventOptions {
+    pub reconnect: ReconnectPolicy,
+    pub filter: Option<EventFilter>,
+    pub history: usize,
+    pub source: EventSource,
+    pub keep_alive: KeepAlive,
+    pub fetch_state: bool,
+}
+
+impl Default for EventOptions {
+    fn default() -> Self {
+        Self {
+            reconnect: ReconnectPolicy::default(),
+            filter: None,
+            history: DEFAULT_HISTORY_SIZE,
+            source: EventSource::default(),
+            keep_alive: KeepAlive::default(),
+            fetch_state: false,
+        }
+    }
+}
+
+/// The [`KeepAlive`] decides how a connection that silently stopped working is detected, which
+/// is common after the hub reboots. A ping is sent every `ping_interval` and if nothing, not even
+/// a pong, has been received for `stale_timeout` the connection is closed and reconnected
+/// according to the [`ReconnectPolicy`]. A zero `ping_interval` disables both pings and the
+/// detection of stale connections.
+#[derive(Debug, Clone)]
+pub struct KeepAlive {
+    pub ping_interval: std::time::Duration,
+    pub stale_timeout: std::time::Duration,
+}
+
+impl Default for KeepAlive {
+    fn default() -> Self {
+        Self {
+            ping_interval: std::time::Duration::from_secs(30),
+            stale_timeout: std::time::Duration::from_secs(90),
+        }
+    }
+}
+
+/// The default interval to poll devices at when the WebSocket can't be used, see
+/// [`EventSource`].
+pub const DEFAULT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Number of failed attempts to connect the WebSocket before [`EventSource::Auto`] falls back to
+/// polling.
+const AUTO_FALLBACK_ATTEMPTS: u32 = 3;
+
+/// Where an [`EventListener`] gets its events from. Some networks block WebSockets so instead of
+/// listening on the WebSocket the devices can be polled. Each poll is compared to the previous
+/// one and `deviceStateChanged`, `deviceAdded` and `deviceRemoved` events are created for the
+/// differences, just like the hub would send them. Only device events can be created this way and
+/// changes between two polls are only seen as one change.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum EventSource {
+    /// Use the WebSocket, but poll every [`DEFAULT_POLL_INTERVAL`] instead if the WebSocket
+    /// can't connect three times in a row without ever having been connected.
+    #[default]
+    Auto,
+    /// Only use the WebSocket.
+    WebSocket,
+    /// Only poll the devices, with the given interval. A zero interval polls every
+    /// [`DEFAULT_POLL_INTERVAL`].
+    Polling(std::time::Duration),
+}
+
+/// The [`ReconnectPolicy`] decides how long to wait before reconnecting. The delay starts at
+/// `initial_delay` and is doubled after each failed attempt up to `max_delay`. A successful
+/// connection resets the delay.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_delay: std::time::Duration,
+    pub max_delay: std::time::Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: std::time::Duration::from_secs(1),
+            max_delay: std::time::Duration::from_secs(60),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    fn next_delay(&self, delay: std::time::Duration) -> std::time::Duration {
+        (delay * 2).min(self.max_delay)
+    }
+}
+
+/// Number of events buffered for each receiver before a slow receiver starts missing events.
+const EVENT_BUFFER_SIZE: usize = 128;
+
+/// The default number of events kept by an [`EventListener`], see [`EventListener::recent`].
+pub const DEFAULT_HISTORY_SIZE: usize = 100;
+
+/// An [`EventListener`] is a handle to the background task that's connected to the hub. The task
+/// is stopped when the [`EventListener`] is dropped, which also ends all streams and subscriptions
+/// created from it. The connection is closed cleanly and the task stops on its own when
+/// [`Hub::shutdown`](crate::hub::Hub::shutdown) is called. It must be created within a [`tokio`]
+/// runtime.
+#[derive(Debug)]
+pub struct EventListener {
+    receiver: tokio::sync::broadcast::Receiver<Event>,
+    history: std::sync::Arc<std::sync::Mutex<std::collections::VecDeque<Event>>>,
+    connected: tokio::sync::watch::Receiver<bool>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl EventListener {
+    pub(crate) fn spawn(hub: crate::hub::Hub, options: EventOptions) -> Self {
+        let (sender, receiver) = tokio::sync::broadcast::channel(EVENT_BUFFER_SIZE);
+        let (connected_sender, connected) = tokio::sync::watch::channel(false);
+        let publisher = Publisher {
+            sender,
+            history: Default::default(),
+            capacity: options.history,
+            connected: connected_sender,
+        };
+        let history = publisher.history.clone();
+        let task = tokio::spawn(run(hub, options, publisher));
+
+        Self {
+            receiver,
+            history,
+            connected,
+            task,
+        }
+    }
+
+    /// Wait until the background task has connected to the hub for the first time, f.ex. to not
+    /// miss the events caused by a change made right after. When polling it's connected once the
+    /// devices have been fetched the first time. Returns `false` if the background task stopped
+    /// before it connected.
+    pub async fn connected(&self) -> bool {
+        let mut connected = self.connected.clone();
+        let result = connected.wait_for(|connected| *connected).await;
+
+        result.is_ok()
+    }
+
+    /// Get the last `n` events, oldest first, f.ex. to show recent changes to a subscriber that
+    /// was created late. At most [`EventOptions::history`] events are kept, including events sent
+    /// before any of the current subscribers were created.
+    pub fn recent(&self, n: usize) -> Vec<Event> {
+        let history = self
+            .history
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        history
+            .iter()
+            .skip(history.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+
+    /// Wait for the next [`Event`]. If events were missed because they weren't consumed fast
+    /// enough an [`Event::Resync`] is returned. Returns [`None`] if the background task has
+    /// stopped.
+    pub async fn next(&mut self) -> Option<Event> {
+        next_event(&mut self.receiver).await
+    }
+
+    /// Subscribe to all events received after this call. Each subscriber gets its own copy of
+    /// every [`Event`]. A subscriber that falls behind will get a
+    /// [`RecvError::Lagged`](tokio::sync::broadcast::error::RecvError::Lagged) error.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<Event> {
+        self.receiver.resubscribe()
+    }
+
+    /// Get a [`Stream`](futures_util::Stream) of all events received after this call, independent
+    /// of other streams and subscribers. Missed events are reported as [`Event::Resync`] just like
+    /// for [`EventListener::next`].
+    pub fn stream(&self) -> impl futures_util::Stream<Item = Event> {
+        futures_util::stream::unfold(self.subscribe(), |mut receiver| async move {
+            next_event(&mut receiver)
+                .await
+                .map(|event| (event, receiver))
+        })
+    }
+
+    /// Pass every [`Event`] to the matching method of the [`EventHandler`] until the background
+    /// task stops. Events are handled one at a time in the order they were received. Spawn the
+    /// returned future to run the handler in its own task:
+    ///
+    /// ```no_run
+    /// # fn example(hub: &dirigera::hub::Hub, handler: impl dirigera::events::EventHandler + 'static) {
+    /// tokio::spawn(hub.events().dispatch(handler));
+    /// # }
+    /// ```
+    pub async fn dispatch<H: EventHandler>(mut self, mut handler: H) {
+        while let Some(event) = self.next().await {
+            match event {
+                Event::Hub(event) => match event.kind() {
+                    EventKind::DeviceStateChanged {
+                        device_id,
+                        changed_attributes,
+                    } => {
+                        handler
+                            .on_device_changed(device_id, changed_attributes)
+                            .await
+                    }
+                    EventKind::ButtonPressed {
+                        device_id,
+                        click_pattern,
+                    } => handler.on_button_pressed(device_id, click_pattern).await,
+                    EventKind::SceneTriggered { scene_id } => {
+                        handler.on_scene_triggered(scene_id).await
+                    }
+                    _ => handler.on_other(event).await,
+                },
+                Event::DoorOpened { device_id, .. } => {
+                    handler.on_door_changed(device_id, true).await
+                }
+                Event::DoorClosed { device_id, .. } => {
+                    handler.on_door_changed(device_id, false).await
+                }
+                Event::WaterLeakDetected { device_id, .. } => {
+                    handler.on_leak_detected(device_id).await
+                }
+                Event::WaterLeakCleared { device_id, .. } => {
+                    handler.on_leak_cleared(device_id).await
+                }
+                Event::AttributesChanged {
+                    device_id, changes, ..
+                } => handler.on_attributes_changed(device_id, changes).await,
+                Event::AddressChanged {
+                    previous,
+                    ip_address,
+                    ..
+                } => handler.on_address_changed(previous, ip_address).await,
+                Event::Resync => handler.on_resync().await,
+            }
+        }
+    }
+
+    /// Turn the [`EventListener`] into a [`Stream`](futures_util::Stream). The background task
+    /// will run until the stream is dropped.
+    pub fn into_stream(self) -> impl futures_util::Stream<Item = Event> {
+        futures_util::stream::unfold(self, |mut listener| async move {
+            listener.next().await.map(|event| (event, listener))
+        })
+    }
+}
+
+/// Sends events to all subscribers and keeps the last `capacity` events in the history.
+#[derive(Debug)]
+struct Publisher {
+    sender: tokio::sync::broadcast::Sender<Event>,
+    history: std::sync::Arc<std::sync::Mutex<std::collections::VecDeque<Event>>>,
+    capacity: usize,
+    connected: tokio::sync::watch::Sender<bool>,
+}
+
+impl Publisher {
+    /// Publish the [`Event`]. Fails if there are no subscribers left.
+    fn send(&self, event: Event) -> Result<(), tokio::sync::broadcast::error::SendError<Event>> {
+        if self.capacity > 0 {
+            let mut history = self
+                .history
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            if history.len() >= self.capacity {
+                history.pop_front();
+            }
+
+            history.push_back(event.clone());
+        }
+
+        self.sender.send(event).map(|_| ())
+    }
+
+    /// Mark the listener as connected, see [`EventListener::connected`].
+    fn set_connected(&self) {
+        self.connected.send_replace(true);
+    }
+
+    fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// Publish an event from the hub and all events created from it if it matches the filter.
+    /// Water leaks are always published. The `state` is updated with the event, even if it
+    /// doesn't match the filter.
+    fn send_hub_event(
+        &self,
+        event: HubEvent,
+        filter: Option<&EventFilter>,
+        state: &mut KnownState,
+    ) -> Result<(), tokio::sync::broadcast::error::SendError<Event>> {
+        let attributes_event = state.update(&event);
+        let water_leak_event = event.water_leak_event();
+
+        if filter.is_none_or(|filter| filter.matches(&event, &state.rooms)) {
+            let door_event = event.door_event();
+
+            self.send(Event::Hub(event))?;
+
+            if let Some(door_event) = door_event {
+                self.send(door_event)?;
+            }
+
+            if let Some(attributes_event) = attributes_event {
+                self.send(attributes_event)?;
+            }
+        }
+
+        if let Some(water_leak_event) = water_leak_event {
+            self.send(water_leak_event)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Receive the next [`Event`], reporting missed events as [`Event::Resync`] since the receiver
+/// has to refetch its state either way.
+async fn next_event(receiver: &mut tokio::sync::broadcast::Receiver<Event>) -> Option<Event> {
+    match receiver.recv().await {
+        Ok(event) => Some(event),
+        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => Some(Event::Resync),
+        Err(tokio::sync::broadcast::error::RecvError::Closed) => None,
+    }
+}
+
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+type Socket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Wait until [`Hub::shutdown`] is called.
+async fn shut_down(shutdown: &mut tokio::sync::watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|closed| *closed).await;
+}
+
+/// Wait for the next tick of the `interval`, or forever if there is none.
+async fn tick(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+async fn connect(hub: &crate::hub::Hub) -> anyhow::Result<Socket> {
+    let connector = tokio_tungstenite::Connector::Rustls(hub.websocket_tls()?);
+
+    let (socket, _) = tokio_tungstenite::connect_async_tls_with_config(
+        hub.websocket_request()?,
+        None,
+        false,
+        Some(connector),
+    )
+    .await?;
+
+    Ok(socket)
+}
+
+/// What the background task knows about each device: the room it's placed in, used by the
+/// [`EventFilter`], and the last known attributes, used to create [`Event::AttributesChanged`].
+#[derive(Debug, Default)]
+struct KnownState {
+    rooms: HashMap<String, String>,
+    attributes: HashMap<String, serde_json::Map<String, serde_json::Value>>,
+}
+
+impl KnownState {
+    /// Fetch the current state of all devices. If the devices can't be fetched the state is
+    /// empty and will be filled in by the events that follow.
+    async fn fetch(hub: &mut crate::hub::Hub) -> Self {
+        let devices = hub.devices_raw().await.unwrap_or_default();
+        Self::from_devices(devices.iter())
+    }
+
+    fn from_devices<'a>(devices: impl Iterator<Item = &'a serde_json::Value>) -> Self {
+        let mut state = Self::default();
+
+        for device in devices {
+            let Some(id) = device.get("id").and_then(serde_json::Value::as_str) else {
+                continue;
+            };
+
+            if let Some(room_id) = device
+                .get("room")
+                .and_then(|room| room.get("id"))
+                .and_then(serde_json::Value::as_str)
+            {
+                state.rooms.insert(id.to_string(), room_id.to_string());
+            }
+
+            if let Some(attributes) = device
+                .get("attributes")
+                .and_then(serde_json::Value::as_object)
+            {
+                state.attributes.insert(id.to_string(), attributes.clone());
+            }
+        }
+
+        state
+    }
+
+    /// Update the state with the event and get an [`Event::AttributesChanged`] with the previous
+    /// values if any attribute changed.
+    fn update(&mut self, event: &HubEvent) -> Option<Event> {
+        let device_id = event.device_id()?;
+
+        if let Some(room_id) = event.room_id() {
+            self.rooms
+                .insert(device_id.to_string(), room_id.to_string());
+        }
+
+        let reported = event
+            .data
+            .get("attributes")
+            .and_then(serde_json::Value::as_object);
+
+        match event.event_type.as_str() {
+            "deviceRemoved" => {
+                self.rooms.remove(device_id);
+                self.attributes.remove(device_id);
+
+                None
+            }
+            "deviceAdded" => {
+                self.attributes
+                    .insert(device_id.to_string(), reported.cloned().unwrap_or_default());
+
+                None
+            }
+            "deviceStateChanged" => {
+                let known = self.attributes.entry(device_id.to_string()).or_default();
+                let mut changes = Vec::new();
+
+                for (name, new) in reported? {
+                    let old = known.insert(name.clone(), new.clone());
+                    if old.as_ref() == Some(new) {
+                        continue;
+                    }
+
+                    changes.push(crate::device::AttributeChange {
+                        attribute: crate::device::to_snake_case(name),
+                        old: old.unwrap_or_default(),
+                        new: new.clone(),
+                    });
+                }
+
+                (!changes.is_empty()).then(|| Event::AttributesChanged {
+                    device_id: device_id.into(),
+                    changes,
+                    time: event.time,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Keep a connection to the hub for as long as someone is listening. The token is sent again on
+/// every connection attempt so a reconnect is also a re-authentication.
+async fn run(mut hub: crate::hub::Hub, options: EventOptions, sender: Publisher) {
+    let mut delay = options.reconnect.initial_delay;
+    let mut has_connected = false;
+    let mut shutdown = hub.shutdown_signal();
+    let mut failed_attempts = 0;
+    let mut ip_address = hub.ip_address();
+
+    if let EventSource::Polling(interval) = options.source {
+        poll(hub, &options, &sender, interval).await;
+        return;
+    }
+
+    loop {
+        if hub.is_shut_down() {
+            return;
+        }
+
+        if let Ok(mut socket) = connect(&hub).await {
+            delay = options.reconnect.initial_delay;
+            failed_attempts = 0;
+
+            let previous = std::mem::replace(&mut ip_address, hub.ip_address());
+            if previous != ip_address
+                && sender
+                    .send(Event::AddressChanged {
+                        previous,
+                        ip_address,
+                        time: chrono::Utc::now(),
+                    })
+                    .is_err()
+            {
+                return;
+            }
+
+            if has_connected && sender.send(Event::Resync).is_err() {
+                return;
+            }
+
+            has_connected = true;
+            sender.set_connected();
+
+            // Devices may have changed or moved while we were disconnected so the state is
+            // refreshed, or started over if it's not fetched, on every connect.
+            let mut state = if options.fetch_state
+                || options
+                    .filter
+                    .as_ref()
+                    .is_some_and(EventFilter::needs_rooms)
+            {
+                KnownState::fetch(&mut hub).await
+            } else {
+                KnownState::default()
+            };
+
+            let mut ping = (!options.keep_alive.ping_interval.is_zero())
+                .then(|| tokio::time::interval(options.keep_alive.ping_interval));
+            let mut last_seen = tokio::time::Instant::now();
+
+            loop {
+                let message = tokio::select! {
+                    message = socket.next() => message,
+                    _ = tick(&mut ping) => {
+                        if last_seen.elapsed() >= options.keep_alive.stale_timeout {
+                            break;
+                        }
+
+                        let ping = tokio_tungstenite::tungstenite::Message::Ping(Vec::new());
+                        if socket.send(ping).await.is_err() {
+                            break;
+                        }
+
+                        continue;
+                    }
+                    _ = shut_down(&mut shutdown) => {
+                        let _ = socket.close(None).await;
+                        return;
+                    }
+                };
+
+                last_seen = tokio::time::Instant::now();
+
+                let Some(Ok(message)) = message else {
+                    break;
+                };
+
+                let text = match message {
+                    tokio_tungstenite::tungstenite::Message::Text(text) => text,
+                    tokio_tungstenite::tungstenite::Message::Close(_) => break,
+                    _ => continue,
+                };
+
+                let Ok(event) = serde_json::from_str::<HubEvent>(&text) else {
+                    continue;
+                };
+
+                if sender
+                    .send_hub_event(event, options.filter.as_ref(), &mut state)
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        } else {
+            failed_attempts += 1;
+
+            // The hub may have a new IP address, connect again right away if it was found.
+            #[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+            if hub.rediscover().await {
+                continue;
+            }
+
+            if options.source == EventSource::Auto
+                && !has_connected
+                && failed_attempts >= AUTO_FALLBACK_ATTEMPTS
+            {
+                poll(hub, &options, &sender, DEFAULT_POLL_INTERVAL).await;
+                return;
+            }
+        }
+
+        if !sender.has_subscribers() {
+            return;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shut_down(&mut shutdown) => return,
+        }
+
+        delay = options.reconnect.next_delay(delay);
+    }
+}
+
+/// Poll the devices every `interval` and publish events for what changed since the previous poll
+/// for as long as someone is listening, see [`EventSource`].
+async fn poll(
+    mut hub: crate::hub::Hub,
+    options: &EventOptions,
+    sender: &Publisher,
+    interval: std::time::Duration,
+) {
+    let mut shutdown = hub.shutdown_signal();
+    let interval = if interval.is_zero() {
+        DEFAULT_POLL_INTERVAL
+    } else {
+        interval
+    };
+    let mut ticker = tokio::time::interval(interval);
+    let mut state = KnownState::default();
+    let mut previous: Option<HashMap<String, serde_json::Value>> = None;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shut_down(&mut shutdown) => return,
+        }
+
+        if !sender.has_subscribers() {
+            return;
+        }
+
+        let Ok(devices) = hub.devices_raw().await else {
+            continue;
+        };
+
+        let current: HashMap<String, serde_json::Value> = devices
+            .into_iter()
+            .filter_map(|device| Some((device.get("id")?.as_str()?.to_string(), device)))
+            .collect();
+
+        match &previous {
+            Some(previous) => {
+                for event in diff_devices(previous, &current) {
+                    if sender
+                        .send_hub_event(event, options.filter.as_ref(), &mut state)
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+            None => {
+                state = KnownState::from_devices(current.values());
+                sender.set_connected();
+            }
+        }
+
+        previous = Some(current);
+    }
+}
+
+/// Create the events the hub would have sent for the changes between two polls of the devices.
+/// A device has changed if any of its attributes or any other field but `lastSeen`, f.ex.
+/// `isReachable` or the room, has changed. The events are sorted by device id.
+fn diff_devices(
+    previous: &HashMap<String, serde_json::Value>,
+    current: &HashMap<String, serde_json::Value>,
+) -> Vec<HubEvent> {
+    let time = chrono::Utc::now();
+    let event = |event_type: &str, data: serde_json::Value| HubEvent {
+        id: String::new(),
+        time,
+        event_type: event_type.to_string(),
+        data,
+    };
+
+    let mut events = Vec::new();
+
+    let mut ids: Vec<_> = current.keys().collect();
+    ids.sort();
+
+    for id in ids {
+        let device = &current[id];
+        let Some(old) = previous.get(id) else {
+            events.push(event("deviceAdded", device.clone()));
+            continue;
+        };
+
+        let attributes = |device: &serde_json::Value| {
+            device
+                .get("attributes")
+                .and_then(serde_json::Value::as_object)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let old_attributes = attributes(old);
+        let changed: serde_json::Map<_, _> = attributes(device)
+            .into_iter()
+            .filter(|(name, value)| old_attributes.get(name) != Some(value))
+            .collect();
+
+        let fields_changed = match (old.as_object(), device.as_object()) {
+            (Some(old), Some(new)) => old.keys().chain(new.keys()).any(|name| {
+                !matches!(name.as_str(), "attributes" | "lastSeen")
+                    && old.get(name) != new.get(name)
+            }),
+            _ => old != device,
+        };
+
+        if changed.is_empty() && !fields_changed {
+            continue;
+        }
+
+        let mut data = device.clone();
+        if let Some(data) = data.as_object_mut() {
+            data.insert("attributes".to_string(), changed.into());
+        }
+
+        events.push(event("deviceStateChanged", data));
+    }
+
+    let mut removed: Vec<_> = previous
+        .keys()
+        .filter(|id| !current.contains_key(*id))
+        .collect();
+    removed.sort();
+
+    for id in removed {
+        events.push(event("deviceRemoved", serde_json::json!({ "id": id })));
+    }
+
+    // Events from the same poll share the time so the device id and the position are added to
+    // keep the ids unique.
+    for (index, event) in events.iter_mut().enumerate() {
+        let device_id = event.device_id().unwrap_or_default().to_string();
+        event.id = format!("poll-{}-{device_id}-{index}", time.timestamp_millis());
+    }
+
+    events
+}
@@ -0,0 +1,168 @@
+//! Bindings for Kotlin, Swift and other languages supported by
+//! [uniffi](https://mozilla.github.io/uniffi-rs/). Only a high level subset of the
+//! [`Hub`](crate::hub::Hub) is exposed: listing devices and scenes, turning devices on and off,
+//! setting the light level and triggering scenes. Devices and scenes are passed as flat records
+//! and referenced by id so no state has to be kept on the other side. It's only available behind
+//! the `uniffi` feature flag.
+//!
+//! To build a library to generate bindings from, build the crate as a `cdylib`:
+//!
+//! ```sh
+//! cargo rustc --release --features uniffi --crate-type cdylib
+//! ```
+
+/// An error from the [`FfiHub`]. The underlying error is only available as a message.
+#[derive(Debug, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum FfiError {
+    InvalidArgument(String),
+    Request(String),
+}
+
+impl std::fmt::Display for FfiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::InvalidArgument(message) => write!(f, "invalid argument: {message}"),
+            Self::Request(message) => write!(f, "request failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for FfiError {}
+
+impl From<anyhow::Error> for FfiError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::Request(format!("{err:#}"))
+    }
+}
+
+/// A [`Device`](crate::Device) with the most commonly used attributes.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiDevice {
+    pub id: String,
+    pub name: String,
+    pub device_type: String,
+    pub room: Option<String>,
+    pub is_reachable: bool,
+    pub is_on: Option<bool>,
+    pub light_level: Option<u8>,
+}
+
+impl From<&crate::Device> for FfiDevice {
+    fn from(device: &crate::Device) -> Self {
+        let inner = device.inner();
+
+        Self {
+            id: inner.id.to_string(),
+            name: inner.attributes.custom_name.clone(),
+            device_type: inner.device_type.to_string(),
+            room: inner.room.as_ref().map(|room| room.name.clone()),
+            is_reachable: inner.is_reachable,
+            is_on: inner.attributes.is_on,
+            light_level: inner.attributes.light_level.map(|level| level.0),
+        }
+    }
+}
+
+/// A [`Scene`](crate::Scene) that can be triggered with [`FfiHub::trigger_scene`].
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct FfiScene {
+    pub id: String,
+    pub name: String,
+}
+
+impl From<&crate::Scene> for FfiScene {
+    fn from(scene: &crate::Scene) -> Self {
+        let inner = scene.inner();
+
+        Self {
+            id: inner.id.to_string(),
+            name: inner.info.name.clone(),
+        }
+    }
+}
+
+/// A [`Hub`](crate::hub::Hub) that can be shared with other languages. Calls are serialized so
+/// only one request is in flight at a time.
+#[derive(uniffi::Object)]
+pub struct FfiHub {
+    hub: tokio::sync::Mutex<crate::hub::Hub>,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl FfiHub {
+    /// Create a new [`FfiHub`] that only accepts the hub's certificate if its SHA-256 fingerprint
+    /// matches `certificate_sha256`, see
+    /// [`HubBuilder::pinned_certificate`](crate::hub::HubBuilder::pinned_certificate).
+    #[uniffi::constructor]
+    pub fn new(
+        ip_address: String,
+        token: String,
+        certificate_sha256: Vec<u8>,
+    ) -> Result<std::sync::Arc<Self>, FfiError> {
+        let ip_address = ip_address
+            .parse()
+            .map_err(|_| FfiError::InvalidArgument(format!("invalid ip address '{ip_address}'")))?;
+
+        let fingerprint = <[u8; 32]>::try_from(certificate_sha256).map_err(|_| {
+            FfiError::InvalidArgument("certificate fingerprint must be 32 bytes".to_string())
+        })?;
+
+        let hub = crate::hub::Hub::builder(ip_address, token)
+            .pinned_certificate(fingerprint)
+            .build()?;
+
+        Ok(std::sync::Arc::new(Self {
+            hub: tokio::sync::Mutex::new(hub),
+        }))
+    }
+
+    /// List all devices.
+    pub async fn devices(&self) -> Result<Vec<FfiDevice>, FfiError> {
+        let devices = self.hub.lock().await.devices().await?;
+
+        Ok(devices.iter().map(FfiDevice::from).collect())
+    }
+
+    /// List all scenes.
+    pub async fn scenes(&self) -> Result<Vec<FfiScene>, FfiError> {
+        let scenes = self.hub.lock().await.scenes().await?;
+
+        Ok(scenes.iter().map(FfiScene::from).collect())
+    }
+
+    /// Toggle the device with the given id on or off. Returns the device after the change.
+    pub async fn toggle(&self, device_id: String) -> Result<FfiDevice, FfiError> {
+        let mut hub = self.hub.lock().await;
+        let mut device = hub.device(&device_id).await?;
+
+        hub.toggle_on_off(&mut device).await?;
+
+        Ok(FfiDevice::from(&device))
+    }
+
+    /// Set the light level of the device with the given id. Returns the device after the change.
+    pub async fn set_light_level(
+        &self,
+        device_id: String,
+        level: u8,
+    ) -> Result<FfiDevice, FfiError> {
+        let mut hub = self.hub.lock().await;
+        let mut device = hub.device(&device_id).await?;
+
+        hub.set_light_level(&mut device, crate::units::Percent(level))
+            .await?;
+
+        Ok(FfiDevice::from(&device))
+    }
+
+    /// Trigger the scene with the given id.
+    pub async fn trigger_scene(&self, scene_id: String) -> Result<(), FfiError> {
+        let mut hub = self.hub.lock().await;
+        let scene = hub.scene(&scene_id).await?;
+
+        hub.trigger_scene(&scene).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+//! Record how device attributes change over time in a SQLite database. Attributes can be recorded
+//! from polled [`Device`](crate::Device)s or, with the `events` feature flag, from
+//! [`Event`](crate::events::Event)s. A value is only stored when it differs from the last recorded
+//! value for the same attribute so polling often doesn't fill the database with duplicates. The
+//! recorded [`Sample`]s can then be queried to f.ex. graph temperature or humidity over time, or
+//! exported as CSV or JSON to be used in a spreadsheet or Grafana. It's only available behind the
+//! `history` feature flag.
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+
+/// A single recorded value for an attribute of a [`Device`](crate::Device).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sample {
+    pub device_id: String,
+    pub attribute: String,
+    pub value: serde_json::Value,
+    pub recorded_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A [`History`] is a connection to the SQLite database holding all recorded [`Sample`]s.
+#[derive(Debug)]
+pub struct History {
+    connection: rusqlite::Connection,
+}
+
+impl History {
+    /// Open the database at `path`, creating it if it doesn't exist.
+    pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        Self::with_connection(rusqlite::Connection::open(path)?)
+    }
+
+    /// Create a new database that is only kept in memory.
+    pub fn in_memory() -> anyhow::Result<Self> {
+        Self::with_connection(rusqlite::Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: rusqlite::Connection) -> anyhow::Result<Self> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS attribute_history (
+                id INTEGER PRIMARY KEY,
+                device_id TEXT NOT NULL,
+                attribute TEXT NOT NULL,
+                value TEXT NOT NULL,
+                recorded_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS attribute_history_lookup
+                ON attribute_history (device_id, attribute, recorded_at);",
+        )?;
+
+        Ok(Self { connection })
+    }
+
+    /// Record a value for an attribute at the given time. Returns `false` if the value wasn't
+    /// stored because it's the same as the last recorded value.
+    pub fn record(
+        &self,
+        device_id: &str,
+        attribute: &str,
+        value: &serde_json::Value,
+        recorded_at: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<bool> {
+        if value.is_null() {
+            return Ok(false);
+        }
+
+        if self
+            .latest(device_id, attribute)?
+            .is_some_and(|latest| &latest.value == value)
+        {
+            return Ok(false);
+        }
+
+        self.connection.execute(
+            "INSERT INTO attribute_history (device_id, attribute, value, recorded_at)
+                VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![
+                device_id,
+                attribute,
+                value.to_string(),
+                recorded_at.timestamp_millis()
+            ],
+        )?;
+
+        Ok(true)
+    }
+
+    /// Record all attributes of a polled [`Device`](crate::Device) that changed since they were
+    /// last recorded. Returns the number of stored values.
+    pub fn record_device(&self, device: &crate::Device) -> anyhow::Result<usize> {
+        let inner = device.inner();
+        let serde_json::Value::Object(attributes) = serde_json::to_value(&inner.attributes)? else {
+            return Ok(0);
+        };
+
+        self.record_attributes(&inner.id, &attributes, chrono::Utc::now())
+    }
+
+    /// Record all attributes that changed in a device event. Other events are ignored. Returns
+    /// the number of stored values.
+    #[cfg(feature = "events")]
+    pub fn record_event(&self, event: &crate::events::Event) -> anyhow::Result<usize> {
+        let crate::events::Event::Hub(event) = event else {
+            return Ok(0);
+        };
+
+        let (Some(device_id), Some(serde_json::Value::Object(attributes))) =
+            (event.device_id(), event.data.get("attributes"))
+        else {
+            return Ok(0);
+        };
+
+        self.record_attributes(device_id, attributes, event.time)
+    }
+
+    fn record_attributes(
+        &self,
+        device_id: &str,
+        attributes: &serde_json::Map<String, serde_json::Value>,
+        recorded_at: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<usize> {
+        let mut recorded = 0;
+
+        for (attribute, value) in attributes {
+            if self.record(device_id, attribute, value, recorded_at)? {
+                recorded += 1;
+            }
+        }
+
+        Ok(recorded)
+    }
+
+    /// Get the last recorded [`Sample`] for an attribute.
+    pub fn latest(&self, device_id: &str, attribute: &str) -> anyhow::Result<Option<Sample>> {
+        self.connection
+            .query_row(
+                "SELECT device_id, attribute, value, recorded_at FROM attribute_history
+                    WHERE device_id = ?1 AND attribute = ?2
+                    ORDER BY recorded_at DESC, id DESC LIMIT 1",
+                rusqlite::params![device_id, attribute],
+                sample_from_row,
+            )
+            .optional()
+            .map_err(|err| anyhow::anyhow!(err))
+    }
+
+    /// Get all recorded [`Sample`]s for an attribute within the given time range, oldest first.
+    pub fn query(
+        &self,
+        device_id: &str,
+        attribute: &str,
+        range: std::ops::Range<chrono::DateTime<chrono::Utc>>,
+    ) -> anyhow::Result<Vec<Sample>> {
+        let mut statement = self.connection.prepare(
+            "SELECT device_id, attribute, value, recorded_at FROM attribute_history
+                WHERE device_id = ?1 AND attribute = ?2
+                    AND recorded_at >= ?3 AND recorded_at < ?4
+                ORDER BY recorded_at, id",
+        )?;
+
+        let samples = statement.query_map(
+            rusqlite::params![
+                device_id,
+                attribute,
+                range.start.timestamp_millis(),
+                range.end.timestamp_millis()
+            ],
+            sample_from_row,
+        )?;
+
+        Ok(samples.collect::<Result<_, _>>()?)
+    }
+
+    /// Get all recorded [`Sample`]s for all devices and attributes within the given time range,
+    /// oldest first.
+    pub fn samples(
+        &self,
+        range: std::ops::Range<chrono::DateTime<chrono::Utc>>,
+    ) -> anyhow::Result<Vec<Sample>> {
+        let mut statement = self.connection.prepare(
+            "SELECT device_id, attribute, value, recorded_at FROM attribute_history
+                WHERE recorded_at >= ?1 AND recorded_at < ?2
+                ORDER BY recorded_at, id",
+        )?;
+
+        let samples = statement.query_map(
+            rusqlite::params![range.start.timestamp_millis(), range.end.timestamp_millis()],
+            sample_from_row,
+        )?;
+
+        Ok(samples.collect::<Result<_, _>>()?)
+    }
+
+    /// Write all [`Sample`]s within the given time range as CSV with a header row. The columns
+    /// are `device_id`, `attribute`, `value` and `recorded_at` where the timestamp is in RFC 3339
+    /// format. Returns the number of written samples.
+    pub fn export_csv(
+        &self,
+        range: std::ops::Range<chrono::DateTime<chrono::Utc>>,
+        mut writer: impl std::io::Write,
+    ) -> anyhow::Result<usize> {
+        let samples = self.samples(range)?;
+
+        writeln!(writer, "device_id,attribute,value,recorded_at")?;
+
+        for sample in &samples {
+            let value = match &sample.value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                csv_field(&sample.device_id),
+                csv_field(&sample.attribute),
+                csv_field(&value),
+                sample.recorded_at.to_rfc3339(),
+            )?;
+        }
+
+        Ok(samples.len())
+    }
+
+    /// Write all [`Sample`]s within the given time range as a JSON array. Returns the number of
+    /// written samples.
+    pub fn export_json(
+        &self,
+        range: std::ops::Range<chrono::DateTime<chrono::Utc>>,
+        writer: impl std::io::Write,
+    ) -> anyhow::Result<usize> {
+        let samples = self.samples(range)?;
+        serde_json::to_writer(writer, &samples)?;
+
+        Ok(samples.len())
+    }
+
+    /// Get the names of all attributes recorded for a [`Device`](crate::Device).
+    pub fn attributes(&self, device_id: &str) -> anyhow::Result<Vec<String>> {
+        let mut statement = self.connection.prepare(
+            "SELECT DISTINCT attribute FROM attribute_history
+                WHERE device_id = ?1 ORDER BY attribute",
+        )?;
+
+        let attributes = statement.query_map([device_id], |row| row.get(0))?;
+
+        Ok(attributes.collect::<Result<_, _>>()?)
+    }
+}
+
+/// Quote a CSV field if needed, doubling any quotes in it.
+fn csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+fn sample_from_row(row: &rusqlite::Row) -> rusqlite::Result<Sample> {
+    let value: String = row.get(2)?;
+    let recorded_at: i64 = row.get(3)?;
+
+    Ok(Sample {
+        device_id: row.get(0)?,
+        attribute: row.get(1)?,
+        value: serde_json::from_str(&value).map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(err))
+        })?,
+        recorded_at: chrono::DateTime::from_timestamp_millis(recorded_at)
+            .ok_or(rusqlite::Error::IntegralValueOutOfRange(3, recorded_at))?,
+    })
+}
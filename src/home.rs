@@ -0,0 +1,54 @@
+//! A [`Home`] is a snapshot of everything known by the hub at a single point in time, fetched with
+//! [`Hub::snapshot`](crate::hub::Hub::snapshot). It's useful when you need to look at devices,
+//! scenes and rooms together without making a request for each lookup.
+use crate::device::Room;
+
+/// All [`Device`](crate::Device)s, [`Scene`](crate::Scene)s and [`Room`]s of the hub.
+#[derive(Debug)]
+pub struct Home {
+    pub devices: Vec<crate::Device>,
+    pub scenes: Vec<crate::Scene>,
+    pub rooms: Vec<Room>,
+    #[cfg(feature = "chrono")]
+    pub fetched_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Home {
+    /// Get the [`Device`](crate::Device) with the given id.
+    pub fn device(&self, id: &str) -> Option<&crate::Device> {
+        self.devices.iter().find(|device| device.inner().id == id)
+    }
+
+    /// Get the [`Scene`](crate::Scene) with the given id.
+    pub fn scene(&self, id: &str) -> Option<&crate::Scene> {
+        self.scenes.iter().find(|scene| scene.inner().id == id)
+    }
+
+    /// Get the [`Room`] with the given id.
+    pub fn room(&self, id: &str) -> Option<&Room> {
+        self.rooms.iter().find(|room| room.id == id)
+    }
+
+    /// Get all [`Device`](crate::Device)s in the [`Room`] with the given id.
+    pub fn devices_in_room<'a>(
+        &'a self,
+        room_id: &'a str,
+    ) -> impl Iterator<Item = &'a crate::Device> + 'a {
+        self.devices.iter().filter(move |device| {
+            device
+                .inner()
+                .room
+                .as_ref()
+                .is_some_and(|room| room.id == room_id)
+        })
+    }
+
+    /// Check if any motion sensor in the [`Room`] with the given id detected motion when the
+    /// snapshot was taken. This isn't the same as the room being occupied, use an
+    /// [`OccupancyTracker`](crate::occupancy::OccupancyTracker) to keep a room occupied for a
+    /// while after the motion stopped.
+    pub fn has_motion(&self, room_id: &str) -> bool {
+        self.devices_in_room(room_id)
+            .any(|device| device.inner().attributes.is_detected == Some(true))
+    }
+}
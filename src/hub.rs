@@ -4,87 +4,863 @@
 //! TLS verification. You also need a bearer token which is obtain via OAuth 2. Configuration for
 //! TLS and tool to get a token is both available under the [`danger`](crate::danger) module and the
 //! `config` feature flag respectively.
-use hyper::service::Service;
 use serde::Deserialize;
 
 use std::collections::HashMap;
-#[cfg(feature = "config")]
-use std::io::Read;
 
 const DIRIGERA_PORT: u16 = 8443;
 const DIRIGERA_API_VERSION: &str = "v1";
 
-/// A [`Hub`] consists of a [`hyper`] client, the hub's IP address and a token to communicate with
-/// it.
-#[derive(Debug)]
+/// The default number of connections a [`Hub`] created with [`Hub::builder`] opens to the hub.
+#[cfg(not(target_arch = "wasm32"))]
+pub const DEFAULT_MAX_CONNECTIONS: usize = 2;
+
+/// The shortest time between two steps when fading a light with [`Hub::fade_light`], to not flood
+/// the hub with requests.
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+const FADE_STEP_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// The default time a [`Hub`] waits for a response before a request fails, see
+/// [`Hub::with_timeout`].
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+pub const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How long to wait before the hub is searched for again after a failed
+/// [`Hub::with_rediscovery`] attempt, so requests to a hub that is offline don't each wait for
+/// discovery.
+#[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+const REDISCOVERY_COOLDOWN: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A [`Hub`] consists of a [`Transport`](crate::transport::Transport), usually a [`hyper`] client,
+/// the hub's IP address and a token to communicate with it. Cloning a [`Hub`] is cheap since the
+/// [`Transport`](crate::transport::Transport) is shared between the clones.
+///
+/// All futures returned by the [`Hub`] can be dropped to cancel the request, f.ex. with
+/// [`tokio::time::timeout`] or [`tokio::select!`]. If the request was already sent the hub may
+/// still apply the change but a passed [`Device`](crate::Device) is only updated once the hub
+/// has responded. Use [`Hub::shutdown`] to wait for requests in flight before exiting.
+#[derive(Debug, Clone)]
 pub struct Hub {
-    client: hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>,
+    client: std::sync::Arc<dyn crate::transport::Transport>,
+    #[cfg(not(target_arch = "wasm32"))]
+    tls: Option<std::sync::Arc<rustls::ClientConfig>>,
+    /// If the certificate of the hub is verified, i.e. the [`Hub`] was built with a TLS
+    /// configuration instead of accepting any certificate.
+    #[cfg(not(target_arch = "wasm32"))]
+    verifies_certificate: bool,
+    #[cfg(feature = "chrono")]
+    audit: Option<std::sync::Arc<dyn crate::audit::AuditSink>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    metrics: Option<std::sync::Arc<dyn crate::metrics::MetricsSink>>,
+    #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+    retry: RetryPolicy,
+    #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+    busy_until: std::sync::Arc<std::sync::Mutex<Option<std::time::Instant>>>,
+    #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+    timeout: std::time::Duration,
+    #[cfg(feature = "runtime")]
+    lifecycle: std::sync::Arc<Lifecycle>,
+    credentials: std::sync::Arc<std::sync::RwLock<Credentials>>,
+    devices_cache: std::sync::Arc<std::sync::Mutex<Option<CachedDevices>>>,
+    #[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+    rediscovery: Option<std::sync::Arc<Rediscovery>>,
+}
+
+/// The hub to look for when it stops answering, see [`Hub::with_rediscovery`]. The lock makes
+/// sure only one search runs at a time and holds the time of the last failed search.
+#[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+struct Rediscovery {
+    serial_number: String,
+    last_failed: tokio::sync::Mutex<Option<std::time::Instant>>,
+}
+
+/// The IP address and token used to talk to the hub. Shared by all clones of a [`Hub`] so they
+/// can be replaced with [`Hub::set_credentials`] while the [`Hub`] is in use.
+#[derive(Debug, Clone)]
+struct Credentials {
     ip_address: std::net::Ipv4Addr,
+    port: u16,
     token: String,
 }
 
+/// The last response for [`Hub::devices_cached`] together with the body it was parsed from.
+#[derive(Debug)]
+struct CachedDevices {
+    body: hyper::body::Bytes,
+    devices: std::sync::Arc<Vec<crate::Device>>,
+}
+
+/// Where [`Hub::deserialize_response_stream`] is in the JSON array it reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ListPosition {
+    /// Before the opening bracket.
+    Start,
+    /// After the opening bracket, expecting the first item or the closing bracket.
+    First,
+    /// After an item, expecting a comma or the closing bracket.
+    AfterItem,
+    /// After a comma, expecting the next item.
+    Next,
+}
+
+/// Shared by all clones of a [`Hub`] to know if it has been shut down and to wait for requests in
+/// flight. Each request holds a read lock for as long as it's running.
+#[cfg(feature = "runtime")]
+#[derive(Debug)]
+struct Lifecycle {
+    closed: tokio::sync::watch::Sender<bool>,
+    in_flight: tokio::sync::RwLock<()>,
+}
+
+#[cfg(feature = "runtime")]
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            closed: tokio::sync::watch::channel(false).0,
+            in_flight: Default::default(),
+        }
+    }
+}
+
+/// The [`RetryPolicy`] decides how requests are retried when the [`Hub`] responds that it's busy,
+/// see [`ApiError::is_busy`](crate::error::ApiError::is_busy). The `Retry-After` header from the
+/// [`Hub`] is used as the delay if set, otherwise `default_delay`, but never more than
+/// `max_delay`. It's not available when targeting WASM.
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub default_delay: std::time::Duration,
+    pub max_delay: std::time::Duration,
+}
+
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            default_delay: std::time::Duration::from_secs(1),
+            max_delay: std::time::Duration::from_secs(30),
+        }
+    }
+}
+
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+impl RetryPolicy {
+    /// A [`RetryPolicy`] that never retries.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+}
+
+/// The result of a lenient listing where each item in the response is parsed on its own. Items
+/// that could be parsed are found in `parsed` while items that failed are kept together with
+/// their raw JSON in `failed`.
+#[derive(Debug)]
+pub struct Lenient<T> {
+    pub parsed: Vec<T>,
+    pub failed: Vec<ParseFailure>,
+}
+
+/// An item that failed to parse in a lenient listing. The raw JSON is kept so it can be inspected
+/// or attached to a bug report.
+#[derive(Debug)]
+pub struct ParseFailure {
+    pub raw: serde_json::Value,
+    pub error: serde_json::Error,
+}
+
 /// If you want to read the configuration from a `toml` file, the [`Config`] is used to deserialize
-/// the file contents. It's only available behind the `config` feature flag.
+/// the file contents. Only the IP address and token are required, everything else uses the same
+/// defaults as [`Hub::builder`]. It's only available behind the `config` feature flag.
+///
+/// ```toml
+/// ip-address = "192.168.1.101"
+/// token = "abc123..."
+/// port = 8443
+/// tls = { pinned = "hub.pem" }
+/// request-timeout = 30
+/// max-connections = 2
+/// rate-limit = { requests = 10, interval = 1 }
+/// serial-number = "abc123"
+/// ```
 #[cfg(feature = "config")]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
-    ip_address: std::net::Ipv4Addr,
-    token: String,
+    pub ip_address: std::net::Ipv4Addr,
+    pub token: String,
+    /// The port of the API, defaults to 8443.
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: TlsMode,
+    /// The timeout for each request in seconds, see [`Hub::with_timeout`]. Ignored without the
+    /// `runtime` feature flag.
+    #[serde(default)]
+    pub request_timeout: Option<u64>,
+    /// The maximum number of concurrent requests, see [`HubBuilder::max_connections`].
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    /// The maximum number of requests within an interval, see [`HubBuilder::rate_limit`]. Ignored
+    /// without the `runtime` feature flag.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    /// The serial number of the hub to find it again if it gets a new IP address, see
+    /// [`Hub::with_rediscovery`]. Ignored without the `discovery` feature flag and unless the
+    /// certificate is pinned with `tls`.
+    #[serde(default)]
+    pub serial_number: Option<String>,
+}
+
+/// The rate limit of a [`Hub`] created from a [`Config`], written as
+/// `rate-limit = { requests = 10, interval = 1 }` in the `toml` file.
+#[cfg(feature = "config")]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct RateLimit {
+    /// The maximum number of requests within the interval.
+    pub requests: usize,
+    /// The length of the interval in seconds.
+    pub interval: u64,
+}
+
+/// How the certificate of the hub is verified by a [`Hub`] created from a [`Config`]. It's
+/// written as `tls = "no-verify"` or `tls = { pinned = "hub.pem" }` in the `toml` file.
+#[cfg(feature = "config")]
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TlsMode {
+    /// Don't verify the certificate, which requires the `danger-accept-invalid-certs` feature
+    /// flag. This is the default to work with the file created by `generate-token`.
+    #[default]
+    NoVerify,
+    /// Only accept the certificate in the PEM file at the path, see
+    /// [`HubBuilder::pinned_certificate`].
+    Pinned(std::path::PathBuf),
 }
 
-/// The default implementation for [`Hub`] can be used to read the IP address and token from a
-/// `toml` file. Such `toml` file will be created by running the `generate-token` binary. It will
-/// also use the [`danger`](crate::danger) module to setup [`rustls`] with no certification
-/// verification.
 #[cfg(feature = "config")]
+impl Config {
+    /// The file created by the `generate-token` binary and read by [`Hub::default`].
+    pub const DEFAULT_PATH: &'static str = "config.toml";
+
+    /// Read the [`Config`] from the `toml` file at `path`.
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", path.display()))?;
+
+        content.parse()
+    }
+}
+
+#[cfg(feature = "config")]
+impl std::str::FromStr for Config {
+    type Err = anyhow::Error;
+
+    /// Parse the [`Config`] from the contents of a `toml` file.
+    fn from_str(toml: &str) -> Result<Self, Self::Err> {
+        toml::from_str(toml).map_err(|err| anyhow::anyhow!("failed to parse config: {err}"))
+    }
+}
+
+/// The default implementation for [`Hub`] can be used to read the IP address and token from a
+/// `toml` file. Such `toml` file will be created by running the `generate-token` binary. Unless
+/// the file says otherwise it will use the [`danger`](crate::danger) module to setup [`rustls`]
+/// with no certification verification so it's only available with both the `config` and
+/// `danger-accept-invalid-certs` feature flags. It panics if the file can't be read, use
+/// [`Hub::try_from_config`] to handle the error instead.
+#[cfg(all(
+    feature = "config",
+    feature = "danger-accept-invalid-certs",
+    not(target_arch = "wasm32")
+))]
 impl Default for Hub {
     fn default() -> Self {
-        let mut toml_content = String::new();
-        std::fs::File::open("config.toml")
-            .expect("Failed to open config.toml")
-            .read_to_string(&mut toml_content)
-            .expect("Failed to read config.toml");
+        Config::from_path(Config::DEFAULT_PATH)
+            .and_then(Self::try_from_config)
+            .expect("Failed to create hub")
+    }
+}
+
+/// A [`HubBuilder`] creates a [`Hub`] with its own [`hyper`] client, see [`Hub::builder`]. It's
+/// not available when targeting WASM, use
+/// [`FetchTransport`](crate::transport::FetchTransport) with [`Hub::with_transport`] instead.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct HubBuilder {
+    ip_address: std::net::Ipv4Addr,
+    token: String,
+    port: u16,
+    tls: Option<rustls::ClientConfig>,
+    max_connections: usize,
+    rate_limit: Option<(usize, std::time::Duration)>,
+    #[cfg(feature = "danger-accept-invalid-certs")]
+    accept_invalid_certs: bool,
+    #[cfg(feature = "proxy")]
+    connector: crate::connector::Connector,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl HubBuilder {
+    /// Use the given TLS configuration, f.ex. from
+    /// [`danger::tls_with_root_ca`](crate::danger::tls_with_root_ca). The configuration is
+    /// trusted to verify the certificate of the hub, so don't pass
+    /// `danger::tls_no_verify` here together with [`Hub::with_rediscovery`].
+    pub fn tls_config(mut self, tls: rustls::ClientConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Only accept the certificate with the given SHA-256 fingerprint, see
+    /// [`danger::tls_with_pinned_certificate`](crate::danger::tls_with_pinned_certificate).
+    pub fn pinned_certificate(self, fingerprint: [u8; 32]) -> Self {
+        self.tls_config(crate::danger::tls_with_pinned_certificate(fingerprint))
+    }
+
+    /// Use another port than the default 8443, f.ex. when the hub is reached through port
+    /// forwarding.
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Set the maximum number of connections to the [`Hub`], shared by all clones of the
+    /// [`Hub`]. Requests exceeding the limit are queued until a connection is available. Defaults
+    /// to [`DEFAULT_MAX_CONNECTIONS`]. Without the `runtime` feature flag it only limits how many
+    /// idle connections are kept open.
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    /// Send at most `requests` requests to the [`Hub`] within any `interval`, shared by all clones
+    /// of the [`Hub`]. Requests exceeding the limit are queued, see
+    /// [`RateLimit`](crate::transport::RateLimit). The time spent in the queue counts towards the
+    /// timeout set with [`Hub::with_timeout`]. There's no limit by default. It's ignored without
+    /// the `runtime` feature flag.
+    pub fn rate_limit(mut self, requests: usize, interval: std::time::Duration) -> Self {
+        self.rate_limit = Some((requests, interval));
+        self
+    }
+
+    /// Accept any certificate from the [`Hub`] without verification if no other TLS
+    /// configuration is set. It's only available behind the `danger-accept-invalid-certs` feature
+    /// flag.
+    #[cfg(feature = "danger-accept-invalid-certs")]
+    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    /// Connect to the [`Hub`] through a [`Proxy`](crate::connector::Proxy). It's only available
+    /// behind the `proxy` feature flag.
+    #[cfg(feature = "proxy")]
+    pub fn proxy(mut self, proxy: crate::connector::Proxy) -> Self {
+        self.connector = self.connector.proxy(proxy);
+        self
+    }
 
-        let config: Config = toml::from_str(&toml_content).expect("Failed to parse TOML");
+    /// Resolve host names, f.ex. of the proxy, with the given
+    /// [`Resolve`](crate::connector::Resolve) implementation. It's only available behind the
+    /// `proxy` feature flag.
+    #[cfg(feature = "proxy")]
+    pub fn resolver(mut self, resolver: impl crate::connector::Resolve + 'static) -> Self {
+        self.connector = self.connector.resolver(resolver);
+        self
+    }
 
-        let tls = crate::danger::tls_no_verify();
+    /// Create the [`Hub`]. Fails if no TLS configuration is set and invalid certificates aren't
+    /// accepted.
+    pub fn build(self) -> anyhow::Result<Hub> {
+        let verifies_certificate = self.tls.is_some();
+        let tls = match self.tls {
+            Some(tls) => tls,
+            #[cfg(feature = "danger-accept-invalid-certs")]
+            None if self.accept_invalid_certs => crate::danger::tls_no_verify(),
+            None => anyhow::bail!(
+                "no tls verification configured, use pinned_certificate or tls_config"
+            ),
+        };
+        let tls = std::sync::Arc::new(tls);
         let https = hyper_rustls::HttpsConnectorBuilder::new()
-            .with_tls_config(tls)
+            .with_tls_config((*tls).clone())
             .https_only()
-            .enable_http1()
-            .build();
-
-        let client = hyper::Client::builder().build::<_, hyper::Body>(https);
+            .enable_http1();
+
+        #[cfg(feature = "proxy")]
+        let https = https.wrap_connector(self.connector);
+        #[cfg(not(feature = "proxy"))]
+        let https = https.build();
+
+        let client = hyper::Client::builder()
+            .pool_max_idle_per_host(self.max_connections)
+            .build::<_, hyper::Body>(https);
+        #[cfg(feature = "runtime")]
+        let client = crate::transport::ConcurrencyLimit::new(client, self.max_connections);
+
+        // The rate limit is applied before a connection is taken so waiting requests don't hold a
+        // connection.
+        #[cfg(feature = "runtime")]
+        let mut hub = match self.rate_limit {
+            Some((requests, interval)) => Hub::with_transport(
+                crate::transport::RateLimit::new(client, requests, interval),
+                self.ip_address,
+                self.token,
+            ),
+            None => Hub::with_transport(client, self.ip_address, self.token),
+        };
+        #[cfg(not(feature = "runtime"))]
+        let mut hub = Hub::with_transport(client, self.ip_address, self.token);
+        hub.tls = Some(tls);
+        hub.verifies_certificate = verifies_certificate;
+        hub.credentials_mut().port = self.port;
 
-        Self::new(client, config.ip_address, config.token)
+        Ok(hub)
     }
 }
 
 impl Hub {
+    /// Create a [`HubBuilder`] for the [`Hub`] with the given IP address and bearer token. This is
+    /// an alternative to constructing your own [`hyper`] client for [`Hub::new`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn builder(ip_address: std::net::Ipv4Addr, token: String) -> HubBuilder {
+        HubBuilder {
+            ip_address,
+            token,
+            port: DIRIGERA_PORT,
+            tls: None,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            rate_limit: None,
+            #[cfg(feature = "danger-accept-invalid-certs")]
+            accept_invalid_certs: false,
+            #[cfg(feature = "proxy")]
+            connector: Default::default(),
+        }
+    }
+
+    /// Create a [`Hub`] from a [`Config`], f.ex. read with [`Config::from_path`]. Fails if the
+    /// pinned certificate can't be read or if [`TlsMode::NoVerify`] is used without the
+    /// `danger-accept-invalid-certs` feature flag.
+    #[cfg(all(feature = "config", not(target_arch = "wasm32")))]
+    pub fn try_from_config(config: Config) -> anyhow::Result<Self> {
+        let mut builder = Self::builder(config.ip_address, config.token);
+
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+
+        if let Some(max_connections) = config.max_connections {
+            builder = builder.max_connections(max_connections);
+        }
+
+        if let Some(rate_limit) = config.rate_limit {
+            builder = builder.rate_limit(
+                rate_limit.requests,
+                std::time::Duration::from_secs(rate_limit.interval),
+            );
+        }
+
+        builder = match config.tls {
+            #[cfg(feature = "danger-accept-invalid-certs")]
+            TlsMode::NoVerify => builder.danger_accept_invalid_certs(true),
+            #[cfg(not(feature = "danger-accept-invalid-certs"))]
+            TlsMode::NoVerify => anyhow::bail!(
+                "tls verification is disabled in the config but the danger-accept-invalid-certs feature isn't enabled"
+            ),
+            TlsMode::Pinned(path) => {
+                let pem = std::fs::read(&path)
+                    .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", path.display()))?;
+
+                builder.pinned_certificate(crate::danger::pem_fingerprint(&pem)?)
+            }
+        };
+
+        let hub = builder.build()?;
+
+        #[cfg(feature = "runtime")]
+        let hub = match config.request_timeout {
+            Some(seconds) => hub.with_timeout(std::time::Duration::from_secs(seconds)),
+            None => hub,
+        };
+
+        #[cfg(feature = "discovery")]
+        let hub = match config.serial_number {
+            Some(serial_number) => hub.with_rediscovery(serial_number),
+            None => hub,
+        };
+
+        Ok(hub)
+    }
+
     /// Create a new instance of the [`Hub`]. You need to construct your own [`hyper]` client and
     /// use it together with the IP address and bearer token for the [`Hub`].
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn new(
         client: hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>,
         ip_address: std::net::Ipv4Addr,
         token: String,
     ) -> Self {
+        Self::with_transport(client, ip_address, token)
+    }
+
+    /// Create a new instance of the [`Hub`] that sends all requests through the given
+    /// [`Transport`](crate::transport::Transport). This can be used to wrap the [`hyper`] client,
+    /// f.ex. with a [`Recorder`](crate::transport::Recorder), or to not talk to a real hub at all.
+    /// With the `debug-http` feature flag the transport is wrapped in a
+    /// [`Logger`](crate::transport::Logger).
+    pub fn with_transport(
+        transport: impl crate::transport::Transport + 'static,
+        ip_address: std::net::Ipv4Addr,
+        token: String,
+    ) -> Self {
+        #[cfg(feature = "debug-http")]
+        let transport = crate::transport::Logger::new(transport);
+
         Hub {
-            client,
-            ip_address,
-            token,
+            client: std::sync::Arc::new(transport),
+            #[cfg(not(target_arch = "wasm32"))]
+            tls: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            verifies_certificate: false,
+            #[cfg(feature = "chrono")]
+            audit: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            metrics: None,
+            #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+            retry: RetryPolicy::default(),
+            #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+            busy_until: Default::default(),
+            #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+            timeout: DEFAULT_TIMEOUT,
+            #[cfg(feature = "runtime")]
+            lifecycle: Default::default(),
+            devices_cache: Default::default(),
+            credentials: std::sync::Arc::new(std::sync::RwLock::new(Credentials {
+                ip_address,
+                port: DIRIGERA_PORT,
+                token,
+            })),
+            #[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+            rediscovery: None,
+        }
+    }
+
+    /// Get the IP address of the [`Hub`].
+    pub fn ip_address(&self) -> std::net::Ipv4Addr {
+        self.credentials().ip_address
+    }
+
+    /// Replace the IP address and token used by the [`Hub`] and all its clones, f.ex. when the
+    /// token has been renewed. Requests already sent aren't affected and an
+    /// [`EventListener`](crate::events::EventListener) uses the new credentials the next time it
+    /// connects.
+    pub fn set_credentials(&self, ip_address: std::net::Ipv4Addr, token: String) {
+        let mut credentials = self.credentials_mut();
+        credentials.ip_address = ip_address;
+        credentials.token = token;
+    }
+
+    /// Check if the [`Hub`] uses the given token without exposing it.
+    #[cfg(feature = "reload")]
+    pub(crate) fn has_token(&self, token: &str) -> bool {
+        self.credentials().token == token
+    }
+
+    fn credentials(&self) -> Credentials {
+        self.credentials
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Lock the credentials for writing. Only plain fields are assigned while the lock is held so
+    /// a poisoned lock still holds usable credentials.
+    fn credentials_mut(&self) -> std::sync::RwLockWriteGuard<'_, Credentials> {
+        self.credentials
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Record every mutating request sent by the [`Hub`], and its clones, in the given
+    /// [`AuditSink`](crate::audit::AuditSink).
+    #[cfg(feature = "chrono")]
+    pub fn with_audit_sink(mut self, sink: impl crate::audit::AuditSink + 'static) -> Self {
+        self.audit = Some(std::sync::Arc::new(sink));
+        self
+    }
+
+    /// Call the given [`MetricsSink`](crate::metrics::MetricsSink) with the latency and outcome of
+    /// every request sent by the [`Hub`], and its clones.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_metrics_sink(mut self, sink: impl crate::metrics::MetricsSink + 'static) -> Self {
+        self.metrics = Some(std::sync::Arc::new(sink));
+        self
+    }
+
+    /// Retry requests according to the given [`RetryPolicy`] when the [`Hub`] is busy instead of
+    /// the default policy. Use [`RetryPolicy::none`] to fail right away.
+    #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Fail requests that haven't gotten a full response within `timeout` instead of after
+    /// [`DEFAULT_TIMEOUT`]. The timeout applies to each attempt if the request is retried. Since
+    /// cloning a [`Hub`] is cheap a single operation can get its own timeout, f.ex. for an
+    /// operation that's known to be slow:
+    ///
+    /// ```no_run
+    /// # async fn example(hub: &dirigera::hub::Hub) -> anyhow::Result<()> {
+    /// let devices = hub
+    ///     .clone()
+    ///     .with_timeout(std::time::Duration::from_secs(120))
+    ///     .devices()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Search for the hub with the given serial number on the local network when it stops
+    /// answering, f.ex. after it got a new IP address from DHCP. If the hub is found at a new
+    /// address the IP address of the [`Hub`] and all its clones is replaced and the request is
+    /// sent again, and [`EventListener`](crate::events::EventListener)s emit an
+    /// [`Event::AddressChanged`](crate::events::Event::AddressChanged) when they reconnect. Use
+    /// [`Hub::serial_number`] to get the serial number while the hub is reachable. Only available
+    /// with the `discovery` feature flag.
+    ///
+    /// Any host on the network can announce itself as a hub and the token is sent to each hub
+    /// found, so the search is only done when the certificate of the hub is verified, i.e. when
+    /// the [`Hub`] is built with [`HubBuilder::pinned_certificate`] or
+    /// [`HubBuilder::tls_config`]. The TLS handshake then fails for any other host before the
+    /// token is sent.
+    #[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+    pub fn with_rediscovery(mut self, serial_number: impl Into<String>) -> Self {
+        self.rediscovery = Some(std::sync::Arc::new(Rediscovery {
+            serial_number: serial_number.into(),
+            last_failed: Default::default(),
+        }));
+        self
+    }
+
+    /// Look for the hub with mDNS if [`Hub::with_rediscovery`] is used. Each hub found at another
+    /// address is asked for its serial number and the first one that matches, and accepts the
+    /// token, replaces the IP address. Returns `true` if the IP address was replaced, which may
+    /// have been done by another request while waiting for the lock. Nothing is searched for if
+    /// the certificate isn't verified since the token would be sent to any host that announces
+    /// itself as a hub.
+    #[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+    pub(crate) async fn rediscover(&self) -> bool {
+        let Some(rediscovery) = &self.rediscovery else {
+            return false;
+        };
+
+        if !self.verifies_certificate {
+            return false;
+        }
+
+        let previous = self.ip_address();
+        let mut last_failed = rediscovery.last_failed.lock().await;
+
+        if self.ip_address() != previous {
+            return true;
+        }
+
+        if last_failed.is_some_and(|at| at.elapsed() < REDISCOVERY_COOLDOWN) {
+            return false;
+        }
+
+        let hubs = crate::discovery::discover(crate::discovery::DEFAULT_TIMEOUT)
+            .await
+            .unwrap_or_default();
+
+        for hub in hubs.iter().filter(|hub| hub.ip_address != previous) {
+            if self.probe_serial_number(hub.ip_address).await.as_deref()
+                == Some(rediscovery.serial_number.as_str())
+            {
+                self.credentials_mut().ip_address = hub.ip_address;
+
+                *last_failed = None;
+                return true;
+            }
+        }
+
+        *last_failed = Some(std::time::Instant::now());
+        false
+    }
+
+    /// Get the serial number of the hub at `ip_address` with the current token. This sends the
+    /// request directly instead of through [`Hub::send`] since it's used while sending.
+    #[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+    async fn probe_serial_number(&self, ip_address: std::net::Ipv4Addr) -> Option<String> {
+        let probe = Hub {
+            credentials: std::sync::Arc::new(std::sync::RwLock::new(Credentials {
+                ip_address,
+                ..self.credentials()
+            })),
+            rediscovery: None,
+            ..self.clone()
+        };
+
+        let response = probe
+            .deliver(
+                probe
+                    .create_request(http::Method::GET, "/devices", None)
+                    .ok()?,
+                true,
+            )
+            .await
+            .ok()?;
+
+        if !response.status().is_success() {
+            return None;
+        }
+
+        let body = hyper::body::to_bytes(response.into_body()).await.ok()?;
+        let devices: Vec<serde_json::Value> = serde_json::from_slice(&body).ok()?;
+
+        devices
+            .iter()
+            .find(|device| device.get("deviceType").and_then(|t| t.as_str()) == Some("gateway"))?
+            .get("attributes")?
+            .get("serialNumber")?
+            .as_str()
+            .map(str::to_string)
+    }
+
+    /// Get until when the [`Hub`] asked to not receive any more requests, if it's currently
+    /// busy. Only the requests that were answered as busy are retried later according to the
+    /// [`RetryPolicy`], new requests are still sent right away. This can be used to tell users that
+    /// the [`Hub`] is busy or to hold back requests until then.
+    #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+    pub fn busy_until(&self) -> Option<std::time::Instant> {
+        let busy_until = *self
+            .busy_until
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        busy_until.filter(|busy_until| *busy_until > std::time::Instant::now())
+    }
+
+    /// Shut down the [`Hub`] and all its clones. New requests will fail right away while requests
+    /// already in flight are allowed to finish before this returns. All
+    /// [`EventListener`](crate::events::EventListener)s created from the [`Hub`] will close their
+    /// connection and stop. It's only available behind the `runtime` feature flag, which is
+    /// enabled by default.
+    #[cfg(feature = "runtime")]
+    pub async fn shutdown(&self) {
+        self.lifecycle.closed.send_replace(true);
+        let _drained = self.lifecycle.in_flight.write().await;
+    }
+
+    /// Check if [`Hub::shutdown`] has been called on the [`Hub`] or any of its clones.
+    #[cfg(feature = "runtime")]
+    pub fn is_shut_down(&self) -> bool {
+        *self.lifecycle.closed.borrow()
+    }
+
+    /// Get notified when the [`Hub`] is shut down.
+    #[cfg(feature = "events")]
+    pub(crate) fn shutdown_signal(&self) -> tokio::sync::watch::Receiver<bool> {
+        self.lifecycle.closed.subscribe()
+    }
+
+    /// Mark a request as in flight until the returned guard is dropped. Fails if the [`Hub`] has
+    /// been shut down.
+    #[cfg(feature = "runtime")]
+    async fn begin_request(&self) -> anyhow::Result<tokio::sync::RwLockReadGuard<'_, ()>> {
+        let guard = self.lifecycle.in_flight.read().await;
+
+        if self.is_shut_down() {
+            anyhow::bail!("the hub has been shut down");
+        }
+
+        Ok(guard)
+    }
+
+    /// Start listening for all events from the [`Hub`] with the default
+    /// [`EventOptions`](crate::events::EventOptions). See [`Hub::events_with_options`].
+    ///
+    /// The WebSocket uses the TLS configuration from the [`HubBuilder`], so it can only connect for
+    /// a [`Hub`] created with [`Hub::builder`], `Hub::try_from_config` or `Hub::default`. A
+    /// [`Hub`] created with [`Hub::new`] or [`Hub::with_transport`] fails every connection, so the
+    /// [`EventListener`](crate::events::EventListener) keeps reconnecting, or falls back to
+    /// polling with [`EventSource::Auto`](crate::events::EventSource::Auto). Use
+    /// [`EventSource::Polling`](crate::events::EventSource::Polling) for such a [`Hub`].
+    #[cfg(feature = "events")]
+    pub fn events(&self) -> crate::events::EventListener {
+        self.events_with_options(crate::events::EventOptions::default())
+    }
+
+    /// Start listening for events from the [`Hub`]. The returned
+    /// [`EventListener`](crate::events::EventListener) will reconnect according to the
+    /// [`ReconnectPolicy`](crate::events::ReconnectPolicy) if the connection is lost and only
+    /// yield events matching the [`EventFilter`](crate::events::EventFilter), if any. Must be
+    /// called within a [`tokio`] runtime.
+    #[cfg(feature = "events")]
+    pub fn events_with_options(
+        &self,
+        options: crate::events::EventOptions,
+    ) -> crate::events::EventListener {
+        crate::events::EventListener::spawn(self.clone(), options)
+    }
+
+    /// Get the TLS configuration used to open the WebSocket to the [`Hub`]. This is the
+    /// configuration from the [`HubBuilder`], which only skips verification if
+    /// `HubBuilder::danger_accept_invalid_certs` was set.
+    #[cfg(feature = "events")]
+    pub(crate) fn websocket_tls(&self) -> anyhow::Result<std::sync::Arc<rustls::ClientConfig>> {
+        match &self.tls {
+            Some(tls) => Ok(tls.clone()),
+            None => {
+                anyhow::bail!("no tls configuration for events, create the hub with Hub::builder")
+            }
         }
     }
 
+    /// Create the request used to open the WebSocket to the [`Hub`].
+    #[cfg(feature = "events")]
+    pub(crate) fn websocket_request(
+        &self,
+    ) -> anyhow::Result<tokio_tungstenite::tungstenite::http::Request<()>> {
+        use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http};
+
+        let credentials = self.credentials();
+        let mut request = format!(
+            "wss://{}:{}/{}",
+            credentials.ip_address, credentials.port, DIRIGERA_API_VERSION
+        )
+        .into_client_request()?;
+
+        request.headers_mut().insert(
+            http::header::AUTHORIZATION,
+            format!("Bearer {}", credentials.token).parse()?,
+        );
+
+        Ok(request)
+    }
+
     fn create_request(
         &self,
         method: http::Method,
         path: &str,
         body: Option<hyper::Body>,
     ) -> anyhow::Result<http::Request<hyper::Body>> {
+        let credentials = self.credentials();
         let uri: hyper::Uri = format!(
             "https://{}:{}/{}{}",
-            self.ip_address, DIRIGERA_PORT, DIRIGERA_API_VERSION, path,
+            credentials.ip_address, credentials.port, DIRIGERA_API_VERSION, path,
         )
         .try_into()?;
 
@@ -93,7 +869,7 @@ impl Hub {
             .uri(&uri)
             .header(http::header::CONTENT_TYPE, "application/json")
             .header("User-Agent", "dirigera-rs/0.1.0")
-            .header("Authorization", format!("Bearer {}", self.token));
+            .header("Authorization", format!("Bearer {}", credentials.token));
 
         let req = match body {
             Some(body) => request.body(body),
@@ -103,6 +879,206 @@ impl Hub {
         req.map_err(|err| anyhow::anyhow!(err))
     }
 
+    /// Send a request to the [`Hub`]. If the [`Hub`] doesn't respond with a successful status the
+    /// body is parsed as an [`ApiError`](crate::error::ApiError) which is returned as the error.
+    /// Requests are retried according to the [`RetryPolicy`] if the [`Hub`] is busy.
+    pub(crate) async fn send(
+        &self,
+        method: http::Method,
+        path: &str,
+        body: Option<hyper::Body>,
+    ) -> anyhow::Result<http::Response<hyper::Body>> {
+        self.send_with(method, path, body, true).await
+    }
+
+    /// Send a request like [`Hub::send`]. Unless `buffered` is set the body of a successful
+    /// response isn't read but returned as it streams in from the [`Hub`], and the timeout only
+    /// covers receiving the status.
+    async fn send_with(
+        &self,
+        method: http::Method,
+        path: &str,
+        body: Option<hyper::Body>,
+        buffered: bool,
+    ) -> anyhow::Result<http::Response<hyper::Body>> {
+        #[cfg(feature = "runtime")]
+        let _in_flight = self.begin_request().await?;
+
+        let body = match body {
+            Some(body) => Some(hyper::body::to_bytes(body).await?),
+            None => None,
+        };
+
+        #[cfg(feature = "chrono")]
+        let audit = self.audit.as_ref().filter(|_| method != http::Method::GET);
+        #[cfg(feature = "chrono")]
+        let sent: Option<serde_json::Value> = audit
+            .and(body.as_ref())
+            .and_then(|body| serde_json::from_slice(body).ok());
+
+        #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+        let mut retries = 0;
+        #[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+        let mut rediscovered = false;
+
+        // Requests are never retried on WASM or without the `runtime` feature flag since there's
+        // no timer to wait with.
+        #[cfg_attr(
+            not(all(feature = "runtime", not(target_arch = "wasm32"))),
+            allow(clippy::never_loop)
+        )]
+        loop {
+            #[cfg(not(target_arch = "wasm32"))]
+            let started = std::time::Instant::now();
+
+            let result = self
+                .deliver(
+                    self.create_request(method.clone(), path, body.clone().map(hyper::Body::from))?,
+                    buffered,
+                )
+                .await;
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(metrics) = &self.metrics {
+                metrics.record(&crate::metrics::RequestMetrics::new(
+                    &method,
+                    path,
+                    started.elapsed(),
+                    &result,
+                ));
+            }
+
+            // The hub may have a new IP address, look for it and send the request again.
+            #[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+            if let Err(err) = &result {
+                if !rediscovered
+                    && err.is::<crate::error::ConnectionError>()
+                    && self.rediscover().await
+                {
+                    rediscovered = true;
+                    continue;
+                }
+            }
+
+            #[cfg(feature = "chrono")]
+            if let Some(audit) = audit {
+                audit.record(&crate::audit::AuditEntry::new(
+                    &method,
+                    path,
+                    sent.clone(),
+                    &result,
+                ));
+            }
+
+            let response = result?;
+
+            if response.status().is_success() {
+                return Ok(response);
+            }
+
+            let error = crate::error::ApiError::from_response(method.clone(), path, response).await;
+
+            #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+            if error.is_busy() {
+                let delay = error
+                    .retry_after
+                    .unwrap_or(self.retry.default_delay)
+                    .min(self.retry.max_delay);
+
+                *self
+                    .busy_until
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner) =
+                    Some(std::time::Instant::now() + delay);
+
+                if retries < self.retry.max_retries {
+                    retries += 1;
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            }
+
+            return Err(error.into());
+        }
+    }
+
+    /// Send a single request with the [`Transport`](crate::transport::Transport) and, if
+    /// `buffered` is set or the status isn't successful, read the whole body while the request is
+    /// still in flight so a shutdown doesn't interrupt it. Fails if it doesn't finish within the
+    /// timeout set with [`Hub::with_timeout`].
+    async fn deliver(
+        &self,
+        request: http::Request<hyper::Body>,
+        buffered: bool,
+    ) -> anyhow::Result<http::Response<hyper::Body>> {
+        let (method, path) = (request.method().clone(), request.uri().path().to_string());
+
+        let response = async {
+            let result = async {
+                let (parts, body) = self.client.send(request).await?.into_parts();
+                if !buffered && parts.status.is_success() {
+                    return Ok(http::Response::from_parts(parts, body));
+                }
+
+                let body = hyper::body::to_bytes(body).await?;
+
+                anyhow::Ok(http::Response::from_parts(parts, hyper::Body::from(body)))
+            }
+            .await;
+
+            result.map_err(|source| {
+                crate::error::ConnectionError::Failed {
+                    method: method.clone(),
+                    endpoint: path.clone(),
+                    source,
+                }
+                .into()
+            })
+        };
+
+        #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+        let response = async {
+            tokio::time::timeout(self.timeout, response)
+                .await
+                .map_err(|_| crate::error::ConnectionError::TimedOut {
+                    method: method.clone(),
+                    endpoint: path.clone(),
+                    after: self.timeout,
+                })?
+        };
+
+        response.await
+    }
+
+    /// Patch the attributes of the [`Device`](crate::Device) with the given id without checking
+    /// any capabilities. Used by the [`typed`](crate::typed) wrappers which have already checked
+    /// the capabilities when they were created.
+    pub(crate) async fn patch_attributes(
+        &self,
+        device_id: &str,
+        attributes: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        let body = serde_json::to_string(&serde_json::json!([{ "attributes": attributes }]))?;
+
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", device_id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Send a `GET` request to the given path and deserialize the response. Only borrows the
+    /// [`Hub`] immutably so multiple requests can be in flight at the same time.
+    async fn get<T>(&self, path: &str) -> anyhow::Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        Self::deserialize_response(self.send(http::Method::GET, path, None).await?).await
+    }
+
     async fn deserialize_response<T>(response: http::Response<hyper::Body>) -> anyhow::Result<T>
     where
         T: serde::de::DeserializeOwned,
@@ -113,38 +1089,330 @@ impl Hub {
         serde_json::from_slice(body.as_ref()).map_err(|err| anyhow::anyhow!(err))
     }
 
+    async fn deserialize_response_lenient<T>(
+        response: http::Response<hyper::Body>,
+    ) -> anyhow::Result<Lenient<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let items: Vec<serde_json::Value> = Self::deserialize_response(response).await?;
+        let mut lenient = Lenient {
+            parsed: Vec::with_capacity(items.len()),
+            failed: Vec::new(),
+        };
+
+        for raw in items {
+            match T::deserialize(&raw) {
+                Ok(item) => lenient.parsed.push(item),
+                Err(error) => lenient.failed.push(ParseFailure { raw, error }),
+            }
+        }
+
+        Ok(lenient)
+    }
+
+    /// Deserialize each item of a JSON array in the response as soon as the whole item has been
+    /// read. The items are read with a [`StreamDeserializer`](serde_json::StreamDeserializer)
+    /// over the bytes received so far and each item is dropped from the buffer once it's parsed.
+    /// The items must be separated by exactly one comma, anything else fails the stream.
+    fn deserialize_response_stream<T>(
+        response: http::Response<hyper::Body>,
+    ) -> impl futures_util::Stream<Item = anyhow::Result<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let state = (response.into_body(), Vec::new(), ListPosition::Start, false);
+
+        futures_util::stream::unfold(state, |state| async move {
+            let (mut body, mut buffer, mut position, done) = state;
+            if done {
+                return None;
+            }
+
+            loop {
+                let whitespace = buffer
+                    .iter()
+                    .take_while(|byte| matches!(byte, b' ' | b'\n' | b'\r' | b'\t'))
+                    .count();
+                buffer.drain(..whitespace);
+
+                let error = match (position, buffer.first()) {
+                    (_, None) => None,
+                    (ListPosition::Start, Some(b'[')) => {
+                        buffer.drain(..1);
+                        position = ListPosition::First;
+                        continue;
+                    }
+                    (ListPosition::Start, Some(_)) => {
+                        Some(anyhow::anyhow!("expected the response to be a list"))
+                    }
+                    (ListPosition::First | ListPosition::AfterItem, Some(b']')) => return None,
+                    (ListPosition::AfterItem, Some(b',')) => {
+                        buffer.drain(..1);
+                        position = ListPosition::Next;
+                        continue;
+                    }
+                    (ListPosition::AfterItem, Some(_)) => {
+                        Some(anyhow::anyhow!("expected a comma between the items"))
+                    }
+                    (ListPosition::First | ListPosition::Next, Some(_)) => {
+                        let mut items = serde_json::Deserializer::from_slice(&buffer).into_iter();
+                        let item = items.next();
+                        let read = items.byte_offset();
+
+                        match item {
+                            Some(Ok(item)) => {
+                                buffer.drain(..read);
+                                let state = (body, buffer, ListPosition::AfterItem, false);
+                                return Some((Ok(item), state));
+                            }
+                            // The item hasn't been fully received yet.
+                            Some(Err(err)) if err.is_eof() => None,
+                            Some(Err(err)) => Some(err.into()),
+                            None => None,
+                        }
+                    }
+                };
+
+                if let Some(error) = error {
+                    return Some((Err(error), (body, buffer, position, true)));
+                }
+
+                match hyper::body::HttpBody::data(&mut body).await {
+                    Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                    Some(Err(err)) => {
+                        return Some((Err(err.into()), (body, buffer, position, true)))
+                    }
+                    None => {
+                        let error =
+                            anyhow::anyhow!("the response ended before the end of the list");
+                        return Some((Err(error), (body, buffer, position, true)));
+                    }
+                }
+            }
+        })
+    }
+
+    /// Get the serial number of the hub itself, f.ex. to find it again with
+    /// [`Hub::with_rediscovery`].
+    pub async fn serial_number(&mut self) -> anyhow::Result<String> {
+        self.devices()
+            .await?
+            .into_iter()
+            .find(|device| device.inner().device_type == crate::DeviceType::Gateway)
+            .map(|device| device.inner().attributes.serial_number.clone())
+            .ok_or_else(|| anyhow::anyhow!("the hub didn't list itself among the devices"))
+    }
+
     /// List all devices that is known for the [`Hub`]. This will return an exhaustive list of
     /// [`Device`](crate::Device)s.
     pub async fn devices(&mut self) -> anyhow::Result<Vec<crate::Device>> {
-        Self::deserialize_response(
-            self.client
-                .call(self.create_request(http::Method::GET, "/devices", None)?)
-                .await?,
-        )
-        .await
+        self.get("/devices").await
     }
 
-    /// Get a single [`Device`](crate::Device) based on its id.
-    pub async fn device(&mut self, id: &str) -> anyhow::Result<crate::Device> {
-        Self::deserialize_response(
-            self.client
-                .call(self.create_request(
-                    http::Method::GET,
-                    format!("/devices/{}", id).as_str(),
-                    None,
-                )?)
-                .await?,
-        )
-        .await
+    /// List all devices like [`Hub::devices`] but skip parsing them if the response is the same as
+    /// the last time, which saves a lot of CPU when polling often on a small device. The body is
+    /// compared to the previous one and the same [`Arc`](std::sync::Arc) is returned as long
+    /// as nothing has changed, so use [`Arc::ptr_eq`](std::sync::Arc::ptr_eq) to tell if there are
+    /// any changes. The cache is shared by all clones of the [`Hub`].
+    pub async fn devices_cached(&mut self) -> anyhow::Result<std::sync::Arc<Vec<crate::Device>>> {
+        let response = self.send(http::Method::GET, "/devices", None).await?;
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+
+        if let Some(cached) = self
+            .devices_cache()
+            .as_ref()
+            .filter(|cached| cached.body == body)
+        {
+            return Ok(cached.devices.clone());
+        }
+
+        let devices: std::sync::Arc<Vec<crate::Device>> =
+            std::sync::Arc::new(serde_json::from_slice(&body)?);
+
+        *self.devices_cache() = Some(CachedDevices {
+            body,
+            devices: devices.clone(),
+        });
+
+        Ok(devices)
     }
 
-    /// Rename a [`Device`](crate::Device). The function takes a mutable reference to the
-    /// [`Device`](crate::Device) because on successful renaming the passed
-    /// [`Device`](crate::Device) will be updated with the new name.
-    pub async fn rename(
+    /// Lock the cache for [`Hub::devices_cached`]. The cache is only ever replaced as a whole so a
+    /// poisoned lock still holds a usable cache.
+    fn devices_cache(&self) -> std::sync::MutexGuard<'_, Option<CachedDevices>> {
+        self.devices_cache
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// List all devices like [`Hub::devices`] but parse each [`Device`](crate::Device) as soon as
+    /// it's been read instead of reading the whole response first. Only the device being read is
+    /// kept in memory and the first device is available before the rest of the response has
+    /// arrived, which helps on hubs with a lot of devices. The timeout set with
+    /// [`Hub::with_timeout`] only covers receiving the status, not reading the devices. The
+    /// stream ends after the first error.
+    ///
+    /// The stream holds one of the connections limited by [`HubBuilder::max_connections`] until
+    /// it's read to the end or dropped. Sending other requests while reading the stream, f.ex. in a
+    /// `while let` loop over it, waits for a free connection, so with one connection, or one
+    /// stream per connection, it never finishes. Collect the devices first or raise the limit.
+    pub async fn devices_stream(
         &mut self,
-        device: &mut crate::device::Device,
-        new_name: &str,
+    ) -> anyhow::Result<impl futures_util::Stream<Item = anyhow::Result<crate::Device>>> {
+        let response = self
+            .send_with(http::Method::GET, "/devices", None, false)
+            .await?;
+
+        Ok(Self::deserialize_response_stream(response))
+    }
+
+    /// List all devices that is known for the [`Hub`] as the JSON sent by the hub, without parsing
+    /// them into [`Device`](crate::Device)s.
+    pub async fn devices_raw(&mut self) -> anyhow::Result<Vec<serde_json::Value>> {
+        self.get("/devices").await
+    }
+
+    /// List all devices that is known for the [`Hub`] but parse each device on its own. Unlike
+    /// [`Hub::devices`] a single [`Device`](crate::Device) that can't be parsed won't fail the
+    /// whole listing but will instead be reported in [`Lenient::failed`].
+    pub async fn devices_lenient(&mut self) -> anyhow::Result<Lenient<crate::Device>> {
+        Self::deserialize_response_lenient(self.send(http::Method::GET, "/devices", None).await?)
+            .await
+    }
+
+    /// Get the battery level of all battery powered [`Device`](crate::Device)s, sorted with the
+    /// lowest battery level first.
+    pub async fn battery_report(&mut self) -> anyhow::Result<Vec<crate::report::BatteryStatus>> {
+        let mut report: Vec<_> = self
+            .devices()
+            .await?
+            .iter()
+            .filter_map(crate::report::BatteryStatus::from_device)
+            .collect();
+
+        report.sort_by(|a, b| {
+            a.percentage
+                .cmp(&b.percentage)
+                .then_with(|| a.custom_name.cmp(&b.custom_name))
+        });
+
+        Ok(report)
+    }
+
+    /// Get the air quality for each room from all devices that report it, such as environment
+    /// sensors and air purifiers. See
+    /// [`RoomAirQuality`](crate::report::RoomAirQuality) for how values are aggregated.
+    pub async fn air_quality_report(
+        &mut self,
+    ) -> anyhow::Result<Vec<crate::report::RoomAirQuality>> {
+        Ok(crate::report::RoomAirQuality::from_devices(
+            &self.devices().await?,
+        ))
+    }
+
+    /// Rename many [`Device`](crate::Device)s at once, f.ex. after pairing a lot of identical
+    /// bulbs. Each name is validated and the devices are renamed concurrently. The result for each
+    /// device is returned in the same order as `renames`.
+    pub async fn rename_many(
+        &mut self,
+        renames: &[(crate::DeviceId, &str)],
+    ) -> anyhow::Result<Vec<(crate::DeviceId, anyhow::Result<()>)>> {
+        let devices = self.devices().await?;
+        let hub = &*self;
+
+        let rename = renames.iter().map(|(id, new_name)| {
+            let device = devices
+                .iter()
+                .map(crate::Device::inner)
+                .find(|device| &device.id == id);
+
+            async move {
+                let result = async {
+                    let device = device.ok_or_else(|| anyhow::anyhow!("device {id} not found"))?;
+
+                    if !has_capability(
+                        &device.capabilities.can_receive,
+                        &[crate::device::Capability::CustomName],
+                    ) {
+                        anyhow::bail!("device cannot change name");
+                    }
+
+                    crate::device::validate_name(new_name)?;
+
+                    hub.patch_attributes(id, serde_json::json!({ "customName": new_name }))
+                        .await
+                }
+                .await;
+
+                (id.clone(), result)
+            }
+        });
+
+        Ok(futures_util::future::join_all(rename).await)
+    }
+
+    /// Find all names used by more than one [`Device`](crate::Device), together with the ids of
+    /// the devices using it. Names are compared ignoring case and surrounding whitespace and
+    /// devices without a name are ignored. The result is sorted by name.
+    pub async fn find_duplicate_names(
+        &mut self,
+    ) -> anyhow::Result<Vec<(String, Vec<crate::DeviceId>)>> {
+        let mut names: std::collections::BTreeMap<String, (String, Vec<crate::DeviceId>)> =
+            Default::default();
+
+        for device in self.devices().await? {
+            let inner = device.inner();
+            let name = inner.attributes.custom_name.trim();
+            if name.is_empty() {
+                continue;
+            }
+
+            names
+                .entry(name.to_lowercase())
+                .or_insert_with(|| (name.to_string(), Vec::new()))
+                .1
+                .push(inner.id.clone());
+        }
+
+        Ok(names
+            .into_values()
+            .filter(|(_, ids)| ids.len() > 1)
+            .collect())
+    }
+
+    /// Get the current time and timezone of the [`Hub`] together with the system time it was
+    /// fetched at. Use [`HubTime::drift`] to see if the clocks differ, f.ex. when scheduled
+    /// scenes are triggered at the wrong time.
+    #[cfg(feature = "chrono")]
+    pub async fn time(&mut self) -> anyhow::Result<HubTime> {
+        let sent_at = chrono::Utc::now();
+        let status: HubStatus = self.get("/hub/status").await?;
+        let received_at = chrono::Utc::now();
+
+        Ok(HubTime {
+            current_time: status.attributes.current_time,
+            timezone: status.attributes.timezone,
+            system_time: sent_at + (received_at - sent_at) / 2,
+        })
+    }
+
+    /// Get a single [`Device`](crate::Device) based on its id.
+    pub async fn device(&mut self, id: &str) -> anyhow::Result<crate::Device> {
+        Self::deserialize_response(
+            self.send(http::Method::GET, format!("/devices/{}", id).as_str(), None)
+                .await?,
+        )
+        .await
+    }
+
+    /// Rename a [`Device`](crate::Device). The function takes a mutable reference to the
+    /// [`Device`](crate::Device) because on successful renaming the passed
+    /// [`Device`](crate::Device) will be updated with the new name.
+    pub async fn rename(
+        &mut self,
+        device: &mut crate::device::Device,
+        new_name: &str,
     ) -> anyhow::Result<()> {
         let inner = device.inner_mut();
 
@@ -155,6 +1423,8 @@ impl Hub {
             anyhow::bail!("device cannot change name");
         }
 
+        crate::device::validate_name(new_name)?;
+
         let mut attributes = HashMap::new();
         attributes.insert("customName", new_name);
 
@@ -163,19 +1433,241 @@ impl Hub {
 
         let body: String = serde_json::to_string(&vec![body])?;
 
-        self.client
-            .call(self.create_request(
-                http::Method::PATCH,
-                format!("/devices/{}", inner.id).as_str(),
-                Some(hyper::Body::from(body)),
-            )?)
-            .await?;
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
 
         inner.attributes.custom_name = new_name.to_string();
 
         Ok(())
     }
 
+    /// Move a [`Device`](crate::Device) to another [`Room`](crate::device::Room). The function
+    /// takes a mutable reference to the [`Device`](crate::Device) because on success the passed
+    /// [`Device`](crate::Device) will be updated with the new room.
+    pub async fn set_room(
+        &mut self,
+        device: &mut crate::device::Device,
+        room: &crate::device::Room,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+        let body = serde_json::to_string(&serde_json::json!([{ "roomId": room.id }]))?;
+
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
+
+        inner.room = Some(room.clone());
+
+        Ok(())
+    }
+
+    /// Allow or disallow new devices to join the [`Hub`]. Requires the
+    /// [`Device`](crate::Device) to support
+    /// [`Capability::PermittingJoin`](crate::device::Capability::PermittingJoin) as a receivable
+    /// capability, which only the gateway does. New devices are reported with a `deviceAdded`
+    /// event. The function takes a mutable reference to the [`Device`](crate::Device) because on
+    /// success the passed [`Device`](crate::Device) will be updated with the new state.
+    pub async fn set_permitting_join(
+        &mut self,
+        device: &mut crate::device::Device,
+        permitting_join: bool,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+
+        if !has_capability(
+            inner.capabilities.can_receive.as_ref(),
+            &[crate::device::Capability::PermittingJoin],
+        ) {
+            anyhow::bail!("device cannot permit join");
+        }
+
+        self.patch_attributes(
+            &inner.id,
+            serde_json::json!({ "permittingJoin": permitting_join }),
+        )
+        .await?;
+
+        inner.attributes.permitting_join = permitting_join;
+
+        Ok(())
+    }
+
+    /// Hide or unhide a [`Device`](crate::Device), f.ex. to remove decommissioned but still paired
+    /// devices from dashboards. The function takes a mutable reference to the
+    /// [`Device`](crate::Device) because on success the passed [`Device`](crate::Device) will be
+    /// updated with the new state.
+    pub async fn set_hidden(
+        &mut self,
+        device: &mut crate::device::Device,
+        hidden: bool,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+
+        let mut body = HashMap::new();
+        body.insert("isHidden", hidden);
+
+        let body: String = serde_json::to_string(&vec![body])?;
+
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
+
+        inner.is_hidden = Some(hidden);
+
+        Ok(())
+    }
+
+    /// List all [`DeviceSet`](crate::device::DeviceSet)s that is configured in the [`Hub`].
+    pub async fn device_sets(&mut self) -> anyhow::Result<Vec<crate::device::DeviceSet>> {
+        self.get("/device-set").await
+    }
+
+    /// Create a new empty [`DeviceSet`](crate::device::DeviceSet) with the given name and icon.
+    pub async fn create_device_set(
+        &mut self,
+        name: &str,
+        icon: &str,
+    ) -> anyhow::Result<crate::device::DeviceSet> {
+        crate::device::validate_name(name)?;
+
+        let body = serde_json::json!({ "name": name, "icon": icon });
+
+        let created: CreatedResource = Self::deserialize_response(
+            self.send(
+                http::Method::POST,
+                "/device-set",
+                Some(hyper::Body::from(serde_json::to_string(&body)?)),
+            )
+            .await?,
+        )
+        .await?;
+
+        Ok(crate::device::DeviceSet {
+            id: created.id,
+            name: name.to_string(),
+            icon: Some(icon.to_string()),
+        })
+    }
+
+    /// Rename a [`DeviceSet`](crate::device::DeviceSet). The function takes a mutable reference
+    /// to the [`DeviceSet`](crate::device::DeviceSet) because on successful change it will be
+    /// updated with the new name.
+    pub async fn rename_device_set(
+        &mut self,
+        device_set: &mut crate::device::DeviceSet,
+        new_name: &str,
+    ) -> anyhow::Result<()> {
+        crate::device::validate_name(new_name)?;
+
+        let body = serde_json::json!({ "name": new_name });
+
+        self.send(
+            http::Method::PATCH,
+            format!("/device-set/{}", device_set.id).as_str(),
+            Some(hyper::Body::from(serde_json::to_string(&body)?)),
+        )
+        .await?;
+
+        device_set.name = new_name.to_string();
+
+        Ok(())
+    }
+
+    /// Delete a [`DeviceSet`](crate::device::DeviceSet). The devices in the set are not deleted.
+    pub async fn delete_device_set(
+        &mut self,
+        device_set: crate::device::DeviceSet,
+    ) -> anyhow::Result<()> {
+        self.send(
+            http::Method::DELETE,
+            format!("/device-set/{}", device_set.id).as_str(),
+            None,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Add a [`Device`](crate::Device) to a [`DeviceSet`](crate::device::DeviceSet). Adding a
+    /// device to a set it's already in does nothing. The function takes a mutable reference to the
+    /// [`Device`](crate::Device) because on successful change the passed
+    /// [`Device`](crate::Device) will be updated with its new sets.
+    pub async fn add_device_to_set(
+        &mut self,
+        device: &mut crate::device::Device,
+        device_set: &crate::device::DeviceSet,
+    ) -> anyhow::Result<()> {
+        let mut device_sets = device.inner().device_set.clone();
+        if device_sets.iter().any(|set| set.id == device_set.id) {
+            return Ok(());
+        }
+
+        device_sets.push(crate::device::DeviceSet {
+            icon: None,
+            ..device_set.clone()
+        });
+
+        self.set_device_sets(device, device_sets).await
+    }
+
+    /// Remove a [`Device`](crate::Device) from a [`DeviceSet`](crate::device::DeviceSet). The
+    /// function takes a mutable reference to the [`Device`](crate::Device) because on successful
+    /// change the passed [`Device`](crate::Device) will be updated with its remaining sets.
+    pub async fn remove_device_from_set(
+        &mut self,
+        device: &mut crate::device::Device,
+        device_set: &crate::device::DeviceSet,
+    ) -> anyhow::Result<()> {
+        let device_sets = device
+            .inner()
+            .device_set
+            .iter()
+            .filter(|set| set.id != device_set.id)
+            .cloned()
+            .collect();
+
+        self.set_device_sets(device, device_sets).await
+    }
+
+    async fn set_device_sets(
+        &mut self,
+        device: &mut crate::device::Device,
+        device_sets: Vec<crate::device::DeviceSet>,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+
+        let ids: Vec<_> = device_sets
+            .iter()
+            .map(|set| serde_json::json!({ "id": set.id }))
+            .collect();
+
+        let mut body = HashMap::new();
+        body.insert("deviceSet", ids);
+
+        let body: String = serde_json::to_string(&vec![body])?;
+
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
+
+        inner.device_set = device_sets;
+
+        Ok(())
+    }
+
     /// Toggle a [`Device`](crate::Device) on and off. Requires the [`Device`](crate::Device) to
     /// support [`Capability::IsOn`](crate::device::Capability::IsOn) as a receivable capability.
     /// The function takes a mutable reference to the [`Device`](crate::Device) because on
@@ -187,46 +1679,411 @@ impl Hub {
     ) -> anyhow::Result<()> {
         let inner = device.inner_mut();
 
-        if !has_capability(
-            inner.capabilities.can_receive.as_ref(),
-            &[crate::device::Capability::IsOn],
-        ) {
-            anyhow::bail!("device cannot be toggled");
-        }
-
+        if !has_capability(
+            inner.capabilities.can_receive.as_ref(),
+            &[crate::device::Capability::IsOn],
+        ) {
+            anyhow::bail!("device cannot be toggled");
+        }
+
+        let mut attributes = HashMap::new();
+        inner
+            .attributes
+            .is_on
+            .map(|x| attributes.insert("isOn", !x));
+
+        let mut body = HashMap::new();
+        body.insert("attributes", attributes);
+
+        let body: String = serde_json::to_string(&vec![body])?;
+
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
+
+        inner.attributes.is_on = inner.attributes.is_on.map(|x| !x);
+
+        Ok(())
+    }
+
+    /// Turn a [`Device`](crate::Device) on or off. Unlike [`Hub::toggle_on_off`] this doesn't
+    /// depend on the current state, which the hub doesn't always report. Requires the
+    /// [`Device`](crate::Device) to support
+    /// [`Capability::IsOn`](crate::device::Capability::IsOn) as a receivable capability. The
+    /// function takes a mutable reference to the [`Device`](crate::Device) because on successful
+    /// change the passed [`Device`](crate::Device) will be updated with the new state.
+    pub async fn set_on(
+        &mut self,
+        device: &mut crate::device::Device,
+        is_on: bool,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+
+        if !has_capability(
+            inner.capabilities.can_receive.as_ref(),
+            &[crate::device::Capability::IsOn],
+        ) {
+            anyhow::bail!("device cannot be turned on and off");
+        }
+
+        self.patch_attributes(&inner.id, serde_json::json!({ "isOn": is_on }))
+            .await?;
+
+        inner.attributes.is_on = Some(is_on);
+
+        Ok(())
+    }
+
+    /// Set light level on the [`Device`](crate::Device). Requires the [`Device`](crate::Device) to
+    /// support [`Capability::LightLevel`](crate::device::Capability::LightLevel) as a receivable
+    /// capability. The function takes a mutable reference to the [`Device`](crate::Device) because
+    /// on successful change the passed [`Device`](crate::Device) will be updated with the new
+    /// light level. The level must be between 1% and 100% since the hub rejects 0%, use
+    /// [`Hub::set_light_level_or_off`] to turn the light off instead.
+    pub async fn set_light_level(
+        &mut self,
+        device: &mut crate::device::Device,
+        level: crate::units::Percent,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+
+        if !has_capability(
+            inner.capabilities.can_receive.as_ref(),
+            &[crate::device::Capability::LightLevel],
+        ) {
+            anyhow::bail!("device cannot set light level");
+        }
+
+        if !level.is_valid_light_level() {
+            anyhow::bail!("level must be between 1 -> 100");
+        }
+
+        let mut attributes = HashMap::new();
+        attributes.insert("lightLevel", level);
+
+        let mut body = HashMap::new();
+        body.insert("attributes", attributes);
+
+        let body: String = serde_json::to_string(&vec![body])?;
+
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
+
+        inner.attributes.light_level = Some(level);
+
+        Ok(())
+    }
+
+    /// Set the light level like [`Hub::set_light_level`] but turn the
+    /// [`Device`](crate::Device) off if the level is 0%, f.ex. when the level comes from a slider
+    /// starting at zero.
+    pub async fn set_light_level_or_off(
+        &mut self,
+        device: &mut crate::device::Device,
+        level: crate::units::Percent,
+    ) -> anyhow::Result<()> {
+        if level == crate::units::Percent(0) {
+            return self
+                .execute(device, &crate::command::Command::TurnOff)
+                .await;
+        }
+
+        self.set_light_level(device, level).await
+    }
+
+    /// Gradually change the light level of the [`Device`](crate::Device) to `target` over
+    /// `duration`, f.ex. for a wake-up light. The level is changed in steps of at least 1% and at
+    /// most every 500 ms. A light that's off is turned on at 1% before fading up and a `target` of
+    /// 0% fades down to 1% and turns the light off. It's not available when targeting WASM.
+    #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+    pub async fn fade_light(
+        &mut self,
+        device: &mut crate::device::Device,
+        target: crate::units::Percent,
+        duration: std::time::Duration,
+    ) -> anyhow::Result<()> {
+        use crate::units::Percent;
+
+        if !target.is_valid() {
+            anyhow::bail!("level must be between 0 -> 100");
+        }
+
+        let mut current = device
+            .inner()
+            .attributes
+            .light_level
+            .ok_or_else(|| anyhow::anyhow!("device has no light level"))?;
+
+        if target > Percent(0) && device.inner().attributes.is_on == Some(false) {
+            self.set_light_level(device, Percent::MIN_LIGHT_LEVEL)
+                .await?;
+            self.execute(device, &crate::command::Command::TurnOn)
+                .await?;
+            current = Percent::MIN_LIGHT_LEVEL;
+        }
+
+        let start = i32::from(current.0);
+        let end = i32::from(target.max(Percent::MIN_LIGHT_LEVEL).0);
+        let max_steps = (duration.as_millis() / FADE_STEP_INTERVAL.as_millis()).max(1);
+        let steps = u32::try_from(max_steps)
+            .unwrap_or(u32::MAX)
+            .min(start.abs_diff(end))
+            .max(1);
+        let interval = duration / steps;
+
+        for step in 1..=steps {
+            tokio::time::sleep(interval).await;
+
+            let level = start + (end - start) * i32::try_from(step)? / i32::try_from(steps)?;
+            self.set_light_level(device, Percent(u8::try_from(level)?))
+                .await?;
+        }
+
+        if target == Percent(0) {
+            self.execute(device, &crate::command::Command::TurnOff)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Set color temperature on the [`Device`](crate::Device). Requires the
+    /// [`Device`](crate::Device) to support
+    /// [`Capability::ColorTemperature`](crate::device::Capability::ColorTemperature) as a
+    /// receivable capability. The function takes a mutable reference to the
+    /// [`Device`](crate::Device) because on successful change the passed [`Device`](crate::Device)
+    /// will be updated with the new color temperature.
+    pub async fn set_temperature(
+        &mut self,
+        device: &mut crate::device::Device,
+        temperature: crate::units::Kelvin,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+
+        if !has_capability(
+            inner.capabilities.can_receive.as_ref(),
+            &[crate::device::Capability::ColorTemperature],
+        ) {
+            anyhow::bail!("device cannot set color temperature");
+        }
+
+        let min = inner
+            .attributes
+            .color_temperature_min
+            .ok_or_else(|| anyhow::anyhow!("device has no min temperature value"))?;
+        let max = inner
+            .attributes
+            .color_temperature_max
+            .ok_or_else(|| anyhow::anyhow!("device has no max temperature value"))?;
+
+        if !(max..=min).contains(&temperature) {
+            anyhow::bail!("color temperature {temperature} not within {min} -> {max}");
+        }
+
+        let mut attributes = HashMap::new();
+        attributes.insert("colorTemperature", temperature);
+
+        let mut body = HashMap::new();
+        body.insert("attributes", attributes);
+
+        let body: String = serde_json::to_string(&vec![body])?;
+
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
+
+        inner.attributes.color_temperature = Some(temperature);
+
+        Ok(())
+    }
+
+    /// Set color temperature on the [`Device`](crate::Device) as a percentage of its range, where
+    /// 0% is the warmest and 100% the coldest temperature the [`Device`](crate::Device) supports.
+    /// The percentage is mapped to kelvin and set with [`Hub::set_temperature`].
+    pub async fn set_temperature_percent(
+        &mut self,
+        device: &mut crate::device::Device,
+        percent: crate::units::Percent,
+    ) -> anyhow::Result<()> {
+        if !percent.is_valid() {
+            anyhow::bail!("percent must be between 0 -> 100");
+        }
+
+        let attributes = &device.inner().attributes;
+        let (Some(min), Some(max)) = (
+            attributes.color_temperature_min,
+            attributes.color_temperature_max,
+        ) else {
+            anyhow::bail!("device has no color temperature range");
+        };
+
+        // The hub reports the range the other way around, the min value is the coldest.
+        let (warmest, coldest) = (u32::from(min.0.min(max.0)), u32::from(min.0.max(max.0)));
+        let kelvin = warmest + ((coldest - warmest) * u32::from(percent.0) + 50) / 100;
+
+        self.set_temperature(device, crate::units::Kelvin(u16::try_from(kelvin)?))
+            .await
+    }
+
+    /// Set hue and saturation on the [`Device`](crate::Device). Requires the
+    /// [`Device`](crate::Device) to support
+    /// [`Capability::ColorHue`](crate::device::Capability::ColorHue) and
+    /// [`Capability::ColorSaturation`](crate::device::Capability::ColorSaturation) as a receivable
+    /// capability. The function takes a mutable reference to the [`Device`](crate::Device) because
+    /// on successful change the passed [`Device`](crate::Device) will be updated with the new hue
+    /// and saturation.
+    pub async fn set_hue_saturation(
+        &mut self,
+        device: &mut crate::device::Device,
+        hue: f64,
+        saturation: f64,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+
+        if !has_capability(
+            inner.capabilities.can_receive.as_ref(),
+            &[
+                crate::device::Capability::ColorHue,
+                crate::device::Capability::ColorSaturation,
+            ],
+        ) {
+            anyhow::bail!("device cannot be change for hue and saturation");
+        }
+
+        if !(0f64..=360f64).contains(&hue) {
+            anyhow::bail!("hue must be between 0.0 -> 360.0");
+        }
+
+        if !(0f64..=1f64).contains(&saturation) {
+            anyhow::bail!("saturation must be between 0.0 -> 1.0");
+        }
+
+        let mut attributes = HashMap::new();
+        attributes.insert("colorHue", hue);
+        attributes.insert("colorSaturation", saturation);
+
+        let mut body = HashMap::new();
+        body.insert("attributes", attributes);
+
+        let body: String = serde_json::to_string(&vec![body])?;
+
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
+
+        inner.attributes.color_hue = Some(hue);
+        inner.attributes.color_saturation = Some(saturation);
+
+        Ok(())
+    }
+
+    /// Set the color of the [`Device`](crate::Device) from any color that can be converted to
+    /// hue and saturation, f.ex. [`Rgb`](crate::color::Rgb) or [`Xy`](crate::color::Xy). Unlike
+    /// [`Hub::set_hue_saturation`] the color is [`Hs::normalized`](crate::color::Hs::normalized)
+    /// instead of rejected when it's out of range, just like with
+    /// [`Colorable::set_color`](crate::typed::Colorable::set_color).
+    pub async fn set_color(
+        &mut self,
+        device: &mut crate::device::Device,
+        color: impl Into<crate::color::Hs>,
+    ) -> anyhow::Result<()> {
+        let color = color.into().normalized();
+
+        self.set_hue_saturation(device, color.hue, color.saturation)
+            .await
+    }
+
+    /// Set effect on the [`Device`](crate::Device). Requires the [`Device`](crate::Device) to
+    /// support [`Capability::Effect`](crate::device::Capability::Effect) as a receivable
+    /// capability. The function takes a mutable reference to the [`Device`](crate::Device) because
+    /// on successful change the passed [`Device`](crate::Device) will be updated with the new
+    /// effect.
+    pub async fn set_effect(
+        &mut self,
+        device: &mut crate::device::Device,
+        effect: crate::device::Effect,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+
+        if !has_capability(
+            inner.capabilities.can_receive.as_ref(),
+            &[crate::device::Capability::Effect],
+        ) {
+            anyhow::bail!("device cannot set effect");
+        }
+
+        let mut attributes = HashMap::new();
+        attributes.insert("effect", &effect);
+
+        let mut body = HashMap::new();
+        body.insert("attributes", attributes);
+
+        let body: String = serde_json::to_string(&vec![body])?;
+
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
+
+        inner.attributes.effect = Some(effect);
+
+        Ok(())
+    }
+
+    /// Set startup behaviour on the [`Device`](crate::Device). The function takes a mutable
+    /// reference to the [`Device`](crate::Device) because on successful change the passed
+    /// [`Device`](crate::Device) will be updated with the new startup behaviour.
+    pub async fn set_startup_behaviour(
+        &mut self,
+        device: &mut crate::device::Device,
+        behaviour: crate::device::Startup,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
+
         let mut attributes = HashMap::new();
-        inner
-            .attributes
-            .is_on
-            .map(|x| attributes.insert("isOn", !x));
+        attributes.insert("startupOnOff", &behaviour);
 
         let mut body = HashMap::new();
         body.insert("attributes", attributes);
 
         let body: String = serde_json::to_string(&vec![body])?;
 
-        self.client
-            .call(self.create_request(
-                http::Method::PATCH,
-                format!("/devices/{}", inner.id).as_str(),
-                Some(hyper::Body::from(body)),
-            )?)
-            .await?;
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
 
-        inner.attributes.is_on = inner.attributes.is_on.map(|x| !x);
+        inner.attributes.startup_on_off = Some(behaviour);
 
         Ok(())
     }
 
-    /// Set light level on the [`Device`](crate::Device). Requires the [`Device`](crate::Device) to
-    /// support [`Capability::LightLevel`](crate::device::Capability::LightLevel) as a receivable
-    /// capability. The function takes a mutable reference to the [`Device`](crate::Device) because
-    /// on successful change the passed [`Device`](crate::Device) will be updated with the new
-    /// light level.
-    pub async fn set_light_level(
+    /// Set the light level the [`Device`](crate::Device) will use after a power outage. Passing
+    /// [`None`] will make it use the same light level as before the outage. Requires the
+    /// [`Device`](crate::Device) to support
+    /// [`Capability::LightLevel`](crate::device::Capability::LightLevel) as a receivable
+    /// capability.
+    pub async fn set_startup_light_level(
         &mut self,
         device: &mut crate::device::Device,
-        level: u8,
+        level: Option<crate::units::Percent>,
     ) -> anyhow::Result<()> {
         let inner = device.inner_mut();
 
@@ -237,41 +2094,31 @@ impl Hub {
             anyhow::bail!("device cannot set light level");
         }
 
-        if level > 100 {
-            anyhow::bail!("level must be between 0.0 -> 100.0");
-        }
-
-        let mut attributes = HashMap::new();
-        attributes.insert("lightLevel", level);
-
-        let mut body = HashMap::new();
-        body.insert("attributes", attributes);
-
-        let body: String = serde_json::to_string(&vec![body])?;
+        let level = match level {
+            Some(level) if !(1..=100).contains(&level.0) => {
+                anyhow::bail!("level must be between 1 -> 100");
+            }
+            Some(level) => i16::from(level.0),
+            None => -1,
+        };
 
-        self.client
-            .call(self.create_request(
-                http::Method::PATCH,
-                format!("/devices/{}", inner.id).as_str(),
-                Some(hyper::Body::from(body)),
-            )?)
+        self.patch_attributes(&inner.id, serde_json::json!({ "startupLightLevel": level }))
             .await?;
 
-        inner.attributes.light_level = Some(level);
+        inner.attributes.startup_light_level = Some(level);
 
         Ok(())
     }
 
-    /// Set color temperature on the [`Device`](crate::Device). Requires the
+    /// Set the color temperature the [`Device`](crate::Device) will use after a power outage.
+    /// Passing [`None`] will make it use the same temperature as before the outage. Requires the
     /// [`Device`](crate::Device) to support
     /// [`Capability::ColorTemperature`](crate::device::Capability::ColorTemperature) as a
-    /// receivable capability. The function takes a mutable reference to the
-    /// [`Device`](crate::Device) because on successful change the passed [`Device`](crate::Device)
-    /// will be updated with the new color temperature.
-    pub async fn set_temperature(
+    /// receivable capability.
+    pub async fn set_startup_temperature(
         &mut self,
         device: &mut crate::device::Device,
-        temperature: u16,
+        temperature: Option<crate::units::Kelvin>,
     ) -> anyhow::Result<()> {
         let inner = device.inner_mut();
 
@@ -282,52 +2129,43 @@ impl Hub {
             anyhow::bail!("device cannot set color temperature");
         }
 
-        let min = inner
-            .attributes
-            .color_temperature_min
-            .ok_or_else(|| anyhow::anyhow!("device has no min temperature value"))?;
-        let max = inner
-            .attributes
-            .color_temperature_max
-            .ok_or_else(|| anyhow::anyhow!("device has no max temperature value"))?;
-
-        if !(max..=min).contains(&temperature) {
-            anyhow::bail!("color temperature {temperature} not within {min} -> {max}");
-        }
-
-        let mut attributes = HashMap::new();
-        attributes.insert("colorTemperature", temperature);
-
-        let mut body = HashMap::new();
-        body.insert("attributes", attributes);
-
-        let body: String = serde_json::to_string(&vec![body])?;
+        let temperature = match temperature {
+            Some(temperature) => {
+                if let (Some(min), Some(max)) = (
+                    inner.attributes.color_temperature_min,
+                    inner.attributes.color_temperature_max,
+                ) {
+                    if !(max..=min).contains(&temperature) {
+                        anyhow::bail!("color temperature {temperature} not within {min} -> {max}");
+                    }
+                }
+
+                i16::try_from(temperature.0)?
+            }
+            None => -1,
+        };
 
-        self.client
-            .call(self.create_request(
-                http::Method::PATCH,
-                format!("/devices/{}", inner.id).as_str(),
-                Some(hyper::Body::from(body)),
-            )?)
-            .await?;
+        self.patch_attributes(
+            &inner.id,
+            serde_json::json!({ "startupTemperature": temperature }),
+        )
+        .await?;
 
-        inner.attributes.color_temperature = Some(temperature);
+        inner.attributes.startup_temperature = Some(temperature);
 
         Ok(())
     }
 
-    /// Set hue and saturation on the [`Device`](crate::Device). Requires the
-    /// [`Device`](crate::Device) to support
+    /// Set the color the [`Device`](crate::Device) will use after a power outage. Passing [`None`]
+    /// will make it use the same color as before the outage. Requires the
+    /// [`Device`](crate::Device) to support both
     /// [`Capability::ColorHue`](crate::device::Capability::ColorHue) and
-    /// [`Capability::ColorSaturation`](crate::device::Capability::ColorSaturation) as a receivable
-    /// capability. The function takes a mutable reference to the [`Device`](crate::Device) because
-    /// on successful change the passed [`Device`](crate::Device) will be updated with the new hue
-    /// and saturation.
-    pub async fn set_hue_saturation(
+    /// [`Capability::ColorSaturation`](crate::device::Capability::ColorSaturation) as receivable
+    /// capabilities.
+    pub async fn set_startup_color(
         &mut self,
         device: &mut crate::device::Device,
-        hue: f64,
-        saturation: f64,
+        color: Option<crate::color::Hs>,
     ) -> anyhow::Result<()> {
         let inner = device.inner_mut();
 
@@ -338,91 +2176,173 @@ impl Hub {
                 crate::device::Capability::ColorSaturation,
             ],
         ) {
-            anyhow::bail!("device cannot be change for hue and saturation");
+            anyhow::bail!("device cannot set color");
         }
 
-        if !(0f64..=360f64).contains(&hue) {
-            anyhow::bail!("hue must be between 0.0 -> 360.0");
-        }
+        let (hue, saturation) = match color {
+            Some(color) => {
+                if !(0.0..=360.0).contains(&color.hue) {
+                    anyhow::bail!("hue must be between 0.0 -> 360.0");
+                }
 
-        if !(0f64..=1f64).contains(&saturation) {
-            anyhow::bail!("hue must be between 0.0 -> 1.0");
-        }
+                if !(0.0..=1.0).contains(&color.saturation) {
+                    anyhow::bail!("saturation must be between 0.0 -> 1.0");
+                }
+
+                (color.hue, color.saturation)
+            }
+            None => (-1.0, -1.0),
+        };
+
+        self.patch_attributes(
+            &inner.id,
+            serde_json::json!({
+                "startupColorHue": hue,
+                "startupColorSaturation": saturation,
+            }),
+        )
+        .await?;
+
+        inner.attributes.startup_color_hue = Some(hue);
+        inner.attributes.startup_color_saturation = Some(saturation);
+
+        Ok(())
+    }
+
+    /// Set the time window when the [`Device`](crate::Device) is allowed to install firmware
+    /// updates, f.ex. only at night. The times are in the hub's timezone. The function takes a
+    /// mutable reference to the [`Device`](crate::Device) because on successful change the passed
+    /// [`Device`](crate::Device) will be updated with the new schedule.
+    pub async fn set_ota_schedule(
+        &mut self,
+        device: &mut crate::device::Device,
+        start: crate::TimeOfDay,
+        end: crate::TimeOfDay,
+    ) -> anyhow::Result<()> {
+        let inner = device.inner_mut();
 
         let mut attributes = HashMap::new();
-        attributes.insert("colorHue", hue);
-        attributes.insert("colorSaturation", saturation);
+        attributes.insert("otaScheduleStart", crate::format_time(&start));
+        attributes.insert("otaScheduleEnd", crate::format_time(&end));
 
         let mut body = HashMap::new();
         body.insert("attributes", attributes);
 
         let body: String = serde_json::to_string(&vec![body])?;
 
-        self.client
-            .call(self.create_request(
-                http::Method::PATCH,
-                format!("/devices/{}", inner.id).as_str(),
-                Some(hyper::Body::from(body)),
-            )?)
-            .await?;
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
 
-        inner.attributes.color_hue = Some(hue);
-        inner.attributes.color_saturation = Some(hue);
+        inner.attributes.ota_schedule_start = Some(start);
+        inner.attributes.ota_schedule_end = Some(end);
 
         Ok(())
     }
 
-    /// Set startup behaviour on the [`Device`](crate::Device). The function takes a mutable
-    /// reference to the [`Device`](crate::Device) because on successful change the passed
-    /// [`Device`](crate::Device) will be updated with the new startup behaviour.
-    pub async fn set_startup_behaviour(
+    /// Set when a motion sensor is active, f.ex. only between sunset and sunrise with
+    /// [`SensorSchedule::sunset_to_sunrise`](crate::device::SensorSchedule::sunset_to_sunrise).
+    /// Passing [`None`] makes the sensor active all day. Requires the [`Device`](crate::Device) to
+    /// be a motion sensor. The function takes a mutable reference to the
+    /// [`Device`](crate::Device) because on successful change the passed
+    /// [`Device`](crate::Device) will be updated with the new schedule.
+    pub async fn set_sensor_schedule(
         &mut self,
         device: &mut crate::device::Device,
-        behaviour: crate::device::Startup,
+        schedule: Option<crate::device::SensorSchedule>,
     ) -> anyhow::Result<()> {
         let inner = device.inner_mut();
 
+        if inner.device_type != crate::device::DeviceType::MotionSensor {
+            anyhow::bail!("only motion sensors has a schedule");
+        }
+
+        let mut sensor_config = serde_json::json!({ "scheduleOn": schedule.is_some() });
+        if let Some(schedule) = &schedule {
+            sensor_config["schedule"] = serde_json::to_value(schedule)?;
+        }
+
+        self.patch_attributes(
+            &inner.id,
+            serde_json::json!({ "sensorConfig": sensor_config }),
+        )
+        .await?;
+
+        match &mut inner.attributes.sensor_config {
+            Some(config) => {
+                config.schedule_on = schedule.is_some();
+                if schedule.is_some() {
+                    config.schedule = schedule;
+                }
+            }
+            config @ None => {
+                *config = Some(crate::device::SensorConfig {
+                    schedule_on: schedule.is_some(),
+                    on_duration: None,
+                    schedule,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set the [`OtaEnvironment`](crate::device::OtaEnvironment) the gateway gets firmware updates
+    /// from. Requires the [`Device`](crate::Device) to be the gateway. The function takes a
+    /// mutable reference to the [`Device`](crate::Device) because on successful change the passed
+    /// [`Device`](crate::Device) will be updated with the new environment.
+    pub async fn set_ota_environment(
+        &mut self,
+        device: &mut crate::device::Device,
+        environment: crate::device::OtaEnvironment,
+    ) -> anyhow::Result<()> {
+        let crate::Device::Gateway(inner) = device else {
+            anyhow::bail!("only the gateway has an ota environment");
+        };
+
         let mut attributes = HashMap::new();
-        attributes.insert("startupOnOff", &behaviour);
+        attributes.insert("otaEnvironment", &environment);
 
         let mut body = HashMap::new();
         body.insert("attributes", attributes);
 
         let body: String = serde_json::to_string(&vec![body])?;
 
-        self.client
-            .call(self.create_request(
-                http::Method::PATCH,
-                format!("/devices/{}", inner.id).as_str(),
-                Some(hyper::Body::from(body)),
-            )?)
-            .await?;
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
 
-        inner.attributes.startup_on_off = Some(behaviour);
+        inner.attributes.ota_environment = Some(environment);
 
         Ok(())
     }
 
     /// Set target level on the [`Device`](crate::Device). Requires the [`Device`](crate::Device)
-    /// to support [`Capability::BlindsState`](crate::device::Capability::BlindsState) as a
-    /// receivable capability. The function takes a mutable reference to the
+    /// to support [`Capability::BlindsTargetLevel`](crate::device::Capability::BlindsTargetLevel)
+    /// as a receivable capability, the same as [`Blind`](crate::typed::Blind). The function takes a mutable reference to the
     /// [`Device`](crate::Device) because on successful change the passed [`Device`](crate::Device)
     /// will be updated with the new target level for the blinds.
     pub async fn set_target_level(
         &mut self,
         device: &mut crate::device::Device,
-        level: u8,
+        level: crate::units::Percent,
     ) -> anyhow::Result<()> {
         let inner = device.inner_mut();
 
         if !has_capability(
             inner.capabilities.can_receive.as_ref(),
-            &[crate::device::Capability::BlindsState],
+            &[crate::device::Capability::BlindsTargetLevel],
         ) {
-            anyhow::bail!("device cannot be change for blind state");
+            anyhow::bail!("device cannot change blinds target level");
         }
 
-        if level > 100 {
+        if !level.is_valid() {
             anyhow::bail!("level must be between 0.0 -> 100.0");
         }
 
@@ -434,78 +2354,722 @@ impl Hub {
 
         let body: String = serde_json::to_string(&vec![body])?;
 
-        self.client
-            .call(self.create_request(
-                http::Method::PATCH,
-                format!("/devices/{}", inner.id).as_str(),
-                Some(hyper::Body::from(body)),
-            )?)
-            .await?;
+        self.send(
+            http::Method::PATCH,
+            format!("/devices/{}", inner.id).as_str(),
+            Some(hyper::Body::from(body)),
+        )
+        .await?;
 
         inner.attributes.blinds_target_level = Some(level);
 
         Ok(())
     }
 
+    /// Execute a [`Command`](crate::command::Command) on the [`Device`](crate::Device). The
+    /// function takes a mutable reference to the [`Device`](crate::Device) because on success the
+    /// passed [`Device`](crate::Device) will be updated with the new state.
+    pub async fn execute(
+        &mut self,
+        device: &mut crate::device::Device,
+        command: &crate::command::Command,
+    ) -> anyhow::Result<()> {
+        use crate::command::Command;
+
+        match command {
+            Command::TurnOn => self.set_on(device, true).await,
+            Command::TurnOff => self.set_on(device, false).await,
+            Command::Toggle => self.toggle_on_off(device).await,
+            Command::SetLightLevel(level) => self.set_light_level(device, *level).await,
+            Command::SetColorTemperature(temperature) => {
+                self.set_temperature(device, *temperature).await
+            }
+            Command::SetBlindsLevel(level) => self.set_target_level(device, *level).await,
+        }
+    }
+
     /// List all scenes that is known for the [`Hub`]. This will return an exhaustive list of
     /// [`Scene`](crate::Scene)s.
     pub async fn scenes(&mut self) -> anyhow::Result<Vec<crate::Scene>> {
-        Self::deserialize_response(
-            self.client
-                .call(self.create_request(http::Method::GET, "/scenes", None)?)
-                .await?,
+        self.get("/scenes").await
+    }
+
+    /// List all rooms that is configured in the [`Hub`].
+    pub async fn rooms(&mut self) -> anyhow::Result<Vec<crate::device::Room>> {
+        self.get("/rooms").await
+    }
+
+    /// Create a new [`Room`](crate::device::Room). See
+    /// [`RoomColor::KNOWN`](crate::device::RoomColor::KNOWN) and
+    /// [`RoomIcon::KNOWN`](crate::device::RoomIcon::KNOWN) for the values known by this crate.
+    /// Unknown colors and icons, f.ex. added in a newer firmware, are sent as is and it's up to
+    /// the hub to accept them.
+    pub async fn create_room(
+        &mut self,
+        name: &str,
+        icon: crate::device::RoomIcon,
+        color: crate::device::RoomColor,
+    ) -> anyhow::Result<crate::device::Room> {
+        crate::device::validate_name(name)?;
+
+        let body = serde_json::json!({ "name": name, "icon": icon, "color": color });
+
+        let created: CreatedResource = Self::deserialize_response(
+            self.send(
+                http::Method::POST,
+                "/rooms",
+                Some(hyper::Body::from(serde_json::to_string(&body)?)),
+            )
+            .await?,
         )
-        .await
+        .await?;
+
+        Ok(crate::device::Room {
+            id: created.id.into(),
+            name: name.to_string(),
+            color,
+            icon,
+        })
+    }
+
+    /// List all scenes with an action for the [`Device`](crate::Device) with the given id, either
+    /// directly or through a [`DeviceSet`](crate::device::DeviceSet) the device is in.
+    pub async fn scenes_for_device(
+        &mut self,
+        device_id: &crate::DeviceId,
+    ) -> anyhow::Result<Vec<crate::Scene>> {
+        let (device, mut scenes): (crate::Device, Vec<crate::Scene>) =
+            futures_util::future::try_join(
+                self.get(&format!("/devices/{}", device_id)),
+                self.get("/scenes"),
+            )
+            .await?;
+
+        scenes.retain(|scene| scene.inner().targets_device(&device));
+
+        Ok(scenes)
+    }
+
+    /// List all scenes with an action for any [`Device`](crate::Device) in the
+    /// [`Room`](crate::device::Room) with the given id.
+    pub async fn scenes_for_room(
+        &mut self,
+        room_id: &crate::RoomId,
+    ) -> anyhow::Result<Vec<crate::Scene>> {
+        let (devices, mut scenes): (Vec<crate::Device>, Vec<crate::Scene>) =
+            futures_util::future::try_join(self.get("/devices"), self.get("/scenes")).await?;
+
+        let in_room: Vec<_> = devices
+            .iter()
+            .filter(|device| {
+                device
+                    .inner()
+                    .room
+                    .as_ref()
+                    .is_some_and(|room| &room.id == room_id)
+            })
+            .collect();
+
+        scenes.retain(|scene| {
+            in_room
+                .iter()
+                .any(|device| scene.inner().targets_device(device))
+        });
+
+        Ok(scenes)
+    }
+
+    /// Turn off all lights that are on. See [`Hub::all_off`].
+    pub async fn turn_off_all_lights(
+        &mut self,
+    ) -> anyhow::Result<Vec<(crate::DeviceId, anyhow::Result<()>)>> {
+        let devices = self.devices().await?;
+
+        Ok(self
+            .turn_off(
+                devices
+                    .iter()
+                    .filter(|device| matches!(device, crate::Device::Light(_))),
+            )
+            .await)
+    }
+
+    /// Turn off all devices that can be turned on and off and are on, f.ex. lights and outlets,
+    /// except the ones with an id in `exclude`. All devices are turned off concurrently and the
+    /// result for each device is returned.
+    pub async fn all_off(
+        &mut self,
+        exclude: &[crate::DeviceId],
+    ) -> anyhow::Result<Vec<(crate::DeviceId, anyhow::Result<()>)>> {
+        let devices = self.devices().await?;
+
+        Ok(self
+            .turn_off(
+                devices
+                    .iter()
+                    .filter(|device| !exclude.contains(&device.inner().id)),
+            )
+            .await)
+    }
+
+    /// Change the light level of the [`Device`](crate::Device)s with the given ids by `delta`
+    /// percentage points relative to their current level, f.ex. `-20` to dim all lights in a room
+    /// by 20%. The new level is clamped to 1 -> 100 so a light is never turned off by dimming. All
+    /// devices are changed concurrently and the new level, or the error, for each device is
+    /// returned.
+    pub async fn adjust_light_level(
+        &mut self,
+        device_ids: &[crate::DeviceId],
+        delta: i8,
+    ) -> anyhow::Result<Vec<(crate::DeviceId, anyhow::Result<crate::units::Percent>)>> {
+        let devices = self.devices().await?;
+        let hub = &*self;
+
+        let adjust = device_ids.iter().map(|id| {
+            let device = devices
+                .iter()
+                .map(crate::Device::inner)
+                .find(|device| &device.id == id);
+
+            async move {
+                let result = async {
+                    let device = device.ok_or_else(|| anyhow::anyhow!("device {id} not found"))?;
+
+                    if !has_capability(
+                        &device.capabilities.can_receive,
+                        &[crate::device::Capability::LightLevel],
+                    ) {
+                        anyhow::bail!("device cannot set light level");
+                    }
+
+                    let current = device
+                        .attributes
+                        .light_level
+                        .ok_or_else(|| anyhow::anyhow!("device has no light level"))?;
+                    let level = crate::units::Percent(
+                        (i16::from(current.0) + i16::from(delta)).clamp(1, 100) as u8,
+                    );
+
+                    hub.patch_attributes(id, serde_json::json!({ "lightLevel": level }))
+                        .await?;
+
+                    Ok(level)
+                }
+                .await;
+
+                (id.clone(), result)
+            }
+        });
+
+        Ok(futures_util::future::join_all(adjust).await)
+    }
+
+    async fn turn_off<'a>(
+        &self,
+        devices: impl Iterator<Item = &'a crate::Device>,
+    ) -> Vec<(crate::DeviceId, anyhow::Result<()>)> {
+        let turn_off = devices
+            .map(crate::Device::inner)
+            .filter(|device| {
+                device.attributes.is_on == Some(true)
+                    && has_capability(
+                        &device.capabilities.can_receive,
+                        &[crate::device::Capability::IsOn],
+                    )
+            })
+            .map(|device| async move {
+                let result = self
+                    .patch_attributes(&device.id, serde_json::json!({ "isOn": false }))
+                    .await;
+
+                (device.id.clone(), result)
+            });
+
+        futures_util::future::join_all(turn_off).await
+    }
+
+    /// Get the devices, scenes and rooms of the [`Hub`] in a single [`Home`](crate::home::Home).
+    /// All three are fetched concurrently so this is faster than calling [`Hub::devices`],
+    /// [`Hub::scenes`] and [`Hub::rooms`] after each other, especially on a slow network.
+    pub async fn snapshot(&mut self) -> anyhow::Result<crate::home::Home> {
+        let (devices, scenes, rooms) = futures_util::future::try_join3(
+            self.get("/devices"),
+            self.get("/scenes"),
+            self.get("/rooms"),
+        )
+        .await?;
+
+        Ok(crate::home::Home {
+            devices,
+            scenes,
+            rooms,
+            #[cfg(feature = "chrono")]
+            fetched_at: chrono::Utc::now(),
+        })
     }
 
     /// Get a single [`Scene`](crate::Scene) based on its id.
     pub async fn scene(&mut self, id: &str) -> anyhow::Result<crate::Scene> {
         Self::deserialize_response(
-            self.client
-                .call(self.create_request(
-                    http::Method::GET,
-                    format!("/scenes/{}", id).as_str(),
-                    None,
-                )?)
+            self.send(http::Method::GET, format!("/scenes/{}", id).as_str(), None)
                 .await?,
         )
         .await
     }
 
+    /// Create a copy of a [`Scene`](crate::Scene) with a new name. The scene is fetched from the
+    /// [`Hub`] to get its current state and created again with the same info, triggers and
+    /// actions. The ids of the triggers and actions are generated by the [`Hub`] for the copy.
+    /// Returns the new [`Scene`](crate::Scene).
+    pub async fn duplicate_scene(
+        &mut self,
+        scene: &crate::scene::Scene,
+        new_name: &str,
+    ) -> anyhow::Result<crate::Scene> {
+        let source = self.scene(&scene.inner().id).await?;
+        let inner = source.inner();
+
+        let triggers = inner
+            .triggers
+            .iter()
+            .map(|trigger| {
+                Ok(without_keys(
+                    serde_json::to_value(trigger)?,
+                    &["id", "nextTriggerAt", "triggeredAt"],
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.create_scene_with_triggers(
+            new_name,
+            &inner.info.icon,
+            triggers,
+            &inner.actions,
+            &inner.commands,
+        )
+        .await
+    }
+
+    /// Create a new [`Scene`](crate::Scene) that can be triggered from the app or with
+    /// [`Hub::trigger_scene`]. See [`SceneIcon::KNOWN`](crate::scene::SceneIcon::KNOWN) for the
+    /// icons known by this crate, unknown icons are sent as is. The ids of the actions are ignored
+    /// and set by the hub.
+    pub async fn create_scene(
+        &mut self,
+        name: &str,
+        icon: crate::scene::SceneIcon,
+        actions: &[crate::scene::Action],
+    ) -> anyhow::Result<crate::Scene> {
+        self.create_scene_with_commands(name, icon, actions, &[])
+            .await
+    }
+
+    /// Create a new [`Scene`](crate::Scene) like [`Hub::create_scene`] that also runs the given
+    /// [`SceneCommand`](crate::scene::SceneCommand)s when triggered.
+    pub async fn create_scene_with_commands(
+        &mut self,
+        name: &str,
+        icon: crate::scene::SceneIcon,
+        actions: &[crate::scene::Action],
+        commands: &[crate::scene::SceneCommand],
+    ) -> anyhow::Result<crate::Scene> {
+        let triggers = vec![serde_json::json!({ "type": "app", "disabled": false })];
+
+        self.create_scene_with_triggers(name, &icon, triggers, actions, commands)
+            .await
+    }
+
+    async fn create_scene_with_triggers(
+        &mut self,
+        name: &str,
+        icon: &crate::scene::SceneIcon,
+        triggers: Vec<serde_json::Value>,
+        actions: &[crate::scene::Action],
+        commands: &[crate::scene::SceneCommand],
+    ) -> anyhow::Result<crate::Scene> {
+        crate::device::validate_name(name)?;
+
+        let actions = actions
+            .iter()
+            .map(|action| Ok(without_keys(serde_json::to_value(action)?, &["id"])))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let body = serde_json::json!({
+            "info": {
+                "name": name,
+                "icon": icon,
+            },
+            "type": "userScene",
+            "triggers": triggers,
+            "actions": actions,
+            "commands": commands,
+        });
+
+        let created: CreatedResource = Self::deserialize_response(
+            self.send(
+                http::Method::POST,
+                "/scenes",
+                Some(hyper::Body::from(serde_json::to_string(&body)?)),
+            )
+            .await?,
+        )
+        .await?;
+
+        self.scene(&created.id).await
+    }
+
+    /// Change when a scheduled trigger of a [`Scene`](crate::Scene) fires. The trigger with the
+    /// given id must be a [`TimeTrigger`](crate::scene::TimeTrigger) or a
+    /// [`SunriseSunsetTrigger`](crate::scene::SunriseSunsetTrigger) and can be changed from one to
+    /// the other. If `days` is [`None`] the trigger fires every day. The function takes a mutable
+    /// reference to the [`Scene`](crate::Scene) because on success the passed
+    /// [`Scene`](crate::Scene) will be refetched to get the next time it triggers.
+    pub async fn set_scene_schedule(
+        &mut self,
+        scene: &mut crate::scene::Scene,
+        trigger_id: &str,
+        time: crate::scene::ScheduleTime,
+        days: Option<std::collections::BTreeSet<crate::scene::Weekday>>,
+    ) -> anyhow::Result<()> {
+        let (trigger_type, schedule) = time.into_trigger(days)?;
+
+        self.update_scheduled_trigger(scene, trigger_id, |trigger| {
+            trigger.insert("type".to_string(), trigger_type.into());
+            trigger.insert("trigger".to_string(), schedule);
+        })
+        .await
+    }
+
+    /// Change what ends a scheduled trigger of a [`Scene`](crate::Scene), f.ex. to turn the
+    /// lights off at a specific time or after a duration. The trigger with the given id must be a
+    /// [`TimeTrigger`](crate::scene::TimeTrigger) or a
+    /// [`SunriseSunsetTrigger`](crate::scene::SunriseSunsetTrigger). The function takes a mutable
+    /// reference to the [`Scene`](crate::Scene) because on success the passed
+    /// [`Scene`](crate::Scene) will be refetched with the new end trigger.
+    pub async fn set_scene_end_trigger(
+        &mut self,
+        scene: &mut crate::scene::Scene,
+        trigger_id: &str,
+        end_trigger: crate::scene::EndTrigger,
+    ) -> anyhow::Result<()> {
+        let end_trigger = serde_json::to_value(end_trigger)?;
+
+        self.update_scheduled_trigger(scene, trigger_id, |trigger| {
+            trigger.insert("endTriggerEvent".to_string(), end_trigger);
+        })
+        .await
+    }
+
+    /// Update the scheduled trigger with the given id with `update` and save all triggers of the
+    /// [`Scene`](crate::Scene). The [`Scene`](crate::Scene) is refetched afterwards since the
+    /// [`Hub`] calculates when the triggers will fire next.
+    async fn update_scheduled_trigger(
+        &mut self,
+        scene: &mut crate::scene::Scene,
+        trigger_id: &str,
+        update: impl FnOnce(&mut serde_json::Map<String, serde_json::Value>),
+    ) -> anyhow::Result<()> {
+        let inner = scene.inner();
+
+        match inner
+            .triggers
+            .iter()
+            .find(|trigger| trigger.id() == trigger_id)
+        {
+            None => anyhow::bail!("scene has no trigger with id {trigger_id}"),
+            Some(crate::scene::Trigger::App(_)) => {
+                anyhow::bail!("trigger {trigger_id} is not scheduled")
+            }
+            Some(_) => {}
+        }
+        let mut update = Some(update);
+
+        let triggers = inner
+            .triggers
+            .iter()
+            .map(|trigger| {
+                let mut value = without_keys(
+                    serde_json::to_value(trigger)?,
+                    &["nextTriggerAt", "triggeredAt"],
+                );
+
+                if trigger.id() == trigger_id {
+                    if let (Some(object), Some(update)) = (value.as_object_mut(), update.take()) {
+                        update(object);
+                    }
+                }
+
+                Ok(value)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let body = serde_json::json!({
+            "info": inner.info,
+            "type": "userScene",
+            "triggers": triggers,
+            "actions": inner.actions,
+            "commands": inner.commands,
+        });
+
+        let id = inner.id.to_string();
+
+        self.send(
+            http::Method::PUT,
+            format!("/scenes/{id}").as_str(),
+            Some(hyper::Body::from(serde_json::to_string(&body)?)),
+        )
+        .await?;
+
+        *scene = self.scene(&id).await?;
+
+        Ok(())
+    }
+
     /// Trigger a [`Scene`](crate::Scene) now. Will work independent of a scheduled scene or not.
     pub async fn trigger_scene(&mut self, scene: &crate::scene::Scene) -> anyhow::Result<()> {
         let inner = scene.inner();
 
-        self.client
-            .call(self.create_request(
-                http::Method::POST,
-                format!("/scenes/{}/trigger", inner.id).as_str(),
-                Some(hyper::Body::empty()),
-            )?)
-            .await?;
+        self.send(
+            http::Method::POST,
+            format!("/scenes/{}/trigger", inner.id).as_str(),
+            Some(hyper::Body::empty()),
+        )
+        .await?;
 
         Ok(())
     }
 
+    /// Trigger a [`Scene`](crate::Scene) and wait until the [`Hub`] reports it as completed or
+    /// the `timeout` expires. Completion is detected by the scene's `last_completed` changing,
+    /// which is checked whenever an event for the scene arrives and at least every second. Once
+    /// completed the devices are fetched to check if each action was applied, see
+    /// [`ActionOutcome`](crate::scene::ActionOutcome). Must be called within a [`tokio`] runtime.
+    #[cfg(feature = "events")]
+    pub async fn trigger_scene_and_wait(
+        &mut self,
+        scene: &crate::scene::Scene,
+        timeout: std::time::Duration,
+    ) -> anyhow::Result<crate::scene::SceneCompletion> {
+        let id = scene.inner().id.clone();
+        let mut listener = self.events();
+        let previous = self.scene(&id).await?.inner().last_completed;
+
+        self.trigger_scene(scene).await?;
+
+        let wait = async {
+            let mut poll = tokio::time::interval(std::time::Duration::from_secs(1));
+
+            loop {
+                tokio::select! {
+                    _ = poll.tick() => {}
+                    Some(event) = listener.next() => {
+                        // Only check the scene for its own events, or if events may have been
+                        // missed.
+                        let is_scene = match &event {
+                            crate::events::Event::Hub(event) => {
+                                event.event_type.starts_with("scene")
+                                    && event.data.get("id").and_then(serde_json::Value::as_str)
+                                        == Some(id.as_str())
+                            }
+                            crate::events::Event::Resync => true,
+                            _ => false,
+                        };
+
+                        if !is_scene {
+                            continue;
+                        }
+                    }
+                }
+
+                let current = self.scene(&id).await?;
+                match current.inner().last_completed {
+                    Some(completed_at) if Some(completed_at) != previous => {
+                        return anyhow::Ok((current, completed_at));
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        let (scene, completed_at) = tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| anyhow::anyhow!("scene {id} did not complete within {timeout:?}"))??;
+
+        let devices = self.devices().await?;
+        let outcome = |device: &crate::device::DeviceData,
+                       attributes: &crate::scene::SceneAttributes| {
+            crate::scene::ActionOutcome {
+                device_id: device.id.clone(),
+                applied: device
+                    .is_reachable
+                    .then(|| attributes.is_applied_to(&device.attributes)),
+            }
+        };
+
+        // A device set action has one outcome for each device in the set.
+        let actions = scene
+            .inner()
+            .actions
+            .iter()
+            .flat_map(|action| match action {
+                crate::scene::Action::Device(action) => {
+                    let device = devices
+                        .iter()
+                        .map(crate::Device::inner)
+                        .find(|device| device.id == action.device_id);
+
+                    vec![match device {
+                        Some(device) => outcome(device, &action.attributes),
+                        None => crate::scene::ActionOutcome {
+                            device_id: action.device_id.clone(),
+                            applied: None,
+                        },
+                    }]
+                }
+                crate::scene::Action::DeviceSet(action) => devices
+                    .iter()
+                    .map(crate::Device::inner)
+                    .filter(|device| {
+                        device
+                            .device_set
+                            .iter()
+                            .any(|set| set.id == action.device_set_id)
+                    })
+                    .map(|device| outcome(device, &action.attributes))
+                    .collect(),
+                crate::scene::Action::Unknown(_) => Vec::new(),
+            })
+            .collect();
+
+        Ok(crate::scene::SceneCompletion {
+            triggered_at: scene.inner().last_triggered,
+            completed_at,
+            scene,
+            actions,
+        })
+    }
+
     /// Undo scene will revert the changes set by the [`Scene`](crate::Scene).
     pub async fn undo_scene(&mut self, scene: &crate::scene::Scene) -> anyhow::Result<()> {
         let inner = scene.inner();
 
-        self.client
-            .call(self.create_request(
-                http::Method::POST,
-                format!("/scenes/{}/undo", inner.id).as_str(),
-                Some(hyper::Body::empty()),
-            )?)
-            .await?;
+        self.send(
+            http::Method::POST,
+            format!("/scenes/{}/undo", inner.id).as_str(),
+            Some(hyper::Body::empty()),
+        )
+        .await?;
 
         Ok(())
     }
 }
 
+/// The current time of the [`Hub`], see [`Hub::time`].
+#[cfg(feature = "chrono")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HubTime {
+    /// The time reported by the [`Hub`].
+    pub current_time: chrono::DateTime<chrono::Utc>,
+    /// The IANA timezone configured in the [`Hub`], f.ex. `Europe/Stockholm`.
+    pub timezone: String,
+    /// The system time when [`HubTime::current_time`] was reported, adjusted for half the round
+    /// trip time of the request.
+    pub system_time: chrono::DateTime<chrono::Utc>,
+}
+
+#[cfg(feature = "chrono")]
+impl HubTime {
+    /// Get how far the [`Hub`] clock is ahead of the system clock. A negative duration means the
+    /// [`Hub`] is behind.
+    pub fn drift(&self) -> chrono::TimeDelta {
+        self.current_time - self.system_time
+    }
+
+    /// Check if the [`Hub`] clock differs from the system clock by more than `tolerance` in
+    /// either direction.
+    pub fn is_drifting(&self, tolerance: chrono::TimeDelta) -> bool {
+        self.drift().abs() > tolerance
+    }
+}
+
+#[cfg(feature = "chrono")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HubStatus {
+    attributes: HubStatusAttributes,
+}
+
+#[cfg(feature = "chrono")]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HubStatusAttributes {
+    #[serde(deserialize_with = "crate::deserialize_datetime")]
+    current_time: chrono::DateTime<chrono::Utc>,
+    timezone: String,
+}
+
+/// The response from the [`Hub`] when creating a new resource.
+#[derive(Debug, Deserialize)]
+struct CreatedResource {
+    id: String,
+}
+
+/// Remove the given keys from a JSON object, used to strip fields set by the [`Hub`] before
+/// sending a resource back.
+fn without_keys(mut value: serde_json::Value, keys: &[&str]) -> serde_json::Value {
+    if let Some(object) = value.as_object_mut() {
+        for key in keys {
+            object.remove(*key);
+        }
+    }
+
+    value
+}
+
 fn has_capability(
     got: &[crate::device::Capability],
     required: &[crate::device::Capability],
 ) -> bool {
     required.iter().all(|item| got.contains(item))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    async fn read_chunks(chunks: &[&'static str]) -> Vec<anyhow::Result<serde_json::Value>> {
+        let chunks = chunks
+            .iter()
+            .map(|chunk| Ok::<_, std::io::Error>(*chunk))
+            .collect::<Vec<_>>();
+        let response =
+            http::Response::new(hyper::Body::wrap_stream(futures_util::stream::iter(chunks)));
+
+        Hub::deserialize_response_stream(response).collect().await
+    }
+
+    #[tokio::test]
+    async fn deserialize_response_stream_items_split_across_chunks() {
+        let items = read_chunks(&[" [ {\"id\":", "\"a\"}", " ,{\"id\":\"b", "\"}\n]"]).await;
+        let ids = items
+            .into_iter()
+            .map(|item| item.unwrap()["id"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ids, ["a", "b"]);
+        assert!(read_chunks(&["[", "]"]).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn deserialize_response_stream_requires_one_comma_between_items() {
+        for body in ["[,{}]", "[{},,{}]", "[{}{}]", "[{},]", "{}", "[{}"] {
+            let items = read_chunks(&[body]).await;
+
+            assert!(
+                items.last().is_some_and(|item| item.is_err()),
+                "{body} should fail"
+            );
+        }
+    }
+}
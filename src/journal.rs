@@ -0,0 +1,220 @@
+//! The [`Journal`] appends every [`Event`](crate::events::Event) as a line of JSON to a file,
+//! giving a record of everything that happened in the home without any further setup. When the
+//! file grows too large it's rotated, keeping a fixed number of old files next to it such as
+//! `events.jsonl.1`, `events.jsonl.2` and so on. Each line looks like this:
+//!
+//! ```json
+//! {"time":"2023-11-16T20:07:09Z","type":"deviceStateChanged","deviceId":"abc123_1","data":{}}
+//! ```
+//!
+//! It's only available behind the `journal` feature flag.
+use serde::Serialize;
+
+use std::io::Write;
+
+/// The size a journal file can grow to before it's rotated if not set with
+/// [`Journal::with_max_size`].
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// The number of rotated files to keep if not set with [`Journal::with_max_files`].
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// A single line in the journal.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry<'a> {
+    time: chrono::DateTime<chrono::Utc>,
+    #[serde(rename = "type")]
+    event_type: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<&'a serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changes: Option<&'a [crate::device::AttributeChange]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous: Option<std::net::Ipv4Addr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ip_address: Option<std::net::Ipv4Addr>,
+}
+
+impl<'a> Entry<'a> {
+    fn new(event: &'a crate::events::Event) -> Self {
+        use crate::events::Event;
+
+        let device_event = |event_type, device_id: &'a crate::DeviceId, time| Self {
+            time,
+            event_type,
+            id: None,
+            device_id: Some(device_id.as_str()),
+            data: None,
+            changes: None,
+            previous: None,
+            ip_address: None,
+        };
+
+        match event {
+            Event::Hub(event) => Self {
+                time: event.time,
+                event_type: &event.event_type,
+                id: Some(&event.id),
+                device_id: event.device_id(),
+                data: Some(&event.data),
+                changes: None,
+                previous: None,
+                ip_address: None,
+            },
+            Event::DoorOpened { device_id, time } => device_event("doorOpened", device_id, *time),
+            Event::DoorClosed { device_id, time } => device_event("doorClosed", device_id, *time),
+            Event::WaterLeakDetected { device_id, time } => {
+                device_event("waterLeakDetected", device_id, *time)
+            }
+            Event::WaterLeakCleared { device_id, time } => {
+                device_event("waterLeakCleared", device_id, *time)
+            }
+            Event::AttributesChanged {
+                device_id,
+                changes,
+                time,
+            } => Self {
+                changes: Some(changes),
+                ..device_event("attributesChanged", device_id, *time)
+            },
+            Event::AddressChanged {
+                previous,
+                ip_address,
+                time,
+            } => Self {
+                time: *time,
+                event_type: "addressChanged",
+                id: None,
+                device_id: None,
+                data: None,
+                changes: None,
+                previous: Some(*previous),
+                ip_address: Some(*ip_address),
+            },
+            Event::Resync => Self {
+                time: chrono::Utc::now(),
+                event_type: "resync",
+                id: None,
+                device_id: None,
+                data: None,
+                changes: None,
+                previous: None,
+                ip_address: None,
+            },
+        }
+    }
+}
+
+/// A [`Journal`] writing events to a file that is rotated when it's larger than the max size.
+#[derive(Debug)]
+pub struct Journal {
+    path: std::path::PathBuf,
+    file: std::fs::File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl Journal {
+    /// Open the journal at `path`, creating the file if it doesn't exist. New events are appended
+    /// to already existing files.
+    pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_size: DEFAULT_MAX_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+        })
+    }
+
+    /// Set the size in bytes a file can grow to before it's rotated.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Set the number of rotated files to keep. With zero the file is truncated when rotated.
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    /// Append the [`Event`](crate::events::Event) to the journal, rotating the file first if it's
+    /// full.
+    pub fn record(&mut self, event: &crate::events::Event) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(&Entry::new(event))?;
+        line.push(b'\n');
+
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(&line)?;
+        self.file.flush()?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Record every event from the [`EventListener`](crate::events::EventListener) until it
+    /// stops. `on_error` is called for events that couldn't be written.
+    pub async fn run<F>(&mut self, listener: &mut crate::events::EventListener, mut on_error: F)
+    where
+        F: FnMut(anyhow::Error),
+    {
+        while let Some(event) = listener.next().await {
+            if let Err(err) = self.record(&event) {
+                on_error(err);
+            }
+        }
+    }
+
+    /// Move `events.jsonl.1` to `events.jsonl.2` and so on, dropping the oldest file, and start a
+    /// new empty file.
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        if self.max_files == 0 {
+            self.file.set_len(0)?;
+            self.size = 0;
+
+            return Ok(());
+        }
+
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(n + 1))?;
+            }
+        }
+
+        std::fs::rename(&self.path, self.rotated_path(1))?;
+
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+
+    fn rotated_path(&self, n: usize) -> std::path::PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+
+        path.into()
+    }
+}
+
+fn open_append(path: &std::path::Path) -> std::io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
@@ -2,31 +2,191 @@
 //! Dirigera is a client to communicate with your IKEA Dirigera hub and control your Trådfri
 //! devices. It is built with [`hyper`] and is bundled with an optional tool to generate the token
 //! you need for the communication.
+#[cfg(feature = "adaptive")]
+pub mod adaptive;
+#[cfg(feature = "chrono")]
+pub mod audit;
+#[cfg(all(feature = "auth", not(target_arch = "wasm32")))]
+pub mod auth;
+#[cfg(feature = "automation")]
+pub mod automation;
+#[cfg(feature = "events")]
+pub mod cache;
+pub mod color;
+pub mod command;
+#[cfg(feature = "proxy")]
+pub mod connector;
 pub mod device;
+#[cfg(all(feature = "discovery", not(target_arch = "wasm32")))]
+pub mod discovery;
+pub mod error;
+#[cfg(feature = "events")]
+pub mod events;
+#[cfg(feature = "uniffi")]
+pub mod ffi;
+#[cfg(feature = "history")]
+pub mod history;
+pub mod home;
 pub mod hub;
+pub mod id;
+#[cfg(feature = "journal")]
+pub mod journal;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod metrics;
+#[cfg(feature = "notifier")]
+pub mod notifier;
+#[cfg(feature = "chrono")]
+pub mod occupancy;
+#[cfg(feature = "queue")]
+pub mod queue;
+#[cfg(feature = "chrono")]
+pub mod reachability;
+#[cfg(feature = "reload")]
+pub mod reload;
+pub mod report;
 pub mod scene;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "tower")]
+pub mod service;
+#[cfg(feature = "chrono")]
+pub mod sun;
+pub mod transport;
+pub mod typed;
+pub mod units;
 
-pub use device::{Device, DeviceData, DeviceType};
+pub use device::{Device, DeviceData, DeviceKind, DeviceType};
+pub use id::{DeviceId, RoomId, SceneId};
 pub use scene::Scene;
 
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
 use serde::Deserialize;
 
-pub(crate) fn deserialize_datetime<'de, D>(
-    deserializer: D,
-) -> Result<chrono::DateTime<chrono::Utc>, D::Error>
+/// Create an enum for string values from the hub where only some values are known. Each known
+/// value gets its own variant and everything else is kept as `Unknown`. The enum is serialized as
+/// the plain string.
+macro_rules! string_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident => $value:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            Unknown(String),
+        }
+
+        impl $name {
+            /// All values known by this crate.
+            pub const KNOWN: &'static [$name] = &[$($name::$variant,)*];
+
+            /// Get the value as used by the hub.
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $value,)*
+                    Self::Unknown(value) => value,
+                }
+            }
+
+            /// Check if the value is known by this crate.
+            pub fn is_known(&self) -> bool {
+                !matches!(self, Self::Unknown(_))
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                match value {
+                    $($value => Self::$variant,)*
+                    value => Self::Unknown(value.to_string()),
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.pad(self.as_str())
+            }
+        }
+
+        #[cfg(feature = "schemars")]
+        impl schemars::JsonSchema for $name {
+            fn schema_name() -> String {
+                stringify!($name).to_string()
+            }
+
+            fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+                String::json_schema(gen)
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Ok(String::deserialize(deserializer)?.as_str().into())
+            }
+        }
+    };
+}
+pub(crate) use string_enum;
+
+/// A point in time sent by the hub. Without the `chrono` feature flag it's kept as the RFC 3339
+/// string sent by the hub.
+#[cfg(feature = "chrono")]
+pub type Timestamp = chrono::DateTime<chrono::Utc>;
+/// A point in time sent by the hub. Without the `chrono` feature flag it's kept as the RFC 3339
+/// string sent by the hub.
+#[cfg(not(feature = "chrono"))]
+pub type Timestamp = String;
+
+/// A time of day such as `22:30` used in schedules. Without the `chrono` feature flag it's kept
+/// as the string sent by the hub.
+#[cfg(feature = "chrono")]
+pub type TimeOfDay = chrono::NaiveTime;
+/// A time of day such as `22:30` used in schedules. Without the `chrono` feature flag it's kept
+/// as the string sent by the hub.
+#[cfg(not(feature = "chrono"))]
+pub type TimeOfDay = String;
+
+/// A duration sent by the hub as a number of seconds. Without the `chrono` feature flag it's kept
+/// as the number of seconds.
+#[cfg(feature = "chrono")]
+pub type TimeDelta = chrono::TimeDelta;
+/// A duration sent by the hub as a number of seconds. Without the `chrono` feature flag it's kept
+/// as the number of seconds.
+#[cfg(not(feature = "chrono"))]
+pub type TimeDelta = i64;
+
+pub(crate) fn deserialize_datetime<'de, D>(deserializer: D) -> Result<Timestamp, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     let date_str = String::deserialize(deserializer)?;
-    match date_str.parse() {
-        Ok(system_time) => Ok(system_time),
-        Err(_) => Err(serde::de::Error::custom("Invalid date format")),
-    }
+
+    #[cfg(feature = "chrono")]
+    return date_str
+        .parse()
+        .map_err(|_| serde::de::Error::custom("Invalid date format"));
+
+    #[cfg(not(feature = "chrono"))]
+    Ok(date_str)
 }
 
 pub(crate) fn deserialize_datetime_optional<'de, D>(
     deserializer: D,
-) -> Result<Option<chrono::DateTime<chrono::Utc>>, D::Error>
+) -> Result<Option<Timestamp>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -36,34 +196,282 @@ where
     }
 }
 
+pub(crate) fn deserialize_time<'de, D>(deserializer: D) -> Result<TimeOfDay, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let time_str = String::deserialize(deserializer)?;
+
+    #[cfg(feature = "chrono")]
+    return chrono::NaiveTime::parse_from_str(&time_str, "%H:%M")
+        .or_else(|_| chrono::NaiveTime::parse_from_str(&time_str, "%H:%M:%S"))
+        .map_err(|_| serde::de::Error::custom("Invalid time format"));
+
+    #[cfg(not(feature = "chrono"))]
+    Ok(time_str)
+}
+
+pub(crate) fn deserialize_time_optional<'de, D>(
+    deserializer: D,
+) -> Result<Option<TimeOfDay>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+
+    Ok(value.and_then(|value| deserialize_time(value).ok()))
+}
+
+pub(crate) fn serialize_time_optional<S>(
+    time: &Option<TimeOfDay>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match time {
+        Some(time) => serializer.serialize_some(&format_time(time)),
+        None => serializer.serialize_none(),
+    }
+}
+
+pub(crate) fn serialize_time<S>(time: &TimeOfDay, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_str(&format_time(time))
+}
+
+/// Format a [`TimeOfDay`] the way the hub expects it.
+pub(crate) fn format_time(time: &TimeOfDay) -> String {
+    #[cfg(feature = "chrono")]
+    return time.format("%H:%M").to_string();
+
+    #[cfg(not(feature = "chrono"))]
+    time.clone()
+}
+
+pub(crate) fn deserialize_duration_seconds<'de, D>(deserializer: D) -> Result<TimeDelta, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let seconds = i64::deserialize(deserializer)?;
+
+    #[cfg(feature = "chrono")]
+    return chrono::TimeDelta::try_seconds(seconds)
+        .ok_or_else(|| serde::de::Error::custom("Duration out of range"));
+
+    #[cfg(not(feature = "chrono"))]
+    Ok(seconds)
+}
+
+pub(crate) fn serialize_duration_seconds<S>(
+    duration: &TimeDelta,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    #[cfg(feature = "chrono")]
+    return serializer.serialize_i64(duration.num_seconds());
+
+    #[cfg(not(feature = "chrono"))]
+    serializer.serialize_i64(*duration)
+}
+
 /// A module that is used to disable TLS verification. This is used because the Dirigera HUB uses
-/// HTTPS but with a self signed certificate.
+/// HTTPS but with a self signed certificate. It's not available when targeting WASM.
+#[cfg(not(target_arch = "wasm32"))]
 pub mod danger {
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+
+    /// A certificate verifier that accepts any certificate. The handshake signatures are still
+    /// verified. It's only available behind the `danger-accept-invalid-certs` feature flag.
+    #[cfg(feature = "danger-accept-invalid-certs")]
+    #[derive(Debug)]
     pub struct NoCertificateVerification;
 
-    impl rustls::client::ServerCertVerifier for NoCertificateVerification {
+    /// The `auth` feature flag uses the verifier internally without making it public.
+    #[cfg(all(feature = "auth", not(feature = "danger-accept-invalid-certs")))]
+    #[derive(Debug)]
+    pub(crate) struct NoCertificateVerification;
+
+    #[cfg(any(feature = "danger-accept-invalid-certs", feature = "auth"))]
+    impl ServerCertVerifier for NoCertificateVerification {
         fn verify_server_cert(
             &self,
-            _end_entity: &rustls::Certificate,
-            _intermediates: &[rustls::Certificate],
-            _server_name: &rustls::client::ServerName,
-            _scts: &mut dyn Iterator<Item = &[u8]>,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
             _ocsp_response: &[u8],
-            _now: std::time::SystemTime,
-        ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
-            Ok(rustls::client::ServerCertVerified::assertion())
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &rustls::DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls12_signature(message, cert, dss)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &rustls::DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls13_signature(message, cert, dss)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+            supported_verify_schemes()
         }
     }
 
+    /// Create a TLS configuration that doesn't verify the certificate of the hub at all. Prefer
+    /// [`tls_with_pinned_certificate`] or [`tls_with_root_ca`]. It's only available behind the
+    /// `danger-accept-invalid-certs` feature flag.
+    #[cfg(feature = "danger-accept-invalid-certs")]
     pub fn tls_no_verify() -> rustls::ClientConfig {
-        let mut tls = rustls::ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(rustls::RootCertStore::empty())
-            .with_no_client_auth();
+        no_verify()
+    }
+
+    /// Create the TLS configuration for [`tls_no_verify`]. It's used without the
+    /// `danger-accept-invalid-certs` feature flag to obtain a token, when the hub's certificate
+    /// can't be pinned yet.
+    #[cfg(any(feature = "danger-accept-invalid-certs", feature = "auth"))]
+    pub(crate) fn no_verify() -> rustls::ClientConfig {
+        rustls::ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(std::sync::Arc::new(NoCertificateVerification))
+            .with_no_client_auth()
+    }
+
+    /// A certificate verifier that only accepts a certificate with the given SHA-256 fingerprint.
+    /// The hub uses a self signed certificate so this is the easiest way to verify that you're
+    /// talking to your hub. The handshake signatures are verified so the hub must also have the
+    /// private key of the certificate.
+    #[derive(Debug)]
+    pub struct PinnedCertificateVerification {
+        fingerprint: [u8; 32],
+    }
 
-        tls.dangerous()
-            .set_certificate_verifier(std::sync::Arc::new(NoCertificateVerification));
+    impl ServerCertVerifier for PinnedCertificateVerification {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            let fingerprint = ring::digest::digest(&ring::digest::SHA256, end_entity);
+
+            if fingerprint.as_ref() != self.fingerprint {
+                return Err(rustls::Error::General(
+                    "certificate doesn't match pinned fingerprint".to_string(),
+                ));
+            }
+
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &rustls::DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls12_signature(message, cert, dss)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &rustls::DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            verify_tls13_signature(message, cert, dss)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+            supported_verify_schemes()
+        }
+    }
+
+    /// Create a TLS configuration that only accepts the certificate with the given SHA-256
+    /// fingerprint of its DER encoding, f.ex. from
+    /// `openssl x509 -noout -fingerprint -sha256`.
+    pub fn tls_with_pinned_certificate(fingerprint: [u8; 32]) -> rustls::ClientConfig {
+        rustls::ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(std::sync::Arc::new(PinnedCertificateVerification {
+                fingerprint,
+            }))
+            .with_no_client_auth()
+    }
+
+    /// Create a TLS configuration that verifies the hub against the given root certificates in
+    /// PEM format, f.ex. the CA extracted from the hub or the CA of a proxy in front of it. Unlike
+    /// [`tls_no_verify`] this is not dangerous but it lives here together with the other TLS
+    /// helpers.
+    pub fn tls_with_root_ca(pem: &[u8]) -> anyhow::Result<rustls::ClientConfig> {
+        let mut roots = rustls::RootCertStore::empty();
+
+        let certificates = rustls_pemfile::certs(&mut &*pem).collect::<Result<Vec<_>, _>>()?;
+        if certificates.is_empty() {
+            anyhow::bail!("no certificates found in pem");
+        }
+
+        for certificate in certificates {
+            roots.add(certificate)?;
+        }
+
+        Ok(rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth())
+    }
+
+    /// Get the SHA-256 fingerprint of the first certificate in the PEM, to be used with
+    /// [`tls_with_pinned_certificate`].
+    pub fn pem_fingerprint(pem: &[u8]) -> anyhow::Result<[u8; 32]> {
+        let certificate = rustls_pemfile::certs(&mut &*pem)
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no certificates found in pem"))??;
+
+        let mut fingerprint = [0; 32];
+        fingerprint
+            .copy_from_slice(ring::digest::digest(&ring::digest::SHA256, &certificate).as_ref());
+
+        Ok(fingerprint)
+    }
+
+    fn signature_algorithms() -> rustls::crypto::WebPkiSupportedAlgorithms {
+        rustls::crypto::ring::default_provider().signature_verification_algorithms
+    }
+
+    fn verify_tls12_signature(
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &signature_algorithms())
+    }
+
+    fn verify_tls13_signature(
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &signature_algorithms())
+    }
 
-        tls
+    fn supported_verify_schemes() -> Vec<rustls::SignatureScheme> {
+        signature_algorithms().supported_schemes()
     }
 }
@@ -0,0 +1,102 @@
+//! Hooks to measure the requests sent to the hub. Register a [`MetricsSink`] with
+//! [`Hub::with_metrics_sink`](crate::hub::Hub::with_metrics_sink) and it's called with the
+//! latency, endpoint and [`Outcome`] of every request, so the numbers can be passed on to
+//! whatever metrics library the application already uses:
+//!
+//! ```no_run
+//! #[derive(Debug)]
+//! struct Printer;
+//!
+//! impl dirigera::metrics::MetricsSink for Printer {
+//!     fn record(&self, request: &dirigera::metrics::RequestMetrics) {
+//!         let route = request.route();
+//!         println!("{} {route} {:?} {:?}", request.method, request.latency, request.outcome);
+//!     }
+//! }
+//! ```
+//!
+//! Each attempt of a request that is retried is recorded on its own. It's not available on WASM.
+
+/// How a request to the hub ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The hub responded with a status, successful or not.
+    Status(http::StatusCode),
+    /// No response was read within the timeout.
+    TimedOut,
+    /// The request couldn't be sent or the response couldn't be read.
+    Failed,
+}
+
+impl Outcome {
+    /// Check if the hub responded with a successful status.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Status(status) if status.is_success())
+    }
+}
+
+/// A single request sent to the hub.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestMetrics<'a> {
+    pub method: &'a http::Method,
+    /// The path of the request, f.ex. `/devices/abc123_1`. Use [`RequestMetrics::route`] for a
+    /// label without ids.
+    pub endpoint: &'a str,
+    /// The time from sending the request until the whole response was read, or until the status
+    /// was received for responses that are streamed, f.ex. by
+    /// [`Hub::devices_stream`](crate::hub::Hub::devices_stream).
+    pub latency: std::time::Duration,
+    pub outcome: Outcome,
+}
+
+impl<'a> RequestMetrics<'a> {
+    pub(crate) fn new(
+        method: &'a http::Method,
+        endpoint: &'a str,
+        latency: std::time::Duration,
+        result: &anyhow::Result<http::Response<hyper::Body>>,
+    ) -> Self {
+        let outcome = match result {
+            Ok(response) => Outcome::Status(response.status()),
+            Err(err) => match err.downcast_ref::<crate::error::ConnectionError>() {
+                Some(crate::error::ConnectionError::TimedOut { .. }) => Outcome::TimedOut,
+                _ => Outcome::Failed,
+            },
+        };
+
+        Self {
+            method,
+            endpoint,
+            latency,
+            outcome,
+        }
+    }
+
+    /// Get the endpoint with ids replaced by `{id}`, f.ex. `/devices/{id}`, to not create one
+    /// metric per device or scene.
+    pub fn route(&self) -> String {
+        let mut previous = "";
+
+        self.endpoint
+            .split('/')
+            .map(|segment| {
+                let is_id = matches!(previous, "devices" | "scenes" | "rooms" | "device-set")
+                    && !segment.is_empty();
+                previous = segment;
+
+                if is_id {
+                    "{id}"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// A [`MetricsSink`] is called for every request sent by the [`Hub`](crate::hub::Hub). Recording
+/// must not block for long since it's done before the response is returned to the caller.
+pub trait MetricsSink: std::fmt::Debug + Send + Sync {
+    fn record(&self, request: &RequestMetrics);
+}
@@ -0,0 +1,226 @@
+//! The [`Notifier`] sends selected events as JSON to webhooks, making it easy to get notified via
+//! services such as ntfy, Slack or Discord when a door is opened, a water leak is detected or a
+//! battery is running low. Each webhook is configured with the [`Notification`]s it should
+//! receive. The body posted to the webhook looks like this:
+//!
+//! ```json
+//! {
+//!   "notification": { "type": "batteryLow", "threshold": 20 },
+//!   "deviceId": "abc123_1",
+//!   "time": "2023-11-16T20:07:09.000Z",
+//!   "data": { "id": "abc123_1", "attributes": { "batteryPercentage": 15 } }
+//! }
+//! ```
+//!
+//! It's only available behind the `notifier` feature flag.
+use serde::Serialize;
+
+/// A [`Notification`] describes what events a webhook should be notified about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Notification {
+    /// An open/close sensor reported that it was opened after being closed, or for the first
+    /// time.
+    DoorOpened,
+    /// An open/close sensor reported that it was closed after being open, or for the first time.
+    DoorClosed,
+    /// A water sensor detected a leak.
+    WaterLeakDetected,
+    /// A device reported a battery percentage below the threshold. Each device is only reported
+    /// once until its battery is back above the threshold, like with
+    /// [`BatteryMonitor`](crate::report::BatteryMonitor).
+    BatteryLow { threshold: u8 },
+    /// Any event with the given type, f.ex. `sceneTriggered`.
+    #[serde(rename_all = "camelCase")]
+    Event { event_type: String },
+}
+
+/// The last reported state of a device, used to only notify when it changes.
+#[derive(Debug, Clone, Copy, Default)]
+struct DeviceState {
+    is_open: Option<bool>,
+    battery_percentage: Option<u64>,
+}
+
+impl DeviceState {
+    fn from_event(event: &crate::events::HubEvent) -> Self {
+        Self {
+            is_open: attribute(event, "isOpen").and_then(serde_json::Value::as_bool),
+            battery_percentage: attribute(event, "batteryPercentage")
+                .and_then(serde_json::Value::as_u64),
+        }
+    }
+}
+
+fn attribute<'a>(event: &'a crate::events::HubEvent, name: &str) -> Option<&'a serde_json::Value> {
+    event
+        .data
+        .get("attributes")
+        .and_then(|attributes| attributes.get(name))
+}
+
+impl Notification {
+    /// Check if the event should be notified, given the `previous` state of the device and the
+    /// `current` state reported by the event.
+    fn matches(
+        &self,
+        event: &crate::events::HubEvent,
+        previous: DeviceState,
+        current: DeviceState,
+    ) -> bool {
+        match self {
+            Self::DoorOpened => current.is_open == Some(true) && previous.is_open != Some(true),
+            Self::DoorClosed => current.is_open == Some(false) && previous.is_open != Some(false),
+            Self::WaterLeakDetected => {
+                attribute(event, "waterLeakDetected").and_then(serde_json::Value::as_bool)
+                    == Some(true)
+            }
+            Self::BatteryLow { threshold } => {
+                let is_low = |percentage: u64| percentage < u64::from(*threshold);
+
+                current.battery_percentage.is_some_and(is_low)
+                    && !previous.battery_percentage.is_some_and(is_low)
+            }
+            Self::Event { event_type } => &event.event_type == event_type,
+        }
+    }
+}
+
+/// The body posted to a webhook.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Payload<'a> {
+    notification: &'a Notification,
+    device_id: Option<&'a str>,
+    time: chrono::DateTime<chrono::Utc>,
+    data: &'a serde_json::Value,
+}
+
+/// A webhook URL together with the [`Notification`]s it should receive.
+#[derive(Debug, Clone)]
+struct Webhook {
+    url: hyper::Uri,
+    notifications: Vec<Notification>,
+}
+
+/// A [`Notifier`] holds all webhooks and the client used to call them. Unlike the hub the
+/// webhooks are called with TLS verification.
+#[derive(Debug)]
+pub struct Notifier {
+    client: hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>,
+    webhooks: Vec<Webhook>,
+    devices: std::sync::Mutex<std::collections::HashMap<String, DeviceState>>,
+}
+
+impl Notifier {
+    /// Create a new [`Notifier`] without any webhooks. Fails if the system's root certificates
+    /// can't be loaded.
+    pub fn new() -> anyhow::Result<Self> {
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()?
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        Ok(Self {
+            client: hyper::Client::builder().build(https),
+            webhooks: Vec::new(),
+            devices: Default::default(),
+        })
+    }
+
+    /// Add a webhook that will receive the given [`Notification`]s.
+    pub fn add_webhook(
+        &mut self,
+        url: &str,
+        notifications: Vec<Notification>,
+    ) -> anyhow::Result<()> {
+        self.webhooks.push(Webhook {
+            url: url.parse()?,
+            notifications,
+        });
+
+        Ok(())
+    }
+
+    /// Send the [`Event`](crate::events::Event) to all webhooks with a matching
+    /// [`Notification`]. `on_sent` is called once for every notification sent with the result of
+    /// calling the webhook.
+    pub async fn notify<F>(&self, event: &crate::events::Event, mut on_sent: F)
+    where
+        F: FnMut(&Notification, anyhow::Result<()>),
+    {
+        let crate::events::Event::Hub(event) = event else {
+            return;
+        };
+
+        let (previous, current) = self.update_device(event);
+
+        for webhook in &self.webhooks {
+            for notification in webhook
+                .notifications
+                .iter()
+                .filter(|n| n.matches(event, previous, current))
+            {
+                let payload = Payload {
+                    notification,
+                    device_id: event.device_id(),
+                    time: event.time,
+                    data: &event.data,
+                };
+
+                on_sent(notification, self.post(&webhook.url, &payload).await);
+            }
+        }
+    }
+
+    /// Send notifications for every event from the
+    /// [`EventListener`](crate::events::EventListener) until it stops. See [`Notifier::notify`].
+    pub async fn run<F>(&self, listener: &mut crate::events::EventListener, mut on_sent: F)
+    where
+        F: FnMut(&Notification, anyhow::Result<()>),
+    {
+        while let Some(event) = listener.next().await {
+            self.notify(&event, &mut on_sent).await;
+        }
+    }
+
+    /// Remember the state reported by the event and return the previous state of the device
+    /// together with the reported one. Attributes not reported by the event keep their previous
+    /// value for the next event.
+    fn update_device(&self, event: &crate::events::HubEvent) -> (DeviceState, DeviceState) {
+        let Some(device_id) = event.device_id() else {
+            return (DeviceState::default(), DeviceState::from_event(event));
+        };
+
+        let mut devices = self
+            .devices
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let previous = devices.get(device_id).copied().unwrap_or_default();
+        let reported = DeviceState::from_event(event);
+        let current = DeviceState {
+            is_open: reported.is_open.or(previous.is_open),
+            battery_percentage: reported.battery_percentage.or(previous.battery_percentage),
+        };
+
+        devices.insert(device_id.to_string(), current);
+
+        (previous, reported)
+    }
+
+    async fn post(&self, url: &hyper::Uri, payload: &Payload<'_>) -> anyhow::Result<()> {
+        let request = http::Request::builder()
+            .method(http::Method::POST)
+            .uri(url)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(hyper::Body::from(serde_json::to_vec(payload)?))?;
+
+        let response = self.client.request(request).await?;
+        if !response.status().is_success() {
+            anyhow::bail!("webhook {url} responded with {}", response.status());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+//! Tell which rooms are occupied from the motion sensors placed in them. The
+//! [`OccupancyTracker`] is fed with the `isDetected` state from polled
+//! [`Device`](crate::Device)s or events and keeps a room occupied while any sensor in it detects
+//! motion and for the decay window after the last motion stopped, so a room doesn't turn vacant
+//! just because someone sat still for a moment. Each time a room turns occupied or vacant an
+//! [`OccupancyChange`] is reported. With the `events` feature flag the tracker can also be turned
+//! into a [`Stream`](futures_util::Stream) with [`OccupancyTracker::watch`].
+use std::collections::{HashMap, HashSet};
+
+/// A change in occupancy for a [`Room`](crate::device::Room). `since` is when the room turned
+/// occupied or vacant, which for vacant rooms is when the decay window ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OccupancyChange {
+    RoomOccupied {
+        room_id: String,
+        since: chrono::DateTime<chrono::Utc>,
+    },
+    RoomVacant {
+        room_id: String,
+        since: chrono::DateTime<chrono::Utc>,
+    },
+}
+
+impl OccupancyChange {
+    /// Get the id of the room that changed.
+    pub fn room_id(&self) -> &str {
+        match self {
+            Self::RoomOccupied { room_id, .. } | Self::RoomVacant { room_id, .. } => room_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    reported: bool,
+    detecting: HashSet<String>,
+    last_motion: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// An [`OccupancyTracker`] holds the motion seen in each room.
+#[derive(Debug, Clone)]
+pub struct OccupancyTracker {
+    decay: chrono::Duration,
+    sensor_rooms: HashMap<String, String>,
+    states: HashMap<String, State>,
+}
+
+impl OccupancyTracker {
+    /// Create a new [`OccupancyTracker`] that keeps rooms occupied for `decay` after the last
+    /// motion.
+    pub fn new(decay: std::time::Duration) -> Self {
+        Self {
+            decay: chrono::Duration::from_std(decay).unwrap_or(chrono::Duration::MAX),
+            sensor_rooms: HashMap::new(),
+            states: HashMap::new(),
+        }
+    }
+
+    /// Observe if the motion sensor with the given id, placed in the room with the given id,
+    /// detects motion at a point in time.
+    pub fn observe_motion(
+        &mut self,
+        sensor_id: &str,
+        room_id: &str,
+        is_detected: bool,
+        at: chrono::DateTime<chrono::Utc>,
+    ) {
+        // The sensor may have been moved to another room since it was last seen.
+        if let Some(previous_room) = self
+            .sensor_rooms
+            .insert(sensor_id.to_string(), room_id.to_string())
+            .filter(|previous_room| previous_room != room_id)
+        {
+            if let Some(state) = self.states.get_mut(&previous_room) {
+                state.detecting.remove(sensor_id);
+            }
+        }
+
+        let state = self.states.entry(room_id.to_string()).or_default();
+        let was_detecting = state.detecting.contains(sensor_id);
+
+        if is_detected {
+            state.detecting.insert(sensor_id.to_string());
+            state.last_motion = Some(at);
+        } else if was_detecting {
+            // The decay window starts when the motion stops.
+            state.detecting.remove(sensor_id);
+            state.last_motion = Some(at);
+        }
+    }
+
+    /// Observe a polled [`Device`](crate::Device). Devices that aren't motion sensors or aren't
+    /// placed in a room are ignored.
+    pub fn observe_device(&mut self, device: &crate::Device) {
+        let inner = device.inner();
+
+        let (Some(is_detected), Some(room)) = (inner.attributes.is_detected, &inner.room) else {
+            return;
+        };
+
+        self.observe_motion(&inner.id, &room.id, is_detected, chrono::Utc::now());
+    }
+
+    /// Observe the motion from a device event. The room is read from the event or, if it's not
+    /// included, from an earlier observation of the same sensor. Events without motion are
+    /// ignored.
+    #[cfg(feature = "events")]
+    pub fn observe_event(&mut self, event: &crate::events::Event) {
+        let crate::events::Event::Hub(event) = event else {
+            return;
+        };
+
+        let (Some(sensor_id), Some(is_detected)) =
+            (event.device_id(), event.attribute_bool("isDetected"))
+        else {
+            return;
+        };
+
+        let Some(room_id) = event
+            .room_id()
+            .map(str::to_string)
+            .or_else(|| self.sensor_rooms.get(sensor_id).cloned())
+        else {
+            return;
+        };
+
+        self.observe_motion(sensor_id, &room_id, is_detected, event.time);
+    }
+
+    /// Check if the room with the given id is occupied at `now`. Rooms without any observed
+    /// motion are never occupied.
+    pub fn is_room_occupied(&self, room_id: &str, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.states
+            .get(room_id)
+            .is_some_and(|state| self.is_occupied(state, now))
+    }
+
+    /// Get all rooms that turned occupied or vacant since the last call at `now`, sorted by room
+    /// id. Each change is only returned once.
+    pub fn changes(&mut self, now: chrono::DateTime<chrono::Utc>) -> Vec<OccupancyChange> {
+        let mut changes = Vec::new();
+
+        for (room_id, state) in &self.states {
+            let occupied = self.is_occupied(state, now);
+            if occupied == state.reported {
+                continue;
+            }
+
+            let room_id = room_id.clone();
+            let last_motion = state.last_motion.unwrap_or(now);
+
+            changes.push(if occupied {
+                OccupancyChange::RoomOccupied {
+                    room_id,
+                    since: last_motion,
+                }
+            } else {
+                OccupancyChange::RoomVacant {
+                    room_id,
+                    since: last_motion
+                        .checked_add_signed(self.decay)
+                        .unwrap_or(now)
+                        .min(now),
+                }
+            });
+        }
+
+        changes.sort_by(|a, b| a.room_id().cmp(b.room_id()));
+
+        for change in &changes {
+            if let Some(state) = self.states.get_mut(change.room_id()) {
+                state.reported = !state.reported;
+            }
+        }
+
+        changes
+    }
+
+    fn is_occupied(&self, state: &State, now: chrono::DateTime<chrono::Utc>) -> bool {
+        !state.detecting.is_empty()
+            || state
+                .last_motion
+                .is_some_and(|last_motion| now - last_motion < self.decay)
+    }
+
+    /// Turn the tracker into a [`Stream`](futures_util::Stream) of changes. Devices are polled
+    /// from the [`Hub`](crate::hub::Hub) every `poll_interval`, but at most once a second, and
+    /// motion is also updated from events in between polls. Must be called within a [`tokio`]
+    /// runtime.
+    #[cfg(feature = "events")]
+    pub fn watch(
+        self,
+        hub: crate::hub::Hub,
+        poll_interval: std::time::Duration,
+    ) -> impl futures_util::Stream<Item = OccupancyChange> {
+        let listener = hub.events();
+        let poll = tokio::time::interval(poll_interval.max(std::time::Duration::from_secs(1)));
+        let check = tokio::time::interval(std::time::Duration::from_secs(1));
+
+        let state = (
+            self,
+            hub,
+            listener,
+            poll,
+            check,
+            std::collections::VecDeque::new(),
+        );
+
+        futures_util::stream::unfold(state, |mut state| async move {
+            let (tracker, hub, listener, poll, check, pending) = &mut state;
+
+            loop {
+                if let Some(change) = pending.pop_front() {
+                    return Some((change, state));
+                }
+
+                tokio::select! {
+                    _ = poll.tick() => {
+                        for device in hub.devices().await.unwrap_or_default() {
+                            tracker.observe_device(&device);
+                        }
+                    }
+                    _ = check.tick() => {}
+                    event = listener.next() => match event {
+                        Some(crate::events::Event::Resync) => poll.reset_immediately(),
+                        Some(event) => tracker.observe_event(&event),
+                        None => return None,
+                    },
+                }
+
+                pending.extend(tracker.changes(chrono::Utc::now()));
+            }
+        })
+    }
+}
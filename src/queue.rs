@@ -0,0 +1,242 @@
+//! A [`CommandQueue`] sends [`Command`]s to the hub in the background and keeps them while the hub
+//! is unreachable instead of failing right away. Commands that couldn't be sent are retried until
+//! the hub answers again or until they've waited longer than the window, and the final result of
+//! each command is reported through its [`PendingCommand`]:
+//!
+//! ```no_run
+//! # async fn example(hub: dirigera::hub::Hub) -> anyhow::Result<()> {
+//! let queue = dirigera::queue::CommandQueue::new(hub)
+//!     .with_window(std::time::Duration::from_secs(120))
+//!     .spawn();
+//!
+//! let pending = queue.submit("abc123_1", dirigera::command::Command::TurnOn)?;
+//! pending.result().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only commands that give the same result if they're sent more than once are queued, so
+//! [`Command::Toggle`] is rejected. A newer command for the same attribute of a device replaces
+//! one that is still waiting, f.ex. a new light level while the hub is offline. It's only
+//! available behind the `queue` feature flag.
+use crate::command::Command;
+
+use std::collections::VecDeque;
+
+/// How long a command is kept while the hub is unreachable if not set with
+/// [`CommandQueue::with_window`].
+pub const DEFAULT_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How often the hub is tried again while it's unreachable if not set with
+/// [`CommandQueue::with_retry_interval`].
+pub const DEFAULT_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// A [`CommandQueue`] is configured and then started with [`CommandQueue::spawn`].
+#[derive(Debug, Clone)]
+pub struct CommandQueue {
+    hub: crate::hub::Hub,
+    window: std::time::Duration,
+    retry_interval: std::time::Duration,
+}
+
+/// A handle to submit commands to a running [`CommandQueue`]. It's cheap to clone and the queue
+/// keeps running until all handles are dropped and every command has been sent or expired.
+#[derive(Debug, Clone)]
+pub struct QueueHandle {
+    sender: tokio::sync::mpsc::UnboundedSender<Queued>,
+}
+
+/// A submitted command waiting for its final result.
+#[derive(Debug)]
+pub struct PendingCommand {
+    receiver: tokio::sync::oneshot::Receiver<anyhow::Result<()>>,
+}
+
+#[derive(Debug)]
+struct Queued {
+    device_id: crate::DeviceId,
+    command: Command,
+    queued_at: tokio::time::Instant,
+    last_error: Option<String>,
+    replies: Vec<tokio::sync::oneshot::Sender<anyhow::Result<()>>>,
+}
+
+impl CommandQueue {
+    /// Create a new [`CommandQueue`] sending commands to the [`Hub`](crate::hub::Hub).
+    pub fn new(hub: crate::hub::Hub) -> Self {
+        Self {
+            hub,
+            window: DEFAULT_WINDOW,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+        }
+    }
+
+    /// Set how long a command is kept while the hub is unreachable before it fails.
+    pub fn with_window(mut self, window: std::time::Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set how often the hub is tried again while it's unreachable. A zero interval is treated as
+    /// one millisecond since the interval can't be zero.
+    pub fn with_retry_interval(mut self, retry_interval: std::time::Duration) -> Self {
+        self.retry_interval = retry_interval.max(std::time::Duration::from_millis(1));
+        self
+    }
+
+    /// Spawn a task sending the commands and return a [`QueueHandle`] to submit them with. Must be
+    /// called within a [`tokio`] runtime.
+    pub fn spawn(self) -> QueueHandle {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(self.run(receiver));
+
+        QueueHandle { sender }
+    }
+
+    async fn run(self, mut receiver: tokio::sync::mpsc::UnboundedReceiver<Queued>) {
+        let mut pending = VecDeque::new();
+        let mut open = true;
+
+        let mut retry = tokio::time::interval(self.retry_interval);
+        retry.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                queued = receiver.recv(), if open => match queued {
+                    Some(queued) => enqueue(&mut pending, queued),
+                    None => open = false,
+                },
+                _ = retry.tick(), if !pending.is_empty() => {}
+            }
+
+            self.flush(&mut pending).await;
+
+            if !open && pending.is_empty() {
+                return;
+            }
+        }
+    }
+
+    /// Send the commands in the order they were submitted until the hub is unreachable. Commands
+    /// that have waited longer than the window fail with the last error.
+    async fn flush(&self, pending: &mut VecDeque<Queued>) {
+        let mut hub = self.hub.clone();
+
+        while let Some(queued) = pending.front_mut() {
+            if queued.queued_at.elapsed() > self.window {
+                let queued = pending.pop_front().expect("front exists");
+                let error = anyhow::anyhow!(
+                    "gave up after {:?} since the hub was unreachable: {}",
+                    self.window,
+                    queued.last_error.as_deref().unwrap_or("no response")
+                );
+
+                queued.reply(Err(error));
+                continue;
+            }
+
+            let result = async {
+                let mut device = hub.device(&queued.device_id).await?;
+                hub.execute(&mut device, &queued.command).await
+            }
+            .await;
+
+            match result {
+                Err(err) if is_unreachable(&err) => {
+                    queued.last_error = Some(format!("{err:#}"));
+                    return;
+                }
+                result => pending.pop_front().expect("front exists").reply(result),
+            }
+        }
+    }
+}
+
+impl QueueHandle {
+    /// Submit a [`Command`] for the [`Device`](crate::Device) with the given id. It's sent right
+    /// away if the hub is reachable. Fails if the command isn't idempotent, i.e.
+    /// [`Command::Toggle`], or if the queue has stopped.
+    pub fn submit(
+        &self,
+        device_id: impl Into<crate::DeviceId>,
+        command: Command,
+    ) -> anyhow::Result<PendingCommand> {
+        if command == Command::Toggle {
+            anyhow::bail!("toggle gives a different result if it's sent twice and can't be queued");
+        }
+
+        let (reply, receiver) = tokio::sync::oneshot::channel();
+
+        self.sender
+            .send(Queued {
+                device_id: device_id.into(),
+                command,
+                queued_at: tokio::time::Instant::now(),
+                last_error: None,
+                replies: vec![reply],
+            })
+            .map_err(|_| anyhow::anyhow!("the command queue has stopped"))?;
+
+        Ok(PendingCommand { receiver })
+    }
+}
+
+impl PendingCommand {
+    /// Wait for the command to be sent to the hub or to give up. A command that was replaced by a
+    /// newer one gets the result of the newer command.
+    pub async fn result(self) -> anyhow::Result<()> {
+        self.receiver
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("the command queue has stopped")))
+    }
+}
+
+impl Queued {
+    fn reply(mut self, result: anyhow::Result<()>) {
+        let newest = self.replies.pop();
+
+        // The error can't be cloned so replaced commands get a copy of the message.
+        for reply in self.replies {
+            let _ = reply.send(match &result {
+                Ok(()) => Ok(()),
+                Err(err) => Err(anyhow::anyhow!("{err:#}")),
+            });
+        }
+
+        if let Some(reply) = newest {
+            let _ = reply.send(result);
+        }
+    }
+}
+
+/// Add the command to the queue, replacing a waiting command for the same attribute of the same
+/// device.
+fn enqueue(pending: &mut VecDeque<Queued>, queued: Queued) {
+    let changes = attribute(&queued.command);
+
+    match pending.iter_mut().find(|waiting| {
+        waiting.device_id == queued.device_id && attribute(&waiting.command) == changes
+    }) {
+        Some(waiting) => {
+            waiting.command = queued.command;
+            waiting.queued_at = queued.queued_at;
+            waiting.replies.extend(queued.replies);
+        }
+        None => pending.push_back(queued),
+    }
+}
+
+/// The attribute a [`Command`] changes, commands changing the same attribute replace each other.
+fn attribute(command: &Command) -> &'static str {
+    match command {
+        Command::TurnOn | Command::TurnOff | Command::Toggle => "isOn",
+        Command::SetLightLevel(_) => "lightLevel",
+        Command::SetColorTemperature(_) => "colorTemperature",
+        Command::SetBlindsLevel(_) => "blindsTargetLevel",
+    }
+}
+
+fn is_unreachable(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| cause.is::<crate::error::ConnectionError>())
+}
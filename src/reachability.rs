@@ -0,0 +1,179 @@
+//! Keep track of when devices go offline and come back online. The [`ReachabilityMonitor`] is fed
+//! with the `is_reachable` state from polled [`Device`](crate::Device)s or events and reports a
+//! [`ReachabilityChange`] once a new state has been stable for the debounce period, so a flaky
+//! device that drops off for a few seconds doesn't cause a storm of notifications. With the
+//! `events` feature flag the monitor can also be turned into a [`Stream`](futures_util::Stream)
+//! with [`ReachabilityMonitor::watch`].
+use std::collections::HashMap;
+
+/// A change in reachability for a [`Device`](crate::Device). `since` is when the new state was
+/// first observed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReachabilityChange {
+    DeviceWentOffline {
+        device_id: String,
+        since: chrono::DateTime<chrono::Utc>,
+    },
+    DeviceCameOnline {
+        device_id: String,
+        since: chrono::DateTime<chrono::Utc>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    reported: bool,
+    observed: bool,
+    observed_since: chrono::DateTime<chrono::Utc>,
+}
+
+/// A [`ReachabilityMonitor`] holds the last known reachability for each device.
+#[derive(Debug, Clone)]
+pub struct ReachabilityMonitor {
+    debounce: chrono::Duration,
+    states: HashMap<String, State>,
+}
+
+impl ReachabilityMonitor {
+    /// Create a new [`ReachabilityMonitor`] that reports changes once they've been stable for
+    /// `debounce`.
+    pub fn new(debounce: std::time::Duration) -> Self {
+        Self {
+            debounce: chrono::Duration::from_std(debounce).unwrap_or(chrono::Duration::MAX),
+            states: HashMap::new(),
+        }
+    }
+
+    /// Observe the reachability of a device at a point in time. The first observation of a
+    /// device is never reported as a change.
+    pub fn observe(
+        &mut self,
+        device_id: &str,
+        is_reachable: bool,
+        at: chrono::DateTime<chrono::Utc>,
+    ) {
+        match self.states.get_mut(device_id) {
+            Some(state) if state.observed != is_reachable => {
+                state.observed = is_reachable;
+                state.observed_since = at;
+            }
+            Some(_) => {}
+            None => {
+                self.states.insert(
+                    device_id.to_string(),
+                    State {
+                        reported: is_reachable,
+                        observed: is_reachable,
+                        observed_since: at,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Observe the reachability of a polled [`Device`](crate::Device).
+    pub fn observe_device(&mut self, device: &crate::Device) {
+        let inner = device.inner();
+        self.observe(&inner.id, inner.is_reachable, chrono::Utc::now());
+    }
+
+    /// Observe the reachability from a device event. Events without reachability are ignored.
+    #[cfg(feature = "events")]
+    pub fn observe_event(&mut self, event: &crate::events::Event) {
+        let crate::events::Event::Hub(event) = event else {
+            return;
+        };
+
+        let (Some(device_id), Some(is_reachable)) = (
+            event.device_id(),
+            event
+                .data
+                .get("isReachable")
+                .and_then(serde_json::Value::as_bool),
+        ) else {
+            return;
+        };
+
+        self.observe(device_id, is_reachable, event.time);
+    }
+
+    /// Get all changes that has been stable for the debounce period at `now`. Each change is only
+    /// returned once.
+    pub fn changes(&mut self, now: chrono::DateTime<chrono::Utc>) -> Vec<ReachabilityChange> {
+        let mut changes = Vec::new();
+
+        for (device_id, state) in &mut self.states {
+            if state.reported == state.observed || now - state.observed_since < self.debounce {
+                continue;
+            }
+
+            state.reported = state.observed;
+
+            let device_id = device_id.clone();
+            let since = state.observed_since;
+
+            changes.push(if state.observed {
+                ReachabilityChange::DeviceCameOnline { device_id, since }
+            } else {
+                ReachabilityChange::DeviceWentOffline { device_id, since }
+            });
+        }
+
+        changes
+    }
+
+    /// Turn the monitor into a [`Stream`](futures_util::Stream) of changes. Devices are polled
+    /// from the [`Hub`](crate::hub::Hub) every `poll_interval`, but at most once a second, and
+    /// reachability is also updated from events in between polls. Must be called within a
+    /// [`tokio`] runtime.
+    #[cfg(feature = "events")]
+    pub fn watch(
+        self,
+        hub: crate::hub::Hub,
+        poll_interval: std::time::Duration,
+    ) -> impl futures_util::Stream<Item = ReachabilityChange> {
+        let listener = hub.events();
+        let poll = tokio::time::interval(poll_interval.max(std::time::Duration::from_secs(1)));
+        let check = tokio::time::interval(
+            self.debounce
+                .to_std()
+                .unwrap_or_default()
+                .max(std::time::Duration::from_secs(1)),
+        );
+
+        let state = (
+            self,
+            hub,
+            listener,
+            poll,
+            check,
+            std::collections::VecDeque::new(),
+        );
+
+        futures_util::stream::unfold(state, |mut state| async move {
+            let (monitor, hub, listener, poll, check, pending) = &mut state;
+
+            loop {
+                if let Some(change) = pending.pop_front() {
+                    return Some((change, state));
+                }
+
+                tokio::select! {
+                    _ = poll.tick() => {
+                        for device in hub.devices().await.unwrap_or_default() {
+                            monitor.observe_device(&device);
+                        }
+                    }
+                    _ = check.tick() => {}
+                    event = listener.next() => match event {
+                        Some(crate::events::Event::Resync) => poll.reset_immediately(),
+                        Some(event) => monitor.observe_event(&event),
+                        None => return None,
+                    },
+                }
+
+                pending.extend(monitor.changes(chrono::Utc::now()));
+            }
+        })
+    }
+}
@@ -0,0 +1,111 @@
+//! Reload the [`Config`](crate::hub::Config) of a long running daemon without restarting it. The
+//! [`ConfigWatcher`] checks the `toml` file for changes and replaces the IP address and token of
+//! a live [`Hub`](crate::hub::Hub), and all its clones, with
+//! [`Hub::set_credentials`](crate::hub::Hub::set_credentials) whenever the file is changed. Each
+//! reload is reported as a [`ConfigEvent`]. It's only available behind the `reload` feature flag.
+
+/// How often the file is checked for changes if not set with [`ConfigWatcher::with_interval`].
+pub const DEFAULT_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// What happened when the configuration file changed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigEvent {
+    /// The file was read and the credentials of the [`Hub`](crate::hub::Hub) were replaced.
+    Reloaded {
+        ip_address: std::net::Ipv4Addr,
+        ip_address_changed: bool,
+        token_changed: bool,
+    },
+    /// The file changed but couldn't be read or parsed. The [`Hub`](crate::hub::Hub) keeps the
+    /// credentials it had.
+    Failed { error: String },
+}
+
+/// A [`ConfigWatcher`] keeps the credentials of a [`Hub`](crate::hub::Hub) in sync with a
+/// configuration file.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    hub: crate::hub::Hub,
+    path: std::path::PathBuf,
+    interval: std::time::Duration,
+}
+
+impl ConfigWatcher {
+    /// Create a new [`ConfigWatcher`] for the [`Hub`](crate::hub::Hub) reading the file at
+    /// `path`, f.ex. [`Config::DEFAULT_PATH`](crate::hub::Config::DEFAULT_PATH).
+    pub fn new(hub: crate::hub::Hub, path: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            hub,
+            path: path.into(),
+            interval: DEFAULT_CHECK_INTERVAL,
+        }
+    }
+
+    /// Set how often the file is checked for changes. A zero interval is treated as one
+    /// millisecond since the interval can't be zero.
+    pub fn with_interval(mut self, interval: std::time::Duration) -> Self {
+        self.interval = interval.max(std::time::Duration::from_millis(1));
+        self
+    }
+
+    /// Turn the watcher into a [`Stream`](futures_util::Stream) of [`ConfigEvent`]s. The file is
+    /// checked every interval and reloaded when its modification time changes. Saving the file
+    /// without changing the content isn't reported. The stream never ends and must be polled for
+    /// the credentials to be updated. Must be called within a [`tokio`] runtime.
+    pub fn watch(self) -> impl futures_util::Stream<Item = ConfigEvent> {
+        let modified = modified_time(&self.path);
+        let ticker = tokio::time::interval(self.interval);
+
+        futures_util::stream::unfold(
+            (self, ticker, modified),
+            |(watcher, mut ticker, mut last_modified)| async move {
+                loop {
+                    ticker.tick().await;
+
+                    let modified = modified_time(&watcher.path);
+                    if modified == last_modified {
+                        continue;
+                    }
+
+                    last_modified = modified;
+
+                    if let Some(event) = watcher.reload() {
+                        return Some((event, (watcher, ticker, last_modified)));
+                    }
+                }
+            },
+        )
+    }
+
+    fn reload(&self) -> Option<ConfigEvent> {
+        let config = match crate::hub::Config::from_path(&self.path) {
+            Ok(config) => config,
+            Err(err) => {
+                return Some(ConfigEvent::Failed {
+                    error: err.to_string(),
+                })
+            }
+        };
+
+        let ip_address_changed = config.ip_address != self.hub.ip_address();
+        let token_changed = !self.hub.has_token(&config.token);
+
+        if !ip_address_changed && !token_changed {
+            return None;
+        }
+
+        self.hub.set_credentials(config.ip_address, config.token);
+
+        Some(ConfigEvent::Reloaded {
+            ip_address: config.ip_address,
+            ip_address_changed,
+            token_changed,
+        })
+    }
+}
+
+fn modified_time(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,163 @@
+//! Reports aggregate attributes from all devices into a summary so you don't need to know which
+//! attributes live on which type of [`Device`](crate::Device). See f.ex.
+//! [`Hub::battery_report`](crate::hub::Hub::battery_report).
+
+/// The battery level for a single battery powered [`Device`](crate::Device).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryStatus {
+    pub device_id: crate::DeviceId,
+    pub custom_name: String,
+    pub room: Option<String>,
+    pub percentage: u8,
+}
+
+impl BatteryStatus {
+    /// Get the [`BatteryStatus`] for a [`Device`](crate::Device), if it has a battery.
+    pub fn from_device(device: &crate::Device) -> Option<Self> {
+        let inner = device.inner();
+        let percentage = u8::try_from(inner.attributes.battery_percentage?).ok()?;
+
+        Some(Self {
+            device_id: inner.id.clone(),
+            custom_name: inner.attributes.custom_name.clone(),
+            room: inner.room.as_ref().map(|room| room.name.clone()),
+            percentage,
+        })
+    }
+}
+
+/// The air quality in a single room, aggregated from all devices in the room that report it, such
+/// as environment sensors and air purifiers. The worst PM2.5 and VOC index and the average
+/// temperature and humidity is used when there are multiple devices. Devices that aren't placed
+/// in a room are reported with no room.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomAirQuality {
+    pub room_id: Option<crate::RoomId>,
+    pub room: Option<String>,
+    /// The PM2.5 concentration in µg/m³.
+    pub pm25: Option<u16>,
+    /// The VOC index between 1 and 500, where 100 is the average of the last 24 hours.
+    pub voc_index: Option<u16>,
+    pub temperature: Option<crate::units::Celsius>,
+    pub humidity: Option<crate::units::Percent>,
+    /// The ids of the devices the values are gathered from.
+    pub devices: Vec<crate::DeviceId>,
+}
+
+impl RoomAirQuality {
+    /// Aggregate the air quality for each room from a list of [`Device`](crate::Device)s, sorted
+    /// by room name. Devices without any air quality attributes are ignored.
+    pub fn from_devices(devices: &[crate::Device]) -> Vec<Self> {
+        let mut rooms: Vec<Self> = Vec::new();
+        let mut temperatures: Vec<Vec<f32>> = Vec::new();
+        let mut humidities: Vec<Vec<u8>> = Vec::new();
+
+        for device in devices {
+            let inner = device.inner();
+            let attributes = &inner.attributes;
+
+            if attributes.current_p_m25.is_none()
+                && attributes.voc_index.is_none()
+                && attributes.current_temperature.is_none()
+                && attributes.current_r_h.is_none()
+            {
+                continue;
+            }
+
+            let room_id = inner.room.as_ref().map(|room| room.id.clone());
+            let index = match rooms.iter().position(|room| room.room_id == room_id) {
+                Some(index) => index,
+                None => {
+                    rooms.push(Self {
+                        room_id,
+                        room: inner.room.as_ref().map(|room| room.name.clone()),
+                        pm25: None,
+                        voc_index: None,
+                        temperature: None,
+                        humidity: None,
+                        devices: Vec::new(),
+                    });
+                    temperatures.push(Vec::new());
+                    humidities.push(Vec::new());
+
+                    rooms.len() - 1
+                }
+            };
+
+            let room = &mut rooms[index];
+            room.pm25 = room.pm25.max(attributes.current_p_m25);
+            room.voc_index = room.voc_index.max(attributes.voc_index);
+            room.devices.push(inner.id.clone());
+
+            temperatures[index].extend(attributes.current_temperature.map(|t| t.0));
+            humidities[index].extend(attributes.current_r_h.map(|h| h.0));
+        }
+
+        for ((room, temperatures), humidities) in rooms.iter_mut().zip(temperatures).zip(humidities)
+        {
+            if !temperatures.is_empty() {
+                room.temperature = Some(crate::units::Celsius(
+                    temperatures.iter().sum::<f32>() / temperatures.len() as f32,
+                ));
+            }
+
+            if !humidities.is_empty() {
+                let sum = humidities.iter().map(|&h| u32::from(h)).sum::<u32>();
+                room.humidity = Some(crate::units::Percent(
+                    (sum as f32 / humidities.len() as f32).round() as u8,
+                ));
+            }
+        }
+
+        rooms.sort_by(|a, b| a.room.cmp(&b.room));
+
+        rooms
+    }
+}
+
+/// A [`BatteryMonitor`] checks device events and reports when a battery drops below the
+/// threshold. Each device is only reported once until its battery is back above the threshold,
+/// f.ex. after the battery has been replaced. It's only available behind the `events` feature
+/// flag.
+#[cfg(feature = "events")]
+#[derive(Debug, Clone)]
+pub struct BatteryMonitor {
+    threshold: u8,
+    low: std::collections::HashSet<String>,
+}
+
+#[cfg(feature = "events")]
+impl BatteryMonitor {
+    /// Create a new [`BatteryMonitor`] reporting batteries below `threshold` percent.
+    pub fn new(threshold: u8) -> Self {
+        Self {
+            threshold,
+            low: Default::default(),
+        }
+    }
+
+    /// Check an [`Event`](crate::events::Event). Returns the device id and battery percentage if
+    /// the event made the battery of a device drop below the threshold.
+    pub fn check(&mut self, event: &crate::events::Event) -> Option<(String, u8)> {
+        let crate::events::Event::Hub(event) = event else {
+            return None;
+        };
+
+        let device_id = event.device_id()?;
+        let percentage = event
+            .data
+            .get("attributes")?
+            .get("batteryPercentage")?
+            .as_u64()
+            .and_then(|percentage| u8::try_from(percentage).ok())?;
+
+        if percentage >= self.threshold {
+            self.low.remove(device_id);
+            return None;
+        }
+
+        self.low
+            .insert(device_id.to_string())
+            .then(|| (device_id.to_string(), percentage))
+    }
+}
@@ -1,49 +1,116 @@
 //! With the IKEA Home Smart app you can configure scenes that can be either triggered manually or
 //! on a schedule. Scenes are specific configuration for a set of devices such as color
 //! temperature, light level, blind level etcetera.
-use crate::{deserialize_datetime, deserialize_datetime_optional};
-use serde::Deserialize;
+use crate::units::{Kelvin, Percent};
+use crate::{
+    deserialize_datetime, deserialize_datetime_optional, deserialize_duration_seconds,
+    deserialize_time, serialize_duration_seconds, serialize_time,
+};
+#[cfg(feature = "chrono")]
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
 
-/// A [`Scene`] is represented by its `type` and will hold all the [`SceneData`].
-#[derive(Debug, Deserialize)]
+use std::collections::{BTreeSet, HashMap};
+
+/// A [`Scene`] is represented by its `type` and will hold all the [`SceneData`]. Two [`Scene`]s
+/// are equal if they have the same id.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum Scene {
     UserScene(SceneData),
 }
 
 /// Specific data for a scene such as what actions it will do and what [`Trigger`]s it has.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct SceneData {
-    pub id: String,
+    pub id: crate::SceneId,
     pub info: Info,
     #[serde(alias = "type")]
     pub scene_type: Option<String>,
     pub actions: Vec<Action>,
-    pub commands: Vec<String>,
+    pub commands: Vec<SceneCommand>,
     pub triggers: Vec<Trigger>,
-    pub undo_allowed_duration: u8,
+    #[serde(
+        deserialize_with = "deserialize_duration_seconds",
+        serialize_with = "serialize_duration_seconds"
+    )]
+    #[cfg_attr(feature = "schemars", schemars(with = "i64"))]
+    pub undo_allowed_duration: crate::TimeDelta,
     #[serde(deserialize_with = "deserialize_datetime")]
-    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: crate::Timestamp,
     #[serde(default, deserialize_with = "deserialize_datetime_optional")]
-    pub last_completed: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_completed: Option<crate::Timestamp>,
     #[serde(default, deserialize_with = "deserialize_datetime_optional")]
-    pub last_triggered: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_triggered: Option<crate::Timestamp>,
     #[serde(default, deserialize_with = "deserialize_datetime_optional")]
-    pub last_undo: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_undo: Option<crate::Timestamp>,
 }
 
 /// Each scene has a name and icon which is represented under the scene [`Info`].
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Info {
     pub name: String,
-    pub icon: String,
+    pub icon: SceneIcon,
+}
+
+crate::string_enum! {
+    /// The icon of a [`Scene`] as shown in the app. The app will only render known icons so
+    /// scenes created through the API should use one of [`SceneIcon::KNOWN`]. Icons not known by
+    /// this crate are kept as [`SceneIcon::Unknown`].
+    pub enum SceneIcon {
+        ArriveHome => "scenes_arrive_home",
+        Bed => "scenes_bed",
+        Book => "scenes_book",
+        Cleaning => "scenes_clean_sparkles",
+        Cooking => "scenes_cooking",
+        Heart => "scenes_heart",
+        LeaveHome => "scenes_leave_home",
+        Party => "scenes_party",
+        Relax => "scenes_relax",
+        Snowflake => "scenes_snowflake",
+        Sunrise => "scenes_sun_horizon",
+        Trophy => "scenes_trophy",
+        Tv => "scenes_tv",
+        WakeUp => "scenes_wake_up",
+    }
+}
+
+/// A [`SceneCommand`] is something a [`Scene`] does besides setting device attributes with its
+/// [`Action`]s. The hub usually sends commands as plain names which are kept as
+/// [`SceneCommand::Named`]. Commands sent as objects are kept as is in [`SceneCommand::Payload`]
+/// since their shape isn't known, use [`SceneCommand::kind`] to see what kind of command it is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum SceneCommand {
+    Named(String),
+    Payload(serde_json::Value),
+}
+
+impl SceneCommand {
+    /// Get the kind of the command, which is the name for a [`SceneCommand::Named`] and the
+    /// `type` or `command` field, if any, for a [`SceneCommand::Payload`].
+    pub fn kind(&self) -> Option<&str> {
+        match self {
+            Self::Named(name) => Some(name),
+            Self::Payload(payload) => ["type", "command"]
+                .iter()
+                .find_map(|key| payload.get(key)?.as_str()),
+        }
+    }
 }
 
 /// A scene can be triggered from the app (or API), based on sunrise or sunset or on a specific
 /// time.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase", tag = "type")]
 pub enum Trigger {
     App(AppTrigger),
@@ -51,45 +118,63 @@ pub enum Trigger {
     Time(TimeTrigger),
 }
 
+impl Trigger {
+    /// Get the id of the [`Trigger`].
+    pub fn id(&self) -> &str {
+        match self {
+            Self::App(trigger) => &trigger.id,
+            Self::SunriseSunset(trigger) => &trigger.id,
+            Self::Time(trigger) => &trigger.id,
+        }
+    }
+}
+
 /// Events triggered from the app shows the state and when it was triggered.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct AppTrigger {
     pub id: String,
     pub disabled: bool,
     #[serde(default, deserialize_with = "deserialize_datetime_optional")]
-    pub triggered_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub triggered_at: Option<crate::Timestamp>,
 }
 
 /// Events triggered on time will show when the next trigger will happen and what [`EndTrigger`] the
 /// schedule has.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct TimeTrigger {
     pub id: String,
     pub disabled: bool,
     #[serde(default, deserialize_with = "deserialize_datetime")]
-    pub next_trigger_at: chrono::DateTime<chrono::Utc>,
+    pub next_trigger_at: crate::Timestamp,
     pub trigger: Time,
     pub end_trigger_event: EndTrigger,
 }
 
 /// Sunrise and sunset events will sync with the user's location and the response will show when
 /// the next trigger will happen and what [`EndTrigger`] the schedule has.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct SunriseSunsetTrigger {
     pub id: String,
     pub disabled: bool,
     #[serde(default, deserialize_with = "deserialize_datetime")]
-    pub next_trigger_at: chrono::DateTime<chrono::Utc>,
+    pub next_trigger_at: crate::Timestamp,
     pub trigger: Follow,
     pub end_trigger_event: EndTrigger,
 }
 
 /// An [`EndTrigger`] is something that will trigger the scene to end. It can be based on a
 /// duration, sunrise or sunset or a specific time.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase", tag = "type", content = "trigger")]
 pub enum EndTrigger {
     Duration(Duration),
@@ -97,60 +182,371 @@ pub enum EndTrigger {
     Time(Time),
 }
 
-/// Duration is just number of seconds from the trigger start.
-#[derive(Debug, Deserialize)]
+/// Duration is the time from the trigger start, sent by the hub as number of seconds.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Duration {
-    pub duration: u32,
+    #[serde(
+        deserialize_with = "deserialize_duration_seconds",
+        serialize_with = "serialize_duration_seconds"
+    )]
+    #[cfg_attr(feature = "schemars", schemars(with = "i64"))]
+    pub duration: crate::TimeDelta,
 }
 
 /// Sunrise and sunset shows what days to trigger for sunrise or sunset if specific days and any
 /// offset from the sunrise or sunset time.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase", tag = "type")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub enum Follow {
     Sunrise {
-        days: Option<Vec<String>>,
+        days: Option<BTreeSet<Weekday>>,
         offset: i32,
     },
     Sunset {
-        days: Option<Vec<String>>,
+        days: Option<BTreeSet<Weekday>>,
         offset: i32,
     },
 }
 
-/// Time shows what days to trigger for the specific time and what time that is.
-#[derive(Debug, Deserialize)]
+/// Time shows what days to trigger for the specific time and what time that is. If no days are
+/// set the schedule applies to every day of the week.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct Time {
-    pub days: Option<Vec<String>>,
-    pub time: String,
+    pub days: Option<BTreeSet<Weekday>>,
+    #[serde(
+        deserialize_with = "deserialize_time",
+        serialize_with = "serialize_time"
+    )]
+    pub time: crate::TimeOfDay,
+}
+
+/// When a scheduled [`Trigger`] should fire, used to change the schedule of a [`Scene`] with
+/// [`Hub::set_scene_schedule`](crate::hub::Hub::set_scene_schedule). The offset is the same as
+/// for [`Follow`].
+#[derive(Debug, Clone)]
+pub enum ScheduleTime {
+    At(crate::TimeOfDay),
+    Sunrise { offset: i32 },
+    Sunset { offset: i32 },
+}
+
+impl ScheduleTime {
+    /// Get the `type` and `trigger` of a [`Trigger`] firing at this time on the given days.
+    pub(crate) fn into_trigger(
+        self,
+        days: Option<BTreeSet<Weekday>>,
+    ) -> serde_json::Result<(&'static str, serde_json::Value)> {
+        Ok(match self {
+            Self::At(time) => ("time", serde_json::to_value(Time { days, time })?),
+            Self::Sunrise { offset } => (
+                "sunriseSunset",
+                serde_json::to_value(Follow::Sunrise { days, offset })?,
+            ),
+            Self::Sunset { offset } => (
+                "sunriseSunset",
+                serde_json::to_value(Follow::Sunset { days, offset })?,
+            ),
+        })
+    }
+}
+
+/// The days of the week as they're represented by the hub in schedules.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::Weekday> for Weekday {
+    fn from(weekday: chrono::Weekday) -> Self {
+        match weekday {
+            chrono::Weekday::Mon => Self::Mon,
+            chrono::Weekday::Tue => Self::Tue,
+            chrono::Weekday::Wed => Self::Wed,
+            chrono::Weekday::Thu => Self::Thu,
+            chrono::Weekday::Fri => Self::Fri,
+            chrono::Weekday::Sat => Self::Sat,
+            chrono::Weekday::Sun => Self::Sun,
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<Weekday> for chrono::Weekday {
+    fn from(weekday: Weekday) -> Self {
+        match weekday {
+            Weekday::Mon => Self::Mon,
+            Weekday::Tue => Self::Tue,
+            Weekday::Wed => Self::Wed,
+            Weekday::Thu => Self::Thu,
+            Weekday::Fri => Self::Fri,
+            Weekday::Sat => Self::Sat,
+            Weekday::Sun => Self::Sun,
+        }
+    }
 }
 
 /// A scene has a type to target for its action.
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase", tag = "type")]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", tag = "type", try_from = "serde_json::Value")]
 pub enum Action {
     Device(ActionData),
+    DeviceSet(DeviceSetActionData),
+    /// An action of a type not known by this crate, kept as sent by the hub. Actions of a known
+    /// type that can't be parsed fail instead of ending up here.
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
+}
+
+impl TryFrom<serde_json::Value> for Action {
+    type Error = serde_json::Error;
+
+    fn try_from(raw: serde_json::Value) -> Result<Self, Self::Error> {
+        let action_type = match raw.get("type").and_then(serde_json::Value::as_str) {
+            Some(action_type @ ("device" | "deviceSet")) => action_type.to_string(),
+            Some(_) => return Ok(Self::Unknown(raw)),
+            None => return Err(serde::de::Error::missing_field("type")),
+        };
+
+        // The tag isn't a field of the data, which matters in strict mode.
+        let mut data = raw;
+        if let Some(data) = data.as_object_mut() {
+            data.remove("type");
+        }
+
+        if action_type == "device" {
+            ActionData::deserialize(data).map(Self::Device)
+        } else {
+            DeviceSetActionData::deserialize(data).map(Self::DeviceSet)
+        }
+    }
 }
 
 /// Data for the action type which holds the [`Device`](crate::Device) id and attribute for the [`Scene`].
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct ActionData {
     pub id: String,
-    pub device_id: String,
+    pub device_id: crate::DeviceId,
+    pub attributes: SceneAttributes,
+}
+
+/// Data for an action targeting all [`Device`](crate::Device)s in a
+/// [`DeviceSet`](crate::device::DeviceSet) at once.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct DeviceSetActionData {
+    pub id: String,
+    pub device_set_id: String,
     pub attributes: SceneAttributes,
 }
 
 /// Attributes to the scene which shows information about on or off state and light level and color
-/// temperature for [`Device`](crate::Device)s that support those.
-#[derive(Debug, Deserialize)]
+/// temperature for [`Device`](crate::Device)s that support those. Attributes that are not modeled
+/// are kept in [`SceneAttributes::extra`].
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct SceneAttributes {
     pub is_on: bool,
-    pub light_level: Option<u8>,
-    pub color_temperature: Option<u16>,
+    pub light_level: Option<Percent>,
+    pub color_temperature: Option<Kelvin>,
+    #[cfg_attr(not(feature = "strict"), serde(flatten))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl PartialEq for Scene {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner().id == other.inner().id
+    }
+}
+
+impl Eq for Scene {}
+
+impl std::hash::Hash for Scene {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.inner().id.hash(state);
+    }
+}
+
+impl SceneData {
+    /// Check if any of the [`Action`]s targets the [`Device`](crate::Device), either directly or
+    /// through a [`DeviceSet`](crate::device::DeviceSet) the device is in.
+    pub fn targets_device(&self, device: &crate::Device) -> bool {
+        let device = device.inner();
+
+        self.actions.iter().any(|action| match action {
+            Action::Device(action) => action.device_id == device.id,
+            Action::DeviceSet(action) => device
+                .device_set
+                .iter()
+                .any(|set| set.id == action.device_set_id),
+            Action::Unknown(_) => false,
+        })
+    }
+}
+
+impl SceneAttributes {
+    /// Check if the [`Attributes`](crate::device::Attributes) of a device matches what the scene
+    /// sets. Light level and color temperature are only compared if the device is supposed to be
+    /// on.
+    pub fn is_applied_to(&self, attributes: &crate::device::Attributes) -> bool {
+        if attributes.is_on.is_some_and(|is_on| is_on != self.is_on) {
+            return false;
+        }
+
+        if !self.is_on {
+            return true;
+        }
+
+        self.light_level
+            .is_none_or(|level| attributes.light_level.is_none_or(|l| l == level))
+            && self.color_temperature.is_none_or(|temperature| {
+                attributes
+                    .color_temperature
+                    .is_none_or(|t| t == temperature)
+            })
+    }
+}
+
+/// The result of triggering a [`Scene`] and waiting for it to complete with
+/// [`Hub::trigger_scene_and_wait`](crate::hub::Hub::trigger_scene_and_wait).
+#[derive(Debug)]
+pub struct SceneCompletion {
+    /// The [`Scene`] as it looked after it completed.
+    pub scene: Scene,
+    pub triggered_at: Option<crate::Timestamp>,
+    pub completed_at: crate::Timestamp,
+    /// The outcome of each [`Action`] in the scene, one for each device in the set for
+    /// [`Action::DeviceSet`]. Unknown actions are skipped.
+    pub actions: Vec<ActionOutcome>,
+}
+
+/// The outcome of a single [`Action`]. The hub doesn't report if an action succeeded so this is
+/// based on the state of the [`Device`](crate::Device) after the scene completed. `applied` is
+/// [`None`] if the device couldn't be found or isn't reachable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionOutcome {
+    pub device_id: crate::DeviceId,
+    pub applied: Option<bool>,
+}
+
+/// The attributes a [`Device`](crate::Device) is predicted to have after a [`Scene`] has been
+/// triggered, see [`Scene::simulate`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredictedAttributes {
+    pub is_on: bool,
+    pub light_level: Option<Percent>,
+    pub color_temperature: Option<Kelvin>,
+}
+
+impl PredictedAttributes {
+    /// Apply the [`SceneAttributes`] to the [`Device`](crate::Device). Light level and color
+    /// temperature are only changed if the device supports them, everything else is kept from
+    /// the current state.
+    fn apply(&mut self, device: &crate::Device, attributes: &SceneAttributes) {
+        use crate::device::Capability;
+
+        self.is_on = attributes.is_on;
+
+        if let Some(level) = attributes
+            .light_level
+            .filter(|_| device.has_capability(Capability::LightLevel))
+        {
+            self.light_level = Some(level);
+        }
+
+        if let Some(temperature) = attributes
+            .color_temperature
+            .filter(|_| device.has_capability(Capability::ColorTemperature))
+        {
+            self.color_temperature = Some(temperature);
+        }
+    }
+}
+
+impl From<&crate::Device> for PredictedAttributes {
+    fn from(device: &crate::Device) -> Self {
+        let attributes = &device.inner().attributes;
+
+        Self {
+            is_on: attributes.is_on.unwrap_or_default(),
+            light_level: attributes.light_level,
+            color_temperature: attributes.color_temperature,
+        }
+    }
+}
+
+impl Time {
+    /// Check if the schedule is active on the given weekday.
+    pub fn is_active_on(&self, weekday: Weekday) -> bool {
+        self.days
+            .as_ref()
+            .map(|days| days.is_empty() || days.contains(&weekday))
+            .unwrap_or(true)
+    }
+
+    /// Get the next point in time after `after` when this schedule will trigger. The time of day
+    /// is interpreted in the timezone of `after` so pass a timestamp in the hub's timezone to get
+    /// the same result as the hub. Returns [`None`] if no matching time exists within a week, f.ex.
+    /// if the time is skipped due to daylight saving time.
+    #[cfg(feature = "chrono")]
+    pub fn next_occurrence<Tz: chrono::TimeZone>(
+        &self,
+        after: &chrono::DateTime<Tz>,
+    ) -> Option<chrono::DateTime<Tz>> {
+        let timezone = after.timezone();
+        let today = after.date_naive();
+
+        (0..=7)
+            .map(|offset| today + chrono::Duration::days(offset))
+            .filter(|date| self.is_active_on(date.weekday().into()))
+            .filter_map(|date| {
+                timezone
+                    .from_local_datetime(&date.and_time(self.time))
+                    .earliest()
+            })
+            .find(|candidate| candidate > after)
+    }
+}
+
+impl TimeTrigger {
+    /// Get the next point in time after `after` when the trigger will fire. See
+    /// [`Time::next_occurrence`] for details about timezones. A disabled trigger will never fire
+    /// and always returns [`None`].
+    #[cfg(feature = "chrono")]
+    pub fn next_occurrence<Tz: chrono::TimeZone>(
+        &self,
+        after: &chrono::DateTime<Tz>,
+    ) -> Option<chrono::DateTime<Tz>> {
+        if self.disabled {
+            return None;
+        }
+
+        self.trigger.next_occurrence(after)
+    }
 }
 
 impl Scene {
@@ -160,4 +556,54 @@ impl Scene {
             Scene::UserScene(inner) => inner,
         }
     }
+
+    /// Compute the state each [`Device`](crate::Device) would have if the scene was triggered
+    /// now, without talking to the hub. The [`Action`]s are applied in order to the devices in
+    /// the [`Home`](crate::home::Home) snapshot, so a device targeted by several actions gets the
+    /// attributes of the last one. [`Action::DeviceSet`] applies to every device in the set.
+    /// Devices that aren't in the snapshot and unknown actions are skipped.
+    pub fn simulate(
+        &self,
+        home: &crate::home::Home,
+    ) -> Vec<(crate::DeviceId, PredictedAttributes)> {
+        let mut predicted: Vec<(crate::DeviceId, PredictedAttributes)> = Vec::new();
+
+        for action in &self.inner().actions {
+            let (devices, attributes): (Vec<&crate::Device>, _) = match action {
+                Action::Device(action) => (
+                    home.device(&action.device_id).into_iter().collect(),
+                    &action.attributes,
+                ),
+                Action::DeviceSet(action) => (
+                    home.devices
+                        .iter()
+                        .filter(|device| {
+                            device
+                                .inner()
+                                .device_set
+                                .iter()
+                                .any(|set| set.id == action.device_set_id)
+                        })
+                        .collect(),
+                    &action.attributes,
+                ),
+                Action::Unknown(_) => continue,
+            };
+
+            for device in devices {
+                let id = &device.inner().id;
+
+                match predicted.iter_mut().find(|(device_id, _)| device_id == id) {
+                    Some((_, state)) => state.apply(device, attributes),
+                    None => {
+                        let mut state = PredictedAttributes::from(device);
+                        state.apply(device, attributes);
+                        predicted.push((id.clone(), state));
+                    }
+                }
+            }
+        }
+
+        predicted
+    }
 }
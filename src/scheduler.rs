@@ -0,0 +1,282 @@
+//! The [`Scheduler`] runs [`Command`]s against the hub at a later time, either once or every day.
+//! This is useful for actions the scenes in the hub can't express, f.ex. "set light level to 1 at
+//! 22:30 every day" or "turn off the outlet in 2 hours". Pending jobs can be persisted to a file so
+//! they survive a restart. Jobs that should have run while the scheduler wasn't running will run
+//! once it's started again if they're one-off jobs, daily jobs will wait for their next
+//! occurrence. It's only available behind the `scheduler` feature flag.
+use crate::command::Command;
+use crate::scene::Weekday;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// A [`Job`] is a [`Command`] to execute on a [`Device`](crate::Device) according to a
+/// [`Schedule`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: u64,
+    pub device_id: String,
+    pub command: Command,
+    pub schedule: Schedule,
+    pub last_run: Option<chrono::DateTime<chrono::Utc>>,
+    /// When the job was added. Daily jobs only run for occurrences after this.
+    #[serde(default)]
+    pub added_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A [`Schedule`] is either a single point in time or a time of day, in the local timezone, that
+/// repeats every day or on specific days. If no days are set a daily schedule runs every day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Schedule {
+    Once {
+        at: chrono::DateTime<chrono::Utc>,
+    },
+    Daily {
+        at: chrono::NaiveTime,
+        days: Option<BTreeSet<Weekday>>,
+    },
+}
+
+impl Schedule {
+    /// Run once after the given duration from now.
+    pub fn after(duration: std::time::Duration) -> anyhow::Result<Self> {
+        Ok(Self::Once {
+            at: chrono::Utc::now() + chrono::Duration::from_std(duration)?,
+        })
+    }
+
+    /// Run every day at the given time of day.
+    pub fn daily(at: chrono::NaiveTime) -> Self {
+        Self::Daily { at, days: None }
+    }
+}
+
+impl Job {
+    /// Get the next time the job should run. Daily jobs only run for occurrences after
+    /// `baseline`, when the job was added and when it last ran, whichever is latest.
+    fn next_run(
+        &self,
+        baseline: chrono::DateTime<chrono::Local>,
+    ) -> Option<chrono::DateTime<chrono::Local>> {
+        match &self.schedule {
+            Schedule::Once { at } => self
+                .last_run
+                .is_none()
+                .then(|| at.with_timezone(&chrono::Local)),
+            Schedule::Daily { at, days } => {
+                let after = [self.added_at, self.last_run]
+                    .into_iter()
+                    .flatten()
+                    .map(|at| at.with_timezone(&chrono::Local))
+                    .fold(baseline, std::cmp::max);
+
+                crate::scene::Time {
+                    days: days.clone(),
+                    time: *at,
+                }
+                .next_occurrence(&after)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    jobs: Vec<Job>,
+    next_id: u64,
+    path: Option<std::path::PathBuf>,
+    /// Incremented every time the jobs change, to know if the jobs changed while they were
+    /// persisted without holding the lock.
+    changes: u64,
+}
+
+impl State {
+    fn persist(&mut self) -> anyhow::Result<()> {
+        self.changes += 1;
+
+        if let Some(path) = &self.path {
+            std::fs::write(path, serde_json::to_vec_pretty(&self.jobs)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A [`Scheduler`] holds all pending [`Job`]s. It's cheap to clone and all clones share the same
+/// jobs so jobs can be added and cancelled from other tasks while [`Scheduler::run`] is running.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    state: Arc<Mutex<State>>,
+    changed: Arc<tokio::sync::Notify>,
+}
+
+impl Scheduler {
+    /// Create a new [`Scheduler`] that only keeps jobs in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new [`Scheduler`] that persists all jobs to the file at `path`. If the file
+    /// exists the jobs in it are loaded.
+    pub fn open(path: impl Into<std::path::PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let jobs: Vec<Job> = if path.exists() {
+            serde_json::from_slice(&std::fs::read(&path)?)?
+        } else {
+            Vec::new()
+        };
+
+        let next_id = jobs.iter().map(|job| job.id + 1).max().unwrap_or_default();
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(State {
+                jobs,
+                next_id,
+                path: Some(path),
+                changes: 0,
+            })),
+            changed: Default::default(),
+        })
+    }
+
+    /// Add a new [`Job`] executing the [`Command`] on the [`Device`](crate::Device) with the given
+    /// id according to the [`Schedule`]. Returns the id of the job.
+    pub fn add(
+        &self,
+        device_id: impl Into<String>,
+        command: Command,
+        schedule: Schedule,
+    ) -> anyhow::Result<u64> {
+        let mut state = self.state();
+        let id = state.next_id;
+
+        state.next_id += 1;
+        state.jobs.push(Job {
+            id,
+            device_id: device_id.into(),
+            command,
+            schedule,
+            last_run: None,
+            added_at: Some(chrono::Utc::now()),
+        });
+        state.persist()?;
+        self.changed.notify_one();
+
+        Ok(id)
+    }
+
+    /// Cancel the [`Job`] with the given id. Returns `false` if there was no such job.
+    pub fn cancel(&self, id: u64) -> anyhow::Result<bool> {
+        let mut state = self.state();
+        let count = state.jobs.len();
+
+        state.jobs.retain(|job| job.id != id);
+        if state.jobs.len() == count {
+            return Ok(false);
+        }
+
+        state.persist()?;
+        self.changed.notify_one();
+
+        Ok(true)
+    }
+
+    /// Get a copy of all pending [`Job`]s.
+    pub fn jobs(&self) -> Vec<Job> {
+        self.state().jobs.clone()
+    }
+
+    /// Run all jobs forever. Every time a [`Job`] has been executed `on_executed` is called with
+    /// the result. One-off jobs are removed after they've run, even if they failed. Returns an
+    /// error if the jobs can't be persisted.
+    pub async fn run<F>(&self, hub: &mut crate::hub::Hub, mut on_executed: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&Job, anyhow::Result<()>),
+    {
+        let baseline = chrono::Local::now();
+
+        loop {
+            let next = self
+                .state()
+                .jobs
+                .iter()
+                .filter_map(|job| job.next_run(baseline))
+                .min();
+
+            let Some(next) = next else {
+                self.changed.notified().await;
+                continue;
+            };
+
+            let wait = (next - chrono::Local::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {},
+                _ = self.changed.notified() => continue,
+            }
+
+            let now = chrono::Local::now();
+            let due: Vec<Job> = self
+                .state()
+                .jobs
+                .iter()
+                .filter(|job| job.next_run(baseline).is_some_and(|at| at <= now))
+                .cloned()
+                .collect();
+
+            for job in due {
+                let result = match hub.device(&job.device_id).await {
+                    Ok(mut device) => hub.execute(&mut device, &job.command).await,
+                    Err(err) => Err(err),
+                };
+
+                {
+                    let mut state = self.state();
+                    match job.schedule {
+                        Schedule::Once { .. } => state.jobs.retain(|j| j.id != job.id),
+                        Schedule::Daily { .. } => {
+                            if let Some(j) = state.jobs.iter_mut().find(|j| j.id == job.id) {
+                                j.last_run = Some(chrono::Utc::now());
+                            }
+                        }
+                    }
+
+                    state.changes += 1;
+                }
+
+                self.persist().await?;
+                on_executed(&job, result);
+            }
+        }
+    }
+
+    /// Persist the jobs from [`Scheduler::run`] without blocking the runtime. The lock isn't held
+    /// while writing so the jobs are written again if they changed in the meantime, otherwise an
+    /// older write could finish last.
+    async fn persist(&self) -> anyhow::Result<()> {
+        loop {
+            let (path, jobs, changes) = {
+                let state = self.state();
+                let Some(path) = state.path.clone() else {
+                    return Ok(());
+                };
+
+                (path, serde_json::to_vec_pretty(&state.jobs)?, state.changes)
+            };
+
+            tokio::fs::write(&path, jobs).await?;
+
+            if self.state().changes == changes {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Lock the jobs. Every change is completed while the lock is held so a poisoned lock still
+    /// holds usable jobs.
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,313 @@
+//! Small [`rhai`] scripts can be registered as event handlers to customize behavior without
+//! recompiling. Each script is run once for every [`Event`](crate::events::Event) with the event
+//! available as the variable `event`, a map with the keys `type`, `id`, `time` and `data`. After a
+//! reconnect the script runs with `type` set to `"resync"`.
+//!
+//! Scripts can't talk to the hub directly. Instead they have access to a small set of functions
+//! that queues commands which are sent to the hub once the script has finished:
+//!
+//! - `turn_on(device_id)` and `turn_off(device_id)`
+//! - `toggle(device_id)`
+//! - `set_light_level(device_id, level)`
+//! - `set_color_temperature(device_id, temperature)`
+//! - `set_blinds_level(device_id, level)`
+//! - `trigger_scene(scene_id)`
+//!
+//! ```rhai
+//! if event.type == "deviceStateChanged" && event.id == "motion-sensor-id" {
+//!     if event.data.attributes.isDetected == true {
+//!         turn_on("hallway-light-id");
+//!     }
+//! }
+//! ```
+//!
+//! It's only available behind the `scripting` feature flag.
+use crate::command::Command;
+use crate::units::{Kelvin, Percent};
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Maximum number of operations a single script run may perform before it's aborted.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// Something queued by a script, either a [`Command`] for a device or a scene to trigger.
+#[derive(Debug, Clone)]
+enum Queued {
+    Device(String, Command),
+    TriggerScene(String),
+}
+
+/// A [`ScriptHandler`] holds all registered scripts and the [`rhai::Engine`] used to run them.
+pub struct ScriptHandler {
+    engine: rhai::Engine,
+    scripts: Vec<(String, rhai::AST)>,
+    commands: Arc<Mutex<Vec<Queued>>>,
+}
+
+impl std::fmt::Debug for ScriptHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ScriptHandler")
+            .field(
+                "scripts",
+                &self
+                    .scripts
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Default for ScriptHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptHandler {
+    /// Create a new [`ScriptHandler`] without any scripts.
+    pub fn new() -> Self {
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = rhai::Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(32);
+
+        register(&mut engine, &commands, "turn_on", Command::TurnOn);
+        register(&mut engine, &commands, "turn_off", Command::TurnOff);
+        register(&mut engine, &commands, "toggle", Command::Toggle);
+
+        let queue = commands.clone();
+        engine.register_fn("trigger_scene", move |id: &str| {
+            push(&queue, Queued::TriggerScene(id.to_string()))
+        });
+
+        let queue = commands.clone();
+        engine.register_fn(
+            "set_light_level",
+            move |id: &str, level: i64| -> Result<(), Box<rhai::EvalAltResult>> {
+                let level = u8::try_from(level).map_err(|err| err.to_string())?;
+                push(
+                    &queue,
+                    Queued::Device(id.to_string(), Command::SetLightLevel(Percent(level))),
+                );
+                Ok(())
+            },
+        );
+
+        let queue = commands.clone();
+        engine.register_fn(
+            "set_color_temperature",
+            move |id: &str, temperature: i64| -> Result<(), Box<rhai::EvalAltResult>> {
+                let temperature = u16::try_from(temperature).map_err(|err| err.to_string())?;
+                push(
+                    &queue,
+                    Queued::Device(
+                        id.to_string(),
+                        Command::SetColorTemperature(Kelvin(temperature)),
+                    ),
+                );
+                Ok(())
+            },
+        );
+
+        let queue = commands.clone();
+        engine.register_fn(
+            "set_blinds_level",
+            move |id: &str, level: i64| -> Result<(), Box<rhai::EvalAltResult>> {
+                let level = u8::try_from(level).map_err(|err| err.to_string())?;
+                push(
+                    &queue,
+                    Queued::Device(id.to_string(), Command::SetBlindsLevel(Percent(level))),
+                );
+                Ok(())
+            },
+        );
+
+        Self {
+            engine,
+            scripts: Vec::new(),
+            commands,
+        }
+    }
+
+    /// Compile and register a script. The name is only used to identify the script when
+    /// reporting results.
+    pub fn register(&mut self, name: impl Into<String>, source: &str) -> anyhow::Result<()> {
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|err| anyhow::anyhow!(err))?;
+
+        self.scripts.push((name.into(), ast));
+
+        Ok(())
+    }
+
+    /// Compile and register a script from a file. The file name is used as the name of the
+    /// script.
+    pub fn register_file(&mut self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)?;
+
+        self.register(path.display().to_string(), &source)
+    }
+
+    /// Run all scripts for a single [`Event`](crate::events::Event) and send the queued commands
+    /// to the [`Hub`](crate::hub::Hub). `on_executed` is called once for every script with the
+    /// result of running it, including the result of its commands.
+    pub async fn handle<F>(
+        &mut self,
+        hub: &mut crate::hub::Hub,
+        event: &crate::events::Event,
+        mut on_executed: F,
+    ) where
+        F: FnMut(&str, anyhow::Result<()>),
+    {
+        let event = match event_to_dynamic(event) {
+            Ok(event) => event,
+            Err(err) => {
+                for (name, _) in &self.scripts {
+                    on_executed(name, Err(anyhow::anyhow!("{err}")));
+                }
+
+                return;
+            }
+        };
+
+        for (name, ast) in &self.scripts {
+            let mut scope = rhai::Scope::new();
+            scope.push_constant("event", event.clone());
+
+            let result = self
+                .engine
+                .run_ast_with_scope(&mut scope, ast)
+                .map_err(|err| anyhow::anyhow!("{err}"));
+
+            let commands = std::mem::take(&mut *lock(&self.commands));
+            let result = match result {
+                Ok(()) => execute(hub, commands).await,
+                Err(err) => Err(err),
+            };
+
+            on_executed(name, result);
+        }
+    }
+
+    /// Run all scripts for every event from the
+    /// [`EventListener`](crate::events::EventListener) until it stops. See
+    /// [`ScriptHandler::handle`].
+    pub async fn run<F>(
+        &mut self,
+        hub: &mut crate::hub::Hub,
+        listener: &mut crate::events::EventListener,
+        mut on_executed: F,
+    ) where
+        F: FnMut(&str, anyhow::Result<()>),
+    {
+        while let Some(event) = listener.next().await {
+            self.handle(hub, &event, &mut on_executed).await;
+        }
+    }
+}
+
+fn push(queue: &Mutex<Vec<Queued>>, queued: Queued) {
+    lock(queue).push(queued);
+}
+
+/// Lock the queued commands. Commands are only pushed and taken while the lock is held so a
+/// poisoned lock still holds a usable queue.
+fn lock(queue: &Mutex<Vec<Queued>>) -> MutexGuard<'_, Vec<Queued>> {
+    queue.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn register(
+    engine: &mut rhai::Engine,
+    commands: &Arc<Mutex<Vec<Queued>>>,
+    name: &str,
+    command: Command,
+) {
+    let queue = commands.clone();
+    engine.register_fn(name, move |id: &str| {
+        push(&queue, Queued::Device(id.to_string(), command.clone()))
+    });
+}
+
+fn event_to_dynamic(event: &crate::events::Event) -> anyhow::Result<rhai::Dynamic> {
+    let value = match event {
+        crate::events::Event::Hub(event) => serde_json::json!({
+            "type": event.event_type,
+            "id": event.id,
+            "time": event.time.to_rfc3339(),
+            "data": event.data,
+        }),
+        crate::events::Event::DoorOpened { device_id, time } => serde_json::json!({
+            "type": "doorOpened",
+            "id": device_id,
+            "time": time.to_rfc3339(),
+        }),
+        crate::events::Event::DoorClosed { device_id, time } => serde_json::json!({
+            "type": "doorClosed",
+            "id": device_id,
+            "time": time.to_rfc3339(),
+        }),
+        crate::events::Event::WaterLeakDetected { device_id, time } => serde_json::json!({
+            "type": "waterLeakDetected",
+            "id": device_id,
+            "time": time.to_rfc3339(),
+        }),
+        crate::events::Event::WaterLeakCleared { device_id, time } => serde_json::json!({
+            "type": "waterLeakCleared",
+            "id": device_id,
+            "time": time.to_rfc3339(),
+        }),
+        crate::events::Event::AttributesChanged {
+            device_id,
+            changes,
+            time,
+        } => serde_json::json!({
+            "type": "attributesChanged",
+            "id": device_id,
+            "time": time.to_rfc3339(),
+            "changes": changes,
+        }),
+        crate::events::Event::AddressChanged {
+            previous,
+            ip_address,
+            time,
+        } => serde_json::json!({
+            "type": "addressChanged",
+            "previous": previous,
+            "ipAddress": ip_address,
+            "time": time.to_rfc3339(),
+        }),
+        crate::events::Event::Resync => serde_json::json!({ "type": "resync" }),
+    };
+
+    rhai::serde::to_dynamic(value).map_err(|err| anyhow::anyhow!("{err}"))
+}
+
+/// Send all commands to the hub. All commands are sent even if one fails, the first error is
+/// returned.
+async fn execute(hub: &mut crate::hub::Hub, commands: Vec<Queued>) -> anyhow::Result<()> {
+    let mut result = Ok(());
+
+    for queued in commands {
+        let outcome = match queued {
+            Queued::Device(id, command) => match hub.device(&id).await {
+                Ok(mut device) => hub.execute(&mut device, &command).await,
+                Err(err) => Err(err),
+            },
+            Queued::TriggerScene(id) => match hub.scene(&id).await {
+                Ok(scene) => hub.trigger_scene(&scene).await,
+                Err(err) => Err(err),
+            },
+        };
+
+        if result.is_ok() {
+            result = outcome;
+        }
+    }
+
+    result
+}
@@ -0,0 +1,116 @@
+//! Integration with [`tower`](https://docs.rs/tower). The [`Hub`] implements
+//! [`Service<DirigeraRequest>`](tower_service::Service) so requests can be sent through standard
+//! middleware such as retries, timeouts, rate limiting or tracing. To also apply middleware to
+//! all requests made by the methods on the [`Hub`], wrap an HTTP service in a
+//! [`ServiceTransport`] and pass it to [`Hub::with_transport`]. It's only available behind the
+//! `tower` feature flag.
+use crate::hub::Hub;
+use crate::transport::{ResponseFuture, Transport};
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A [`DirigeraRequest`] is a request to a path of the hub API, f.ex. `/devices`. Authorization
+/// and the full URL is added by the [`Hub`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirigeraRequest {
+    pub method: http::Method,
+    pub path: String,
+    pub body: Option<serde_json::Value>,
+}
+
+impl DirigeraRequest {
+    /// Create a `GET` request to the given path.
+    pub fn get(path: impl Into<String>) -> Self {
+        Self {
+            method: http::Method::GET,
+            path: path.into(),
+            body: None,
+        }
+    }
+
+    /// Create a `PATCH` request to the given path with a JSON body.
+    pub fn patch(path: impl Into<String>, body: serde_json::Value) -> Self {
+        Self {
+            method: http::Method::PATCH,
+            path: path.into(),
+            body: Some(body),
+        }
+    }
+
+    /// Create a `POST` request to the given path with a JSON body.
+    pub fn post(path: impl Into<String>, body: serde_json::Value) -> Self {
+        Self {
+            method: http::Method::POST,
+            path: path.into(),
+            body: Some(body),
+        }
+    }
+}
+
+impl tower_service::Service<DirigeraRequest> for Hub {
+    type Response = http::Response<hyper::Body>;
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = anyhow::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Send the request to the hub. Unsuccessful responses are returned as an
+    /// [`ApiError`](crate::error::ApiError).
+    fn call(&mut self, request: DirigeraRequest) -> Self::Future {
+        let hub = self.clone();
+
+        Box::pin(async move {
+            let body = request
+                .body
+                .map(|body| serde_json::to_vec(&body))
+                .transpose()?
+                .map(hyper::Body::from);
+
+            hub.send(request.method, &request.path, body).await
+        })
+    }
+}
+
+/// A [`ServiceTransport`] uses any [`Service`](tower_service::Service) for HTTP requests as a
+/// [`Transport`]. The service is cloned for each request, as is common for tower services.
+#[derive(Debug, Clone)]
+pub struct ServiceTransport<S> {
+    service: S,
+}
+
+impl<S> ServiceTransport<S> {
+    /// Create a new [`ServiceTransport`] sending all requests through `service`.
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+}
+
+impl<S> Transport for ServiceTransport<S>
+where
+    S: tower_service::Service<http::Request<hyper::Body>, Response = http::Response<hyper::Body>>
+        + Clone
+        + std::fmt::Debug
+        + Send
+        + Sync,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    S::Future: Send,
+{
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        let mut service = self.service.clone();
+
+        Box::pin(async move {
+            futures_util::future::poll_fn(|cx| service.poll_ready(cx))
+                .await
+                .map_err(|err| anyhow::anyhow!(err.into()))?;
+
+            service
+                .call(request)
+                .await
+                .map_err(|err| anyhow::anyhow!(err.into()))
+        })
+    }
+}
@@ -0,0 +1,127 @@
+//! Calculate sunrise and sunset for a location. The hub (and the IKEA app) uses the location of the
+//! hub to schedule things around the sun so this is used to do the same thing client side. The
+//! calculation is based on the [sunrise equation](https://en.wikipedia.org/wiki/Sunrise_equation)
+//! and is accurate to within a minute or two which is more than enough for scheduling. The
+//! [`elevation`] of the sun is used to follow the daylight, f.ex. for adaptive lighting.
+
+/// Julian date for the epoch J2000.0.
+const J2000: f64 = 2_451_545.0;
+
+/// Julian date for the unix epoch.
+const UNIX_EPOCH_JULIAN: f64 = 2_440_587.5;
+
+/// The time of sunrise in UTC at the given location for the given date. Latitude and longitude
+/// are in degrees with north and east being positive. Returns [`None`] if the sun doesn't rise or
+/// set that day, f.ex. during polar night or midnight sun.
+pub fn sunrise(
+    date: chrono::NaiveDate,
+    latitude: f64,
+    longitude: f64,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    let (transit, hour_angle) = solar_transit(date, latitude, longitude)?;
+    from_julian(transit - hour_angle / 360.0)
+}
+
+/// The time of sunset in UTC at the given location for the given date. See [`sunrise`] for
+/// details.
+pub fn sunset(
+    date: chrono::NaiveDate,
+    latitude: f64,
+    longitude: f64,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    let (transit, hour_angle) = solar_transit(date, latitude, longitude)?;
+    from_julian(transit + hour_angle / 360.0)
+}
+
+/// The elevation of the sun in degrees above the horizon at the given location and time, negative
+/// when the sun is below the horizon. Latitude and longitude are in degrees with north and east
+/// being positive.
+pub fn elevation(at: chrono::DateTime<chrono::Utc>, latitude: f64, longitude: f64) -> f64 {
+    let days = at.timestamp() as f64 / 86_400.0 + UNIX_EPOCH_JULIAN - J2000;
+    let (declination, right_ascension) = equatorial_position(days);
+
+    let sidereal_time = (280.16 + 360.985_623_5 * days + longitude).to_radians();
+    let hour_angle = sidereal_time - right_ascension;
+    let latitude = latitude.to_radians();
+
+    (latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos())
+        .asin()
+        .to_degrees()
+}
+
+/// The highest elevation of the sun in degrees at the given location for the given date, reached
+/// at solar noon. Negative during polar night.
+pub fn max_elevation(date: chrono::NaiveDate, latitude: f64, longitude: f64) -> f64 {
+    let noon = date.and_hms_opt(12, 0, 0).unwrap_or_default().and_utc()
+        - chrono::Duration::seconds((longitude / 360.0 * 86_400.0) as i64);
+    let days = noon.timestamp() as f64 / 86_400.0 + UNIX_EPOCH_JULIAN - J2000;
+    let (declination, _) = equatorial_position(days);
+
+    90.0 - (latitude - declination.to_degrees()).abs()
+}
+
+/// Get the coordinates of the hub as latitude and longitude, used when no location is configured.
+#[cfg(any(feature = "adaptive", feature = "automation"))]
+pub(crate) async fn hub_coordinates(hub: &mut crate::hub::Hub) -> Option<(f64, f64)> {
+    hub.devices().await.ok()?.iter().find_map(|device| {
+        let coordinates = device.inner().attributes.extra.get("coordinates")?;
+
+        Some((
+            coordinates.get("latitude")?.as_f64()?,
+            coordinates.get("longitude")?.as_f64()?,
+        ))
+    })
+}
+
+/// Returns the declination and right ascension of the sun in radians, `days` after J2000.0.
+fn equatorial_position(days: f64) -> (f64, f64) {
+    let m = (357.5291 + 0.98560028 * days)
+        .rem_euclid(360.0)
+        .to_radians();
+    let center = 1.9148 * m.sin() + 0.0200 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_longitude = (m.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let obliquity = 23.44f64.to_radians();
+
+    let declination = (ecliptic_longitude.sin() * obliquity.sin()).asin();
+    let right_ascension =
+        (ecliptic_longitude.sin() * obliquity.cos()).atan2(ecliptic_longitude.cos());
+
+    (declination, right_ascension)
+}
+
+/// Returns the julian date of the solar noon together with the hour angle in degrees between
+/// solar noon and sunrise or sunset.
+fn solar_transit(date: chrono::NaiveDate, latitude: f64, longitude: f64) -> Option<(f64, f64)> {
+    let midnight = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp() as f64;
+    let julian_date = midnight / 86_400.0 + UNIX_EPOCH_JULIAN;
+    let day = (julian_date - J2000 + 0.0008).ceil();
+
+    let mean_solar_time = day - longitude / 360.0;
+    let mean_anomaly = (357.5291 + 0.98560028 * mean_solar_time).rem_euclid(360.0);
+    let m = mean_anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.0200 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_longitude = (mean_anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+
+    let transit =
+        J2000 + mean_solar_time + 0.0053 * m.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+    let declination = (ecliptic_longitude.sin() * 23.44f64.to_radians().sin()).asin();
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = ((-0.833f64).to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+
+    Some((transit, cos_hour_angle.acos().to_degrees()))
+}
+
+fn from_julian(julian_date: f64) -> Option<chrono::DateTime<chrono::Utc>> {
+    let seconds = (julian_date - UNIX_EPOCH_JULIAN) * 86_400.0;
+    chrono::DateTime::from_timestamp(seconds.round() as i64, 0)
+}
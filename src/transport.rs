@@ -0,0 +1,494 @@
+//! All requests to the hub goes through a [`Transport`]. By default this is a [`hyper`] client but
+//! since the [`Hub`](crate::hub::Hub) only depends on the trait it can be swapped or wrapped. This
+//! module also contains [`Recorder`] and [`Replayer`] which can be used to capture real responses
+//! from a hub to disk and later serve them again without any hardware. When targeting WASM the
+//! [`hyper`] client isn't available and [`FetchTransport`] is used instead.
+use std::path::{Path, PathBuf};
+
+/// The future returned by a [`Transport`] when sending a request.
+pub type ResponseFuture<'a> = std::pin::Pin<
+    Box<dyn std::future::Future<Output = anyhow::Result<http::Response<hyper::Body>>> + Send + 'a>,
+>;
+
+/// A [`Transport`] takes a fully built request, including headers and authorization, and returns
+/// the response from the hub.
+pub trait Transport: std::fmt::Debug + Send + Sync {
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<C> Transport for hyper::Client<C>
+where
+    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+{
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        Box::pin(async move { Ok(self.request(request).await?) })
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        (**self).send(request)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        (**self).send(request)
+    }
+}
+
+/// A [`ConcurrencyLimit`] wraps another [`Transport`] and only lets `max` requests be in flight at
+/// the same time. Other requests are queued and sent in the order they were made. The hub handles
+/// many simultaneous TLS sessions poorly so this is used by the
+/// [`HubBuilder`](crate::hub::HubBuilder) to keep every clone of a [`Hub`](crate::hub::Hub) on
+/// the same few connections. A connection is used until the response body has been read, so the
+/// next request is let through once the body is read or dropped.
+/// It's only available behind the `runtime` feature flag, which is enabled by default.
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+pub struct ConcurrencyLimit<T> {
+    inner: T,
+    permits: std::sync::Arc<tokio::sync::Semaphore>,
+}
+
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+impl<T: Transport> ConcurrencyLimit<T> {
+    /// Create a new [`ConcurrencyLimit`] allowing at most `max` requests at the same time. At
+    /// least one request is always allowed.
+    pub fn new(inner: T, max: usize) -> Self {
+        Self {
+            inner,
+            permits: std::sync::Arc::new(tokio::sync::Semaphore::new(max.max(1))),
+        }
+    }
+}
+
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+impl<T: Transport> Transport for ConcurrencyLimit<T> {
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let permit = self.permits.clone().acquire_owned().await?;
+            let (parts, body) = self.inner.send(request).await?.into_parts();
+
+            // The permit is released once the body has been read or dropped, which lets a body be
+            // streamed while the connection is still counted as used.
+            let body = futures_util::stream::unfold((body, permit), |(mut body, permit)| async {
+                let chunk = hyper::body::HttpBody::data(&mut body).await?;
+                Some((chunk, (body, permit)))
+            });
+
+            Ok(http::Response::from_parts(
+                parts,
+                hyper::Body::wrap_stream(body),
+            ))
+        })
+    }
+}
+
+/// A [`RateLimit`] wraps another [`Transport`] and sends at most `requests` requests within any
+/// `interval`. Requests exceeding the limit wait until the oldest request in the interval is old
+/// enough and are sent in the order they were made. It's used by
+/// [`HubBuilder::rate_limit`](crate::hub::HubBuilder::rate_limit) to avoid being answered as busy
+/// by the hub when a lot of requests are made in a short time.
+/// It's only available behind the `runtime` feature flag, which is enabled by default.
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+pub struct RateLimit<T> {
+    inner: T,
+    requests: usize,
+    interval: std::time::Duration,
+    sent: tokio::sync::Mutex<std::collections::VecDeque<tokio::time::Instant>>,
+}
+
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+impl<T: Transport> RateLimit<T> {
+    /// Create a new [`RateLimit`] allowing at most `requests` requests within `interval`. At
+    /// least one request is always allowed.
+    pub fn new(inner: T, requests: usize, interval: std::time::Duration) -> Self {
+        Self {
+            inner,
+            requests: requests.max(1),
+            interval,
+            sent: Default::default(),
+        }
+    }
+}
+
+#[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+impl<T: Transport> Transport for RateLimit<T> {
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            {
+                // The lock is held while waiting so requests are let through in order.
+                let mut sent = self.sent.lock().await;
+                loop {
+                    let now = tokio::time::Instant::now();
+                    while sent
+                        .front()
+                        .is_some_and(|at| now.duration_since(*at) >= self.interval)
+                    {
+                        sent.pop_front();
+                    }
+
+                    match sent.front() {
+                        Some(oldest) if sent.len() >= self.requests => {
+                            tokio::time::sleep_until(*oldest + self.interval).await;
+                        }
+                        _ => {
+                            sent.push_back(now);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            self.inner.send(request).await
+        })
+    }
+}
+
+/// A [`Logger`] wraps another [`Transport`] and logs every request and response, including the
+/// full bodies as pretty printed JSON, at trace level with the [`log`] crate. The bearer token is
+/// redacted. It's useful to capture payloads with attributes that aren't modeled yet. It's only
+/// available behind the `debug-http` feature flag, which also makes every
+/// [`Hub`](crate::hub::Hub) use it.
+#[cfg(feature = "debug-http")]
+#[derive(Debug)]
+pub struct Logger<T> {
+    inner: T,
+}
+
+#[cfg(feature = "debug-http")]
+impl<T: Transport> Logger<T> {
+    /// Create a new [`Logger`] that will forward all requests to `inner`.
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+#[cfg(feature = "debug-http")]
+impl<T: Transport> Transport for Logger<T> {
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+
+            let token = parts
+                .headers
+                .get(http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .filter(|token| !token.is_empty())
+                .map(str::to_string);
+            let redact = |text: String| match &token {
+                Some(token) => text.replace(token.as_str(), "<redacted>"),
+                None => text,
+            };
+
+            let headers: Vec<_> = parts
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    let value = String::from_utf8_lossy(value.as_bytes()).to_string();
+                    format!("{name}: {}", redact(value))
+                })
+                .collect();
+
+            log::trace!(
+                "{} {}\n{}\n\n{}",
+                parts.method,
+                parts.uri,
+                headers.join("\n"),
+                redact(pretty_body(&body)),
+            );
+
+            let method = parts.method.clone();
+            let uri = parts.uri.clone();
+
+            let result = self
+                .inner
+                .send(http::Request::from_parts(parts, hyper::Body::from(body)))
+                .await;
+
+            let response = match result {
+                Ok(response) => response,
+                Err(err) => {
+                    log::trace!("{method} {uri} failed: {err:#}");
+                    return Err(err);
+                }
+            };
+
+            let (parts, body) = response.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+
+            log::trace!(
+                "{method} {uri} -> {}\n\n{}",
+                parts.status,
+                redact(pretty_body(&body)),
+            );
+
+            Ok(http::Response::from_parts(parts, hyper::Body::from(body)))
+        })
+    }
+}
+
+/// Pretty print the body if it's JSON, otherwise show it as text.
+#[cfg(feature = "debug-http")]
+fn pretty_body(body: &[u8]) -> String {
+    serde_json::from_slice::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| serde_json::to_string_pretty(&json).ok())
+        .unwrap_or_else(|| String::from_utf8_lossy(body).to_string())
+}
+
+/// A [`Recorder`] wraps another [`Transport`] and will store the body of every successful response
+/// in the given directory. The stored responses can later be served by a [`Replayer`].
+#[derive(Debug)]
+pub struct Recorder<T> {
+    inner: T,
+    directory: PathBuf,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Create a new [`Recorder`] that will forward all requests to `inner` and store responses
+    /// in `directory`. The directory will be created if it doesn't exist.
+    pub fn new(inner: T, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            directory: directory.into(),
+        }
+    }
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let path = recording_path(&self.directory, &request);
+            let response = self.inner.send(request).await?;
+
+            let (parts, body) = response.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+
+            if parts.status.is_success() {
+                write_recording(&self.directory, &path, &body).await?;
+            }
+
+            Ok(http::Response::from_parts(parts, hyper::Body::from(body)))
+        })
+    }
+}
+
+/// A [`Replayer`] serves responses previously stored by a [`Recorder`] without ever talking to a
+/// hub. Requests that has no recorded response will fail.
+#[derive(Debug)]
+pub struct Replayer {
+    directory: PathBuf,
+}
+
+impl Replayer {
+    /// Create a new [`Replayer`] that will serve responses from `directory`.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl Transport for Replayer {
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let path = recording_path(&self.directory, &request);
+            let body = read_recording(&path).await.map_err(|err| {
+                anyhow::anyhow!(
+                    "no recorded response for {} {} in {}: {err}",
+                    request.method(),
+                    request.uri().path(),
+                    path.display(),
+                )
+            })?;
+
+            http::Response::builder()
+                .status(http::StatusCode::OK)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(hyper::Body::from(body))
+                .map_err(|err| anyhow::anyhow!(err))
+        })
+    }
+}
+
+/// Write a recorded response, with [`tokio::fs`] to not block the runtime if the `runtime`
+/// feature flag is enabled.
+async fn write_recording(
+    directory: &std::path::Path,
+    path: &std::path::Path,
+    body: &[u8],
+) -> std::io::Result<()> {
+    #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+    {
+        tokio::fs::create_dir_all(directory).await?;
+        tokio::fs::write(path, body).await
+    }
+
+    #[cfg(not(all(feature = "runtime", not(target_arch = "wasm32"))))]
+    {
+        std::fs::create_dir_all(directory)?;
+        std::fs::write(path, body)
+    }
+}
+
+/// Read a recorded response, see [`write_recording`].
+async fn read_recording(path: &std::path::Path) -> std::io::Result<Vec<u8>> {
+    #[cfg(all(feature = "runtime", not(target_arch = "wasm32")))]
+    {
+        tokio::fs::read(path).await
+    }
+
+    #[cfg(not(all(feature = "runtime", not(target_arch = "wasm32"))))]
+    {
+        std::fs::read(path)
+    }
+}
+
+/// Record responses on the first run and replay them on every run after that. If `directory`
+/// doesn't exist a [`Recorder`] wrapping `inner` is returned, otherwise a [`Replayer`]. Remove the
+/// directory to record new responses.
+pub fn record_or_replay<T: Transport + 'static>(
+    inner: T,
+    directory: impl Into<PathBuf>,
+) -> Box<dyn Transport> {
+    let directory = directory.into();
+
+    if directory.exists() {
+        Box::new(Replayer::new(directory))
+    } else {
+        Box::new(Recorder::new(inner, directory))
+    }
+}
+
+/// A [`FetchTransport`] sends requests with the browser's `fetch` API. Browsers can't talk to the
+/// hub directly since it uses a self signed certificate so the requests are sent to a proxy at
+/// `base_url` instead, keeping the path of the original request. The proxy is expected to forward
+/// the requests, including the `Authorization` header, to the hub. It's only available when
+/// targeting WASM.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone)]
+pub struct FetchTransport {
+    base_url: String,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl FetchTransport {
+    /// Create a new [`FetchTransport`] sending all requests to the proxy at `base_url`, f.ex.
+    /// `http://localhost:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Transport for FetchTransport {
+    fn send(&self, request: http::Request<hyper::Body>) -> ResponseFuture<'_> {
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let body = hyper::body::to_bytes(body).await?.to_vec();
+
+            let target = parts
+                .uri
+                .path_and_query()
+                .map(|pq| pq.as_str())
+                .unwrap_or_else(|| parts.uri.path());
+            let url = format!("{}{}", self.base_url, target);
+
+            // Browsers don't allow setting the user agent.
+            let headers = parts
+                .headers
+                .iter()
+                .filter(|(name, _)| *name != http::header::USER_AGENT)
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect();
+
+            // The JavaScript futures can't be sent between threads so the request is run on the
+            // local executor and the result is passed back over a channel.
+            let (sender, receiver) = futures_channel::oneshot::channel();
+            let method = parts.method.to_string();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let _ = sender.send(fetch(method, url, headers, body).await);
+            });
+
+            let (status, body) = receiver
+                .await?
+                .map_err(|err| anyhow::anyhow!("fetch failed: {err}"))?;
+
+            http::Response::builder()
+                .status(status)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(hyper::Body::from(body))
+                .map_err(|err| anyhow::anyhow!(err))
+        })
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn fetch(
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+) -> Result<(u16, Vec<u8>), String> {
+    use wasm_bindgen::JsCast;
+
+    let error = |err: wasm_bindgen::JsValue| format!("{err:?}");
+
+    let init = web_sys::RequestInit::new();
+    init.set_method(&method);
+    if !body.is_empty() {
+        init.set_body(&js_sys::Uint8Array::from(body.as_slice()).into());
+    }
+
+    let request = web_sys::Request::new_with_str_and_init(&url, &init).map_err(error)?;
+    for (name, value) in headers {
+        request.headers().set(&name, &value).map_err(error)?;
+    }
+
+    let window = web_sys::window().ok_or_else(|| "no window available".to_string())?;
+    let response: web_sys::Response =
+        wasm_bindgen_futures::JsFuture::from(window.fetch_with_request(&request))
+            .await
+            .map_err(error)?
+            .dyn_into()
+            .map_err(error)?;
+
+    let buffer = wasm_bindgen_futures::JsFuture::from(response.array_buffer().map_err(error)?)
+        .await
+        .map_err(error)?;
+
+    Ok((response.status(), js_sys::Uint8Array::new(&buffer).to_vec()))
+}
+
+fn recording_path(directory: &Path, request: &http::Request<hyper::Body>) -> PathBuf {
+    let uri = request.uri();
+    let target = uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or_else(|| uri.path());
+
+    let name: String = target
+        .trim_matches('/')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    directory.join(format!("{}_{}.json", request.method(), name))
+}
@@ -0,0 +1,176 @@
+//! Typed wrappers around a [`Device`] that can only be created when the device has the required
+//! capabilities, f.ex. with [`Device::as_dimmable`]. Since the capabilities are checked once when
+//! the wrapper is created its methods don't need to check them again, so code that holds f.ex. a
+//! [`Dimmable`] can't fail because the device can't receive a light level. Each wrapper holds a
+//! mutable reference to the [`Device`] which is updated on every successful change, just like
+//! the setters on the [`Hub`].
+use crate::color::Hs;
+use crate::device::Capability;
+use crate::hub::Hub;
+use crate::units::Percent;
+use crate::Device;
+
+fn can_receive(device: &Device, required: &[Capability]) -> bool {
+    let can_receive = &device.inner().capabilities.can_receive;
+
+    required
+        .iter()
+        .all(|capability| can_receive.contains(capability))
+}
+
+/// A [`Device`] that can be turned on and off.
+#[derive(Debug)]
+pub struct Light<'a> {
+    device: &'a mut Device,
+}
+
+impl<'a> Light<'a> {
+    pub(crate) fn new(device: &'a mut Device) -> Option<Self> {
+        can_receive(device, &[Capability::IsOn]).then_some(Self { device })
+    }
+
+    /// Get the wrapped [`Device`].
+    pub fn device(&self) -> &Device {
+        self.device
+    }
+
+    /// Check if the light is on.
+    pub fn is_on(&self) -> bool {
+        self.device.inner().attributes.is_on.unwrap_or(false)
+    }
+
+    /// Turn the light on or off.
+    pub async fn set_on(&mut self, hub: &mut Hub, is_on: bool) -> anyhow::Result<()> {
+        let inner = self.device.inner_mut();
+
+        hub.patch_attributes(&inner.id, serde_json::json!({ "isOn": is_on }))
+            .await?;
+
+        inner.attributes.is_on = Some(is_on);
+
+        Ok(())
+    }
+}
+
+/// A [`Device`] with a light level.
+#[derive(Debug)]
+pub struct Dimmable<'a> {
+    device: &'a mut Device,
+}
+
+impl<'a> Dimmable<'a> {
+    pub(crate) fn new(device: &'a mut Device) -> Option<Self> {
+        can_receive(device, &[Capability::LightLevel]).then_some(Self { device })
+    }
+
+    /// Get the wrapped [`Device`].
+    pub fn device(&self) -> &Device {
+        self.device
+    }
+
+    /// Get the current light level.
+    pub fn level(&self) -> Option<Percent> {
+        self.device.inner().attributes.light_level
+    }
+
+    /// Set the light level. Levels above 100% are set to 100% and 0% is set to 1% since the hub
+    /// doesn't accept it.
+    pub async fn set_level(&mut self, hub: &mut Hub, level: Percent) -> anyhow::Result<()> {
+        let level = level.clamp(Percent::MIN_LIGHT_LEVEL, Percent::MAX);
+        let inner = self.device.inner_mut();
+
+        hub.patch_attributes(&inner.id, serde_json::json!({ "lightLevel": level }))
+            .await?;
+
+        inner.attributes.light_level = Some(level);
+
+        Ok(())
+    }
+}
+
+/// A [`Device`] with hue and saturation.
+#[derive(Debug)]
+pub struct Colorable<'a> {
+    device: &'a mut Device,
+}
+
+impl<'a> Colorable<'a> {
+    pub(crate) fn new(device: &'a mut Device) -> Option<Self> {
+        can_receive(device, &[Capability::ColorHue, Capability::ColorSaturation])
+            .then_some(Self { device })
+    }
+
+    /// Get the wrapped [`Device`].
+    pub fn device(&self) -> &Device {
+        self.device
+    }
+
+    /// Get the current color.
+    pub fn color(&self) -> Option<Hs> {
+        let attributes = &self.device.inner().attributes;
+
+        Some(Hs {
+            hue: attributes.color_hue?,
+            saturation: attributes.color_saturation?,
+        })
+    }
+
+    /// Set the color from anything that can be converted to hue and saturation, f.ex.
+    /// [`Rgb`](crate::color::Rgb). The color is [`Hs::normalized`] first, just like with
+    /// [`Hub::set_color`].
+    pub async fn set_color(&mut self, hub: &mut Hub, color: impl Into<Hs>) -> anyhow::Result<()> {
+        let Hs { hue, saturation } = color.into().normalized();
+        let inner = self.device.inner_mut();
+
+        hub.patch_attributes(
+            &inner.id,
+            serde_json::json!({ "colorHue": hue, "colorSaturation": saturation }),
+        )
+        .await?;
+
+        inner.attributes.color_hue = Some(hue);
+        inner.attributes.color_saturation = Some(saturation);
+
+        Ok(())
+    }
+}
+
+/// A [`Device`] with a blinds level.
+#[derive(Debug)]
+pub struct Blind<'a> {
+    device: &'a mut Device,
+}
+
+impl<'a> Blind<'a> {
+    pub(crate) fn new(device: &'a mut Device) -> Option<Self> {
+        can_receive(device, &[Capability::BlindsTargetLevel]).then_some(Self { device })
+    }
+
+    /// Get the wrapped [`Device`].
+    pub fn device(&self) -> &Device {
+        self.device
+    }
+
+    /// Get the current level of the blinds.
+    pub fn level(&self) -> Option<Percent> {
+        self.device.inner().attributes.blinds_current_level
+    }
+
+    /// Get the level the blinds are moving to.
+    pub fn target_level(&self) -> Option<Percent> {
+        self.device.inner().attributes.blinds_target_level
+    }
+
+    /// Set the level the blinds should move to. Levels above 100% are set to 100%.
+    pub async fn set_level(&mut self, hub: &mut Hub, level: Percent) -> anyhow::Result<()> {
+        let level = level.min(Percent::MAX);
+        let inner = self.device.inner_mut();
+
+        hub.patch_attributes(&inner.id, serde_json::json!({ "blindsTargetLevel": level }))
+            .await?;
+
+        inner.attributes.blinds_target_level = Some(level);
+
+        Ok(())
+    }
+}
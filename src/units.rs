@@ -0,0 +1,119 @@
+//! Light-weight wrappers for the units used by device attributes and setters so a value in one
+//! unit can't be passed where another is expected, f.ex. a color temperature where a light level
+//! is expected. All units are serialized as the plain number the hub uses.
+use serde::{Deserialize, Serialize};
+
+/// A percentage between 0 and 100, used for f.ex. light level and blinds level.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct Percent(pub u8);
+
+impl Percent {
+    /// The highest valid percentage.
+    pub const MAX: Percent = Percent(100);
+
+    /// The lowest light level accepted by the hub. Turn the light off instead of setting the
+    /// level to zero.
+    pub const MIN_LIGHT_LEVEL: Percent = Percent(1);
+
+    /// Check if the percentage is between 0 and 100.
+    pub fn is_valid(self) -> bool {
+        self <= Self::MAX
+    }
+
+    /// Check if the percentage is a light level accepted by the hub, between 1 and 100.
+    pub fn is_valid_light_level(self) -> bool {
+        (Self::MIN_LIGHT_LEVEL..=Self::MAX).contains(&self)
+    }
+}
+
+impl std::fmt::Display for Percent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+/// A color temperature in kelvin, which is what the hub uses for color temperature.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct Kelvin(pub u16);
+
+impl Kelvin {
+    /// Convert the color temperature to [`Mireds`].
+    pub fn to_mireds(self) -> Mireds {
+        Mireds(reciprocal_mega(self.0))
+    }
+}
+
+impl From<Mireds> for Kelvin {
+    fn from(mireds: Mireds) -> Self {
+        mireds.to_kelvin()
+    }
+}
+
+impl std::fmt::Display for Kelvin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} K", self.0)
+    }
+}
+
+/// A color temperature in mireds (micro reciprocal degrees), commonly used by Zigbee and other
+/// integrations such as Home Assistant. A higher value is a warmer color.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct Mireds(pub u16);
+
+impl Mireds {
+    /// Convert the color temperature to [`Kelvin`].
+    pub fn to_kelvin(self) -> Kelvin {
+        Kelvin(reciprocal_mega(self.0))
+    }
+}
+
+impl From<Kelvin> for Mireds {
+    fn from(kelvin: Kelvin) -> Self {
+        kelvin.to_mireds()
+    }
+}
+
+impl std::fmt::Display for Mireds {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} mired", self.0)
+    }
+}
+
+/// A temperature in degrees Celsius, used by f.ex. environment sensors.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(transparent)]
+pub struct Celsius(pub f32);
+
+impl Celsius {
+    /// Convert the temperature to degrees Fahrenheit.
+    pub fn to_fahrenheit(self) -> f32 {
+        self.0 * 9.0 / 5.0 + 32.0
+    }
+}
+
+impl std::fmt::Display for Celsius {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} °C", self.0)
+    }
+}
+
+/// Kelvin and mireds convert to each other the same way, `1 000 000 / value`, rounded to the
+/// nearest integer. Zero is treated as one to avoid dividing by zero.
+fn reciprocal_mega(value: u16) -> u16 {
+    let value = u32::from(value.max(1));
+
+    u16::try_from((1_000_000 + value / 2) / value).unwrap_or(u16::MAX)
+}